@@ -8,9 +8,12 @@
 #![feature(hash_drain_filter)]
 use std::sync::Arc;
 
+use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
+use fbinit::FacebookInit;
 use sharding_ext::RepoShard;
+use tokio::runtime::Handle;
 
 pub mod args;
 #[cfg(fbcode_build)]
@@ -67,3 +70,34 @@ pub trait RepoShardedProcessExecutor: Send + Sync {
     /// SHOULD return quickly (i.e. should not be long running).
     async fn stop(&self) -> Result<()>;
 }
+
+/// Run the process for a single repo to completion, without any sharding
+/// infrastructure. This is intended for local testing of sharded processes:
+/// the process is set up for `repo_name`, executed on `runtime_handle` until
+/// the execution returns, and then stopped. Errors from any of these steps
+/// are returned with the repo name attached.
+pub async fn run_single_shard(
+    _fb: FacebookInit,
+    runtime_handle: Handle,
+    process: Arc<dyn RepoShardedProcess>,
+    repo_name: &str,
+) -> Result<()> {
+    let repo_shard = RepoShard::with_repo_name(repo_name);
+    let executor = process
+        .setup(&repo_shard)
+        .await
+        .with_context(|| format!("Failed to set up repo {}", repo_name))?;
+    let execution = runtime_handle
+        .spawn({
+            let executor = executor.clone();
+            async move { executor.execute().await }
+        })
+        .await
+        .with_context(|| format!("Execution of repo {} panicked or was cancelled", repo_name))?
+        .with_context(|| format!("Failed to execute repo {}", repo_name));
+    let stop = executor
+        .stop()
+        .await
+        .with_context(|| format!("Failed to stop repo {}", repo_name));
+    execution.and(stop)
+}
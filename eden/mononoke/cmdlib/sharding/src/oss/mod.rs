@@ -6,40 +6,235 @@
  */
 
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
+use anyhow::Context;
 use anyhow::Result;
 use fbinit::FacebookInit;
+use sharding_ext::RepoShard;
+use slog::error;
+use slog::info;
+use slog::warn;
 use slog::Logger;
 use tokio::runtime::Handle;
+use tokio::sync::watch;
 
 use crate::RepoShardedProcess;
 
-pub struct ShardedProcessExecutor {}
+/// How often the termination flag is checked while the shard is executing.
+const TERMINATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Handle used to request a graceful shutdown of a [`ShardedProcessExecutor`].
+///
+/// Once shutdown is requested, the executor does not set up any new shard
+/// and stops the executing one by calling [`RepoShardedProcessExecutor::stop`].
+/// The execution is then allowed to finish for up to the executor's
+/// `timeout_secs`, after which it is forcibly dropped.
+///
+/// [`RepoShardedProcessExecutor::stop`]: crate::RepoShardedProcessExecutor::stop
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    /// Request the executor to shut down. This returns immediately and does
+    /// not wait for the shutdown to complete.
+    pub fn shutdown(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether shutdown has been requested.
+    pub fn is_shutdown_requested(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    fn subscribe(&self) -> watch::Receiver<bool> {
+        self.sender.subscribe()
+    }
+}
+
+/// Executor for sharded processes in builds without ShardManager.
+///
+/// There is no ShardManager to assign shards, so the executor runs the process
+/// for a single shard whose id is given by the service name (for example,
+/// `--sharded-service-name=myrepo`). The shard id is parsed the same way as
+/// ShardManager shard ids, so chunked and cross-repo shards are supported.
+pub struct ShardedProcessExecutor {
+    runtime_handle: Handle,
+    timeout_secs: u64,
+    process: Arc<dyn RepoShardedProcess>,
+    repo_shard: RepoShard,
+    shutdown: ShutdownHandle,
+}
 
 impl ShardedProcessExecutor {
     pub fn new(
         _fb: FacebookInit,
-        _runtime_handle: Handle,
-        _logger: &Logger,
-        _service_name: &'static str,
+        runtime_handle: Handle,
+        logger: &Logger,
+        service_name: &'static str,
         _service_scope: &'static str,
-        _timeout_secs: u64,
-        _bp_handle: Arc<dyn RepoShardedProcess>,
+        timeout_secs: u64,
+        bp_handle: Arc<dyn RepoShardedProcess>,
         _shard_healing: bool,
     ) -> Result<Self> {
-        unimplemented!("ShardedProcessExecutor is supported only for fbcode build")
+        let repo_shard = RepoShard::from_shard_id(service_name)
+            .with_context(|| format!("Invalid shard id {}", service_name))?;
+        info!(
+            logger,
+            "ShardManager is not available in this build. Executing single shard {}", repo_shard
+        );
+        Ok(Self {
+            runtime_handle,
+            timeout_secs,
+            process: bp_handle,
+            repo_shard,
+            shutdown: ShutdownHandle::new(),
+        })
     }
 
+    /// Get a handle that can be used to gracefully shut down the executor,
+    /// for example on SIGTERM. See [`ShutdownHandle`] for the shutdown
+    /// contract.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Set up and execute the process for the shard, blocking until the
+    /// execution completes, `terminate_process` is set or shutdown is
+    /// requested via the [`ShutdownHandle`]. On termination, the process is
+    /// stopped and given `timeout_secs` to finish before its execution is
+    /// dropped.
     pub async fn block_and_execute(
         &mut self,
-        _logger: &Logger,
-        _terminate_process: Arc<AtomicBool>,
+        logger: &Logger,
+        terminate_process: Arc<AtomicBool>,
     ) -> Result<()> {
-        unimplemented!("ShardedProcessExecutor is supported only for fbcode build")
+        execute_shard(
+            logger,
+            &self.runtime_handle,
+            self.process.as_ref(),
+            &self.repo_shard,
+            self.timeout_secs,
+            terminate_process,
+            self.shutdown.subscribe(),
+        )
+        .await
+    }
+
+    /// Execute the process for the shard in the background, without
+    /// blocking. Errors are logged.
+    pub fn execute(&mut self, logger: &Logger) {
+        let logger = logger.clone();
+        let runtime_handle = self.runtime_handle.clone();
+        let process = self.process.clone();
+        let repo_shard = self.repo_shard.clone();
+        let timeout_secs = self.timeout_secs;
+        let shutdown = self.shutdown.subscribe();
+        self.runtime_handle.spawn(async move {
+            if let Err(e) = execute_shard(
+                &logger,
+                &runtime_handle,
+                process.as_ref(),
+                &repo_shard,
+                timeout_secs,
+                Arc::new(AtomicBool::new(false)),
+                shutdown,
+            )
+            .await
+            {
+                error!(logger, "Execution of shard {} failed: {:?}", repo_shard, e);
+            }
+        });
+    }
+}
+
+async fn execute_shard(
+    logger: &Logger,
+    runtime_handle: &Handle,
+    process: &dyn RepoShardedProcess,
+    repo_shard: &RepoShard,
+    timeout_secs: u64,
+    terminate_process: Arc<AtomicBool>,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    if *shutdown.borrow() {
+        info!(
+            logger,
+            "Shutdown requested. Not setting up shard {}", repo_shard
+        );
+        return Ok(());
+    }
+
+    info!(logger, "Setting up shard {}", repo_shard);
+    let executor = process
+        .setup(repo_shard)
+        .await
+        .with_context(|| format!("Failed to set up shard {}", repo_shard))?;
+
+    info!(logger, "Executing shard {}", repo_shard);
+    let mut execution = runtime_handle.spawn({
+        let executor = executor.clone();
+        async move { executor.execute().await }
+    });
+
+    let mut poll_interval = tokio::time::interval(TERMINATION_POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            result = &mut execution => {
+                info!(logger, "Finished executing shard {}", repo_shard);
+                return result
+                    .context("Shard execution panicked or was cancelled")?
+                    .with_context(|| format!("Failed to execute shard {}", repo_shard));
+            }
+            _ = poll_interval.tick() => {
+                if terminate_process.load(Ordering::Relaxed) || *shutdown.borrow() {
+                    break;
+                }
+            }
+            Ok(()) = shutdown.changed() => {
+                if *shutdown.borrow() {
+                    break;
+                }
+            }
+        }
     }
 
-    pub fn execute(&mut self, _logger: &Logger) {
-        unimplemented!("ShardedProcessExecutor is supported only for fbcode build")
+    info!(logger, "Terminating execution of shard {}", repo_shard);
+    // A failure to stop must not leave the execution running detached, so
+    // it is still given the timeout and dropped before reporting the error.
+    let stop_result = executor
+        .stop()
+        .await
+        .with_context(|| format!("Failed to stop shard {}", repo_shard));
+    if let Err(e) = &stop_result {
+        error!(logger, "{:?}", e);
+    }
+    match tokio::time::timeout(Duration::from_secs(timeout_secs), &mut execution).await {
+        Ok(result) => {
+            result
+                .context("Shard execution panicked or was cancelled")?
+                .with_context(|| format!("Failed to execute shard {}", repo_shard))?;
+        }
+        Err(_) => {
+            warn!(
+                logger,
+                "Shard {} did not finish within {}s after being stopped. Dropping its execution",
+                repo_shard,
+                timeout_secs
+            );
+            execution.abort();
+        }
     }
+    stop_result
 }
@@ -207,4 +207,30 @@ impl CommitGraphStorage for BufferedCommitGraphStorage {
             .into_iter()
             .collect())
     }
+
+    async fn fetch_many_children(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        let mut children: HashMap<ChangesetId, HashSet<ChangesetId>> = HashMap::new();
+        for (cs_id, cs_children) in self
+            .in_memory_storage
+            .fetch_many_children(ctx, cs_ids)
+            .await?
+            .into_iter()
+            .chain(
+                self.persistent_storage
+                    .fetch_many_children(ctx, cs_ids)
+                    .await?
+                    .into_iter(),
+            )
+        {
+            children.entry(cs_id).or_default().extend(cs_children);
+        }
+        Ok(children
+            .into_iter()
+            .map(|(cs_id, cs_children)| (cs_id, cs_children.into_iter().collect()))
+            .collect())
+    }
 }
@@ -249,6 +249,18 @@ impl CachingCommitGraphStorage {
         Self::new(storage, CacheHandlerFactory::Mocked)
     }
 
+    async fn fill_cache(
+        &self,
+        ctx: &CoreContext,
+        many_edges: impl IntoIterator<Item = ChangesetEdges>,
+    ) {
+        let entries: HashMap<ChangesetId, CachedChangesetEdges> = many_edges
+            .into_iter()
+            .map(|edges| (edges.node.cs_id, CachedChangesetEdges::fetched(edges)))
+            .collect();
+        fill_cache(&self.request(ctx, Prefetch::None), &entries).await;
+    }
+
     fn request<'a>(&'a self, ctx: &'a CoreContext, prefetch: Prefetch) -> CacheRequest<'a> {
         let prefetch = if tunables().disable_commit_graph_prefetch().unwrap_or(false) {
             Prefetch::None
@@ -289,11 +301,17 @@ impl CommitGraphStorage for CachingCommitGraphStorage {
     }
 
     async fn add(&self, ctx: &CoreContext, edges: ChangesetEdges) -> Result<bool> {
-        self.storage.add(ctx, edges).await
+        let added = self.storage.add(ctx, edges.clone()).await?;
+        // Edges are immutable once stored, so they can be inserted into the
+        // cache straight away, saving a fetch when they are traversed.
+        self.fill_cache(ctx, [edges]).await;
+        Ok(added)
     }
 
     async fn add_many(&self, ctx: &CoreContext, many_edges: Vec1<ChangesetEdges>) -> Result<usize> {
-        self.storage.add_many(ctx, many_edges).await
+        let added = self.storage.add_many(ctx, many_edges.clone()).await?;
+        self.fill_cache(ctx, many_edges).await;
+        Ok(added)
     }
 
     async fn fetch_edges(
@@ -374,4 +392,16 @@ impl CommitGraphStorage for CachingCommitGraphStorage {
     ) -> Result<Vec<ChangesetId>> {
         self.storage.fetch_children(ctx, cs_id).await
     }
+
+    async fn count_children(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<usize> {
+        self.storage.count_children(ctx, cs_id).await
+    }
+
+    async fn fetch_many_children(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        self.storage.fetch_many_children(ctx, cs_ids).await
+    }
 }
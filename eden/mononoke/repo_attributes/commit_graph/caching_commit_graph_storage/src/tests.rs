@@ -5,18 +5,29 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
 use std::future::Future;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use anyhow::Result;
+use async_trait::async_trait;
+use commit_graph_testlib::utils::*;
 use commit_graph_testlib::*;
+use commit_graph_types::edges::ChangesetEdges;
 use commit_graph_types::storage::CommitGraphStorage;
+use commit_graph_types::storage::Prefetch;
 use context::CoreContext;
 use fbinit::FacebookInit;
+use mononoke_types::ChangesetId;
+use mononoke_types::ChangesetIdPrefix;
+use mononoke_types::ChangesetIdsResolvedFromPrefix;
 use mononoke_types::RepositoryId;
 use rendezvous::RendezVousOptions;
 use sql_commit_graph_storage::SqlCommitGraphStorageBuilder;
 use sql_construct::SqlConstruct;
+use vec1::Vec1;
 
 use crate::CachingCommitGraphStorage;
 
@@ -39,3 +50,161 @@ where
 }
 
 impl_commit_graph_tests!(run_test);
+
+/// Storage wrapper that counts the number of edge fetches that reach the
+/// inner storage.
+struct CountingCommitGraphStorage {
+    storage: Arc<dyn CommitGraphStorage>,
+    fetches: AtomicUsize,
+}
+
+impl CountingCommitGraphStorage {
+    fn new(storage: Arc<dyn CommitGraphStorage>) -> Self {
+        Self {
+            storage,
+            fetches: AtomicUsize::new(0),
+        }
+    }
+
+    fn fetches(&self) -> usize {
+        self.fetches.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl CommitGraphStorage for CountingCommitGraphStorage {
+    fn repo_id(&self) -> RepositoryId {
+        self.storage.repo_id()
+    }
+
+    async fn add(&self, ctx: &CoreContext, edges: ChangesetEdges) -> Result<bool> {
+        self.storage.add(ctx, edges).await
+    }
+
+    async fn add_many(&self, ctx: &CoreContext, many_edges: Vec1<ChangesetEdges>) -> Result<usize> {
+        self.storage.add_many(ctx, many_edges).await
+    }
+
+    async fn fetch_edges(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Option<ChangesetEdges>> {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        self.storage.fetch_edges(ctx, cs_id).await
+    }
+
+    async fn fetch_edges_required(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<ChangesetEdges> {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        self.storage.fetch_edges_required(ctx, cs_id).await
+    }
+
+    async fn fetch_many_edges(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+        prefetch: Prefetch,
+    ) -> Result<HashMap<ChangesetId, ChangesetEdges>> {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        self.storage.fetch_many_edges(ctx, cs_ids, prefetch).await
+    }
+
+    async fn fetch_many_edges_required(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+        prefetch: Prefetch,
+    ) -> Result<HashMap<ChangesetId, ChangesetEdges>> {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        self.storage
+            .fetch_many_edges_required(ctx, cs_ids, prefetch)
+            .await
+    }
+
+    async fn find_by_prefix(
+        &self,
+        ctx: &CoreContext,
+        cs_prefix: ChangesetIdPrefix,
+        limit: usize,
+    ) -> Result<ChangesetIdsResolvedFromPrefix> {
+        self.storage.find_by_prefix(ctx, cs_prefix, limit).await
+    }
+
+    async fn fetch_children(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Vec<ChangesetId>> {
+        self.storage.fetch_children(ctx, cs_id).await
+    }
+
+    async fn fetch_many_children(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        self.storage.fetch_many_children(ctx, cs_ids).await
+    }
+}
+
+fn counting_storage() -> Arc<CountingCommitGraphStorage> {
+    Arc::new(CountingCommitGraphStorage::new(Arc::new(
+        SqlCommitGraphStorageBuilder::with_sqlite_in_memory()
+            .unwrap()
+            .build(RendezVousOptions::for_test(), RepositoryId::new(1)),
+    )))
+}
+
+#[fbinit::test]
+async fn test_caching_storage_hits_cache(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let counting = counting_storage();
+    let storage = CachingCommitGraphStorage::mocked(counting.clone());
+
+    // Populate the inner storage directly, so that nothing is cached yet.
+    from_dag(&ctx, "A-B-C-D", counting.clone()).await?;
+    let fetches = counting.fetches();
+
+    // The first fetch goes to the inner storage, the second hits the cache.
+    assert!(storage.fetch_edges(&ctx, name_cs_id("A")).await?.is_some());
+    assert_eq!(counting.fetches(), fetches + 1);
+    assert!(storage.fetch_edges(&ctx, name_cs_id("A")).await?.is_some());
+    assert_eq!(counting.fetches(), fetches + 1);
+
+    // Fetching many edges populates the cache for every returned id.
+    let ids = [name_cs_id("B"), name_cs_id("C")];
+    assert_eq!(
+        storage
+            .fetch_many_edges(&ctx, &ids, Prefetch::None)
+            .await?
+            .len(),
+        2
+    );
+    assert_eq!(counting.fetches(), fetches + 2);
+    assert!(storage.fetch_edges(&ctx, name_cs_id("B")).await?.is_some());
+    assert!(storage.fetch_edges(&ctx, name_cs_id("C")).await?.is_some());
+    assert_eq!(counting.fetches(), fetches + 2);
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_caching_storage_add_fills_cache(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let counting = counting_storage();
+    let storage = Arc::new(CachingCommitGraphStorage::mocked(counting.clone()));
+
+    // Edges added through the caching storage are inserted into the cache.
+    from_dag(&ctx, "A-B-C", storage.clone()).await?;
+    let fetches = counting.fetches();
+    for name in ["A", "B", "C"] {
+        assert!(storage.fetch_edges(&ctx, name_cs_id(name)).await?.is_some());
+    }
+    assert_eq!(counting.fetches(), fetches);
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use caching_commit_graph_storage::CachingCommitGraphStorage;
+use caching_ext::CacheHandlerFactory;
+use commit_graph_types::storage::CommitGraphStorage;
+use commit_graph_types::storage::Prefetch;
+
+use crate::CommitGraph;
+use crate::CommitGraphError;
+
+/// Name of the cachelib pool used when a cache size is set.
+const CACHE_POOL_NAME: &str = "commit_graph";
+
+/// Builder for a `CommitGraph`, gathering its storage, caching and prefetch
+/// settings in one place.
+///
+/// A storage must be provided. Any setting that isn't set keeps its
+/// default, so `CommitGraph::new(storage)` is equivalent to
+/// `CommitGraphBuilder::new().with_storage(storage).build()`.
+pub struct CommitGraphBuilder {
+    storage: Option<Arc<dyn CommitGraphStorage>>,
+    cache_size: Option<usize>,
+    default_prefetch: Prefetch,
+}
+
+impl CommitGraphBuilder {
+    pub fn new() -> Self {
+        CommitGraphBuilder {
+            storage: None,
+            cache_size: None,
+            default_prefetch: Prefetch::for_p1_linear_traversal(),
+        }
+    }
+
+    /// Set the storage back-end where the commits are stored.
+    pub fn with_storage(mut self, storage: Arc<dyn CommitGraphStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Cache the edges fetched from the storage in a local cachelib pool of
+    /// the given size in bytes, by wrapping the storage in a
+    /// `CachingCommitGraphStorage`.
+    ///
+    /// Cachelib must be initialized before the graph is built. The size of
+    /// an existing pool can't be changed, so building fails with
+    /// `CommitGraphError::CachePoolExists` if the "commit_graph" pool
+    /// already exists.
+    pub fn with_cache_size(mut self, size: usize) -> Self {
+        self.cache_size = Some(size);
+        self
+    }
+
+    /// Set the prefetch hint used when fetching the edges of the changesets
+    /// visited by traversals. Defaults to prefetching along first parents.
+    pub fn with_default_prefetch(mut self, prefetch: Prefetch) -> Self {
+        self.default_prefetch = prefetch;
+        self
+    }
+
+    /// Build the commit graph, failing with `CommitGraphError::MissingStorage`
+    /// if no storage was provided.
+    pub fn build(self) -> Result<CommitGraph> {
+        let mut storage = self.storage.ok_or(CommitGraphError::MissingStorage)?;
+        if let Some(cache_size) = self.cache_size {
+            if cachelib::get_volatile_pool(CACHE_POOL_NAME)?.is_some() {
+                return Err(CommitGraphError::CachePoolExists {
+                    name: CACHE_POOL_NAME.to_string(),
+                }
+                .into());
+            }
+            let cachelib_pool = cachelib::get_or_create_volatile_pool(CACHE_POOL_NAME, cache_size)?;
+            storage = Arc::new(CachingCommitGraphStorage::new(
+                storage,
+                CacheHandlerFactory::Local { cachelib_pool },
+            ));
+        }
+        Ok(CommitGraph {
+            storage,
+            default_prefetch: self.default_prefetch,
+        })
+    }
+}
+
+impl Default for CommitGraphBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -54,7 +54,7 @@ impl CommitGraph {
                 p1_linear_depth = parent_edge.node.p1_linear_depth + 1;
             } else if let Some(previous_parent) = skip_tree_parent {
                 skip_tree_parent = self
-                    .lowest_common_ancestor(
+                    .tree_lowest_common_ancestor(
                         ctx,
                         previous_parent.cs_id,
                         parent_edge.node.cs_id,
@@ -236,8 +236,9 @@ impl CommitGraph {
         .await
     }
 
-    /// Returns the lowest common ancestor of two changesets.
-    async fn lowest_common_ancestor<F, G, H>(
+    /// Returns the lowest common ancestor of two changesets in the tree
+    /// described by the given parent, skew ancestor and depth closures.
+    async fn tree_lowest_common_ancestor<F, G, H>(
         &self,
         ctx: &CoreContext,
         cs_id1: ChangesetId,
@@ -323,7 +324,7 @@ impl CommitGraph {
         cs_id1: ChangesetId,
         cs_id2: ChangesetId,
     ) -> Result<Option<ChangesetNode>> {
-        self.lowest_common_ancestor(
+        self.tree_lowest_common_ancestor(
             ctx,
             cs_id1,
             cs_id2,
@@ -341,7 +342,7 @@ impl CommitGraph {
         cs_id1: ChangesetId,
         cs_id2: ChangesetId,
     ) -> Result<Option<ChangesetNode>> {
-        self.lowest_common_ancestor(
+        self.tree_lowest_common_ancestor(
             ctx,
             cs_id1,
             cs_id2,
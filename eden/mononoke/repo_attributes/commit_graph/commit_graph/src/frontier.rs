@@ -9,17 +9,103 @@ use anyhow::anyhow;
 use anyhow::Result;
 use borrowed::borrowed;
 use commit_graph_types::edges::ChangesetNode;
+use commit_graph_types::edges::ChangesetNodeParents;
 use commit_graph_types::frontier::ChangesetFrontier;
 use commit_graph_types::storage::Prefetch;
 use context::CoreContext;
 use futures::future;
+use futures::stream;
 use futures::Future;
+use futures::StreamExt;
+use futures::TryStreamExt;
 use mononoke_types::ChangesetId;
 use mononoke_types::Generation;
 
 use crate::CommitGraph;
 
+/// A frontier of changesets, ordered by generation number, that can be
+/// combined with other frontiers and lowered through the commit graph it
+/// was created from.
+///
+/// Obtained from `CommitGraph::frontier`.
+#[derive(Clone)]
+pub struct Frontier {
+    commit_graph: CommitGraph,
+    frontier: ChangesetFrontier,
+}
+
+impl Frontier {
+    /// Returns all changesets in the frontier.
+    pub fn changesets(&self) -> Vec<ChangesetId> {
+        self.frontier.changesets()
+    }
+
+    /// Returns all changesets in the frontier together with their
+    /// generation numbers, in increasing generation order.
+    pub fn changesets_with_generations(&self) -> Vec<(ChangesetId, Generation)> {
+        self.frontier.clone().into_flat_iter().collect()
+    }
+
+    /// Returns true if the frontier contains no changesets.
+    pub fn is_empty(&self) -> bool {
+        self.frontier.is_empty()
+    }
+
+    /// Returns the highest generation of any changeset in the frontier.
+    pub fn highest_generation(&self) -> Option<Generation> {
+        self.frontier
+            .last_key_value()
+            .map(|(generation, _)| *generation)
+    }
+
+    /// Returns a frontier of the changesets that are in either frontier.
+    pub fn union(&self, other: &Frontier) -> Frontier {
+        let mut frontier = self.frontier.clone();
+        frontier.extend(other.frontier.clone().into_flat_iter());
+        Frontier {
+            commit_graph: self.commit_graph.clone(),
+            frontier,
+        }
+    }
+
+    /// Returns a frontier of the changesets that are in both frontiers.
+    pub fn intersection(&self, other: &Frontier) -> Frontier {
+        let frontier = self
+            .frontier
+            .clone()
+            .into_flat_iter()
+            .filter(|(cs_id, generation)| {
+                other
+                    .frontier
+                    .get(generation)
+                    .is_some_and(|cs_ids| cs_ids.contains(cs_id))
+            })
+            .collect();
+        Frontier {
+            commit_graph: self.commit_graph.clone(),
+            frontier,
+        }
+    }
+
+    /// Lower the frontier so that it contains the highest ancestors of the
+    /// frontier that have a generation number less than or equal to
+    /// `generation`.
+    pub async fn lower_to(&mut self, ctx: &CoreContext, generation: Generation) -> Result<()> {
+        self.commit_graph
+            .lower_frontier(ctx, &mut self.frontier, generation)
+            .await
+    }
+}
+
 impl CommitGraph {
+    /// Returns the frontier of the given changesets, which must all exist.
+    pub async fn frontier(&self, ctx: &CoreContext, cs_ids: Vec<ChangesetId>) -> Result<Frontier> {
+        Ok(Frontier {
+            commit_graph: self.clone(),
+            frontier: self.changeset_frontier(ctx, cs_ids).await?,
+        })
+    }
+
     /// Obtain a frontier of changesets from a single changeset id, which must
     /// exist.
     pub(crate) async fn single_frontier(
@@ -33,7 +119,7 @@ impl CommitGraph {
 
     /// Obtain a frontier of changesets from a list of changeset ids, which
     /// must all exist.
-    pub(crate) async fn frontier(
+    pub(crate) async fn changeset_frontier(
         &self,
         ctx: &CoreContext,
         cs_ids: Vec<ChangesetId>,
@@ -121,6 +207,73 @@ impl CommitGraph {
         }
     }
 
+    /// Same as `lower_frontier_step`, but evaluates the property for up to
+    /// `concurrency` changesets of the popped generation at a time.
+    ///
+    /// The frontier is only updated once the property has been evaluated
+    /// for all of the popped changesets, so the result is the same as for
+    /// `lower_frontier_step`.
+    pub(crate) async fn lower_frontier_step_concurrent<Property, Out>(
+        &self,
+        ctx: &CoreContext,
+        frontier: &mut ChangesetFrontier,
+        property: Property,
+        prefetch: Prefetch,
+        concurrency: usize,
+    ) -> Result<Option<Vec<ChangesetId>>>
+    where
+        Property: Fn(ChangesetNode) -> Out + Send + Sync,
+        Out: Future<Output = Result<bool>>,
+    {
+        match frontier.pop_last() {
+            None => Ok(None),
+            Some((_, cs_ids)) => {
+                let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
+                let frontier_edges = self
+                    .storage
+                    .fetch_many_edges_required(ctx, &cs_ids, prefetch)
+                    .await?;
+
+                let property = &property;
+                // For each changeset, either it satisfies the property, or
+                // the nodes it should be lowered to are returned.
+                let outcomes: Vec<(Option<ChangesetId>, ChangesetNodeParents)> =
+                    stream::iter(frontier_edges.into_values())
+                        .map(|edges| async move {
+                            if property(edges.node).await? {
+                                return anyhow::Ok((Some(edges.node.cs_id), Default::default()));
+                            }
+                            match edges
+                                .lowest_skip_tree_edge_with(|node| async move {
+                                    Ok(!property(node).await?)
+                                })
+                                .await?
+                            {
+                                Some(ancestor) => Ok((None, [ancestor].into_iter().collect())),
+                                None => Ok((None, edges.parents)),
+                            }
+                        })
+                        .buffer_unordered(concurrency.max(1))
+                        .try_collect()
+                        .await?;
+
+                let mut property_frontier: Vec<_> = Default::default();
+
+                for (satisfying, lowered) in outcomes {
+                    property_frontier.extend(satisfying);
+                    for node in lowered {
+                        frontier
+                            .entry(node.generation)
+                            .or_default()
+                            .insert(node.cs_id);
+                    }
+                }
+
+                Ok(Some(property_frontier))
+            }
+        }
+    }
+
     /// Lower a frontier so that it contains the highest ancestors of the
     /// frontier that have a generation number less than or equal to
     /// `generation`.
@@ -160,7 +313,7 @@ impl CommitGraph {
             let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
             let frontier_edges = self
                 .storage
-                .fetch_many_edges_required(ctx, &cs_ids, Prefetch::for_p1_linear_traversal())
+                .fetch_many_edges_required(ctx, &cs_ids, self.default_prefetch)
                 .await?;
 
             for cs_id in cs_ids {
@@ -178,4 +331,66 @@ impl CommitGraph {
         }
         Ok(())
     }
+
+    /// Returns the children of the given changesets, together with their
+    /// generation numbers.
+    pub(crate) async fn children_with_generations(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<Vec<(ChangesetId, Generation)>> {
+        let children = self
+            .storage
+            .fetch_many_children(ctx, cs_ids)
+            .await?
+            .into_values()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let children_edges = self
+            .storage
+            .fetch_many_edges_required(ctx, &children, Prefetch::None)
+            .await?;
+
+        Ok(children_edges
+            .into_values()
+            .map(|edges| (edges.node.cs_id, edges.node.generation))
+            .collect())
+    }
+
+    /// Raise a frontier so that it contains the lowest descendants of the
+    /// frontier that have a generation number greater than or equal to
+    /// `target_generation`.
+    pub(crate) async fn raise_frontier(
+        &self,
+        ctx: &CoreContext,
+        frontier: &mut ChangesetFrontier,
+        target_generation: Generation,
+    ) -> Result<()> {
+        loop {
+            match frontier.first_key_value() {
+                None => return Ok(()),
+                Some((generation, _)) if *generation >= target_generation => {
+                    return Ok(());
+                }
+                _ => {}
+            }
+
+            self.raise_frontier_lowest_generation(ctx, frontier).await?;
+        }
+    }
+
+    /// Raise the lowest generation changesets of a frontier
+    /// to their immediate children.
+    pub(crate) async fn raise_frontier_lowest_generation(
+        &self,
+        ctx: &CoreContext,
+        frontier: &mut ChangesetFrontier,
+    ) -> Result<()> {
+        if let Some((_, cs_ids)) = frontier.pop_first() {
+            let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
+            frontier.extend(self.children_with_generations(ctx, &cs_ids).await?);
+        }
+        Ok(())
+    }
 }
@@ -9,14 +9,21 @@
 //!
 //! The graph of all commits in the repository.
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::ops::Range;
 use std::sync::Arc;
 
 use anyhow::anyhow;
 use anyhow::Result;
 use borrowed::borrowed;
+use buffered_commit_graph_storage::BufferedCommitGraphStorage;
+use commit_graph_types::edges::ChangesetEdges;
 use commit_graph_types::edges::ChangesetNode;
+use commit_graph_types::edges::ChangesetNodeParents;
 use commit_graph_types::edges::ChangesetParents;
 use commit_graph_types::frontier::ChangesetFrontier;
 use commit_graph_types::storage::CommitGraphStorage;
@@ -32,11 +39,78 @@ use mononoke_types::ChangesetId;
 use mononoke_types::ChangesetIdPrefix;
 use mononoke_types::ChangesetIdsResolvedFromPrefix;
 use mononoke_types::Generation;
+use smallvec::smallvec;
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 
+mod builder;
 mod compat;
 mod core;
 mod frontier;
 
+pub use builder::CommitGraphBuilder;
+pub use frontier::Frontier;
+
+/// Errors returned by commit graph operations that callers may want to
+/// handle specifically.
+#[derive(Debug, Error)]
+pub enum CommitGraphError {
+    #[error("Ancestors difference has more than {cap} changesets")]
+    AncestorsDifferenceCapExceeded { cap: usize },
+
+    #[error("Parent {parent} of changeset {cs_id} is missing from the commit graph")]
+    MissingParent {
+        cs_id: ChangesetId,
+        parent: ChangesetId,
+    },
+
+    #[error(
+        "Changeset {cs_id} has generation {generation}, but its parents imply generation {expected}"
+    )]
+    InconsistentGeneration {
+        cs_id: ChangesetId,
+        generation: u64,
+        expected: u64,
+    },
+
+    #[error(
+        "Skip tree skew ancestor {skew_ancestor} of changeset {cs_id} does not have a lower generation"
+    )]
+    SkewAncestorGenerationNotLower {
+        cs_id: ChangesetId,
+        skew_ancestor: ChangesetId,
+    },
+
+    #[error("Skip tree skew ancestor {skew_ancestor} of changeset {cs_id} is not its ancestor")]
+    SkewAncestorNotAncestor {
+        cs_id: ChangesetId,
+        skew_ancestor: ChangesetId,
+    },
+
+    #[error("Commit graph traversal was cancelled")]
+    Cancelled,
+
+    #[error("No storage was provided to build the commit graph")]
+    MissingStorage,
+
+    #[error("Cache pool {name} already exists, so it can't be created with the requested size")]
+    CachePoolExists { name: String },
+}
+
+/// Number of edges buffered in memory by `add_many` before they are
+/// written to storage.
+const ADD_MANY_BUFFER_SIZE: usize = 10000;
+
+/// State of a traversal slicing the ancestors of some heads, shared by
+/// `slice_ancestors` and `slice_ancestors_stream`.
+struct SliceAncestorsState {
+    frontier: ChangesetFrontier,
+    slice_start: u64,
+    slice_size: u64,
+    lower_to: Option<Generation>,
+    finished: bool,
+}
+
 /// Commit Graph.
 ///
 /// This contains the graph of all commits known to Mononoke for a particular
@@ -48,11 +122,20 @@ mod frontier;
 pub struct CommitGraph {
     /// The storage back-end where the commits are actually stored.
     storage: Arc<dyn CommitGraphStorage>,
+
+    /// The prefetch hint used when fetching the edges of the changesets
+    /// visited by traversals.
+    default_prefetch: Prefetch,
 }
 
 impl CommitGraph {
+    /// Create a commit graph over the given storage, with default settings
+    /// for everything else. Use `CommitGraphBuilder` to change them.
     pub fn new(storage: Arc<dyn CommitGraphStorage>) -> CommitGraph {
-        CommitGraph { storage }
+        CommitGraph {
+            storage,
+            default_prefetch: Prefetch::for_p1_linear_traversal(),
+        }
     }
 
     /// Add a new changeset to the commit graph.
@@ -78,6 +161,118 @@ impl CommitGraph {
             .await
     }
 
+    /// Add many changesets to the commit graph at once.
+    ///
+    /// The changesets don't need to be sorted: they are added in topological
+    /// order, parents before children. Each parent must either already exist
+    /// in the commit graph or be part of the batch, and the batch must not
+    /// contain cycles, otherwise an error is returned and nothing is added.
+    ///
+    /// Returns the number of newly inserted changesets.
+    pub async fn add_many(
+        &self,
+        ctx: &CoreContext,
+        commits: Vec<(ChangesetId, ChangesetParents)>,
+    ) -> Result<usize> {
+        let mut batch: HashMap<ChangesetId, ChangesetParents> = HashMap::new();
+        let mut batch_order = vec![];
+        for (cs_id, parents) in commits {
+            if batch.insert(cs_id, parents).is_none() {
+                batch_order.push(cs_id);
+            }
+        }
+
+        let external_parents = batch
+            .values()
+            .flatten()
+            .filter(|parent| !batch.contains_key(parent))
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut edges_map = self
+            .storage
+            .fetch_many_edges(ctx, &external_parents, Prefetch::None)
+            .await?;
+        let missing_parents = external_parents
+            .iter()
+            .filter(|parent| !edges_map.contains_key(parent))
+            .collect::<BTreeSet<_>>();
+        if !missing_parents.is_empty() {
+            return Err(anyhow!(
+                "Parents missing from the commit graph and not part of the batch: {}",
+                missing_parents
+                    .into_iter()
+                    .map(|cs_id| cs_id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        // Sort the batch topologically, keeping the input order among
+        // changesets whose parents have all been sorted.
+        let mut children: HashMap<ChangesetId, Vec<ChangesetId>> = HashMap::new();
+        let mut unsorted_parents: HashMap<ChangesetId, usize> = HashMap::new();
+        for cs_id in &batch_order {
+            for parent in batch[cs_id].iter().filter(|p| batch.contains_key(p)) {
+                children.entry(*parent).or_default().push(*cs_id);
+                *unsorted_parents.entry(*cs_id).or_default() += 1;
+            }
+        }
+        let mut queue: VecDeque<ChangesetId> = batch_order
+            .iter()
+            .filter(|cs_id| !unsorted_parents.contains_key(cs_id))
+            .copied()
+            .collect();
+        let mut sorted = Vec::with_capacity(batch.len());
+        while let Some(cs_id) = queue.pop_front() {
+            sorted.push(cs_id);
+            for child in children.remove(&cs_id).unwrap_or_default() {
+                let count = unsorted_parents
+                    .get_mut(&child)
+                    .expect("child should have unsorted parents");
+                *count -= 1;
+                if *count == 0 {
+                    unsorted_parents.remove(&child);
+                    queue.push_back(child);
+                }
+            }
+        }
+        if !unsorted_parents.is_empty() {
+            return Err(anyhow!(
+                "Changesets being added to the commit graph contain a cycle involving: {}",
+                unsorted_parents
+                    .into_keys()
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .map(|cs_id| cs_id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        // Changesets in the batch need to be visible to each other while
+        // their edges are built, so add them through a buffered storage.
+        let buffered_storage = Arc::new(BufferedCommitGraphStorage::new(
+            self.storage.clone(),
+            ADD_MANY_BUFFER_SIZE,
+        ));
+        let graph = CommitGraph::new(buffered_storage.clone());
+        let mut added = 0;
+        for (index, cs_id) in sorted.into_iter().enumerate() {
+            if index > 0 && index % ADD_MANY_BUFFER_SIZE == 0 {
+                added += buffered_storage.flush(ctx).await?;
+            }
+            let parents = batch.remove(&cs_id).unwrap_or_default();
+            let edges = graph.build_edges(ctx, cs_id, parents, &edges_map).await?;
+            edges_map.insert(cs_id, edges.clone());
+            buffered_storage.add(ctx, edges).await?;
+        }
+        added += buffered_storage.flush(ctx).await?;
+
+        Ok(added)
+    }
+
     /// Find all changeset ids with a given prefix.
     pub async fn find_by_prefix(
         &self,
@@ -88,12 +283,77 @@ impl CommitGraph {
         self.storage.find_by_prefix(ctx, cs_prefix, limit).await
     }
 
+    /// Find all changeset ids with each of the given prefixes.
+    ///
+    /// The results are in the same order as the prefixes, and each of them
+    /// is resolved independently, as with `find_by_prefix`. The storage has
+    /// no batched prefix lookup, so the prefixes are resolved concurrently.
+    pub async fn find_by_prefixes(
+        &self,
+        ctx: &CoreContext,
+        cs_prefixes: Vec<ChangesetIdPrefix>,
+        limit: usize,
+    ) -> Result<Vec<ChangesetIdsResolvedFromPrefix>> {
+        future::try_join_all(
+            cs_prefixes
+                .into_iter()
+                .map(|cs_prefix| self.storage.find_by_prefix(ctx, cs_prefix, limit)),
+        )
+        .await
+    }
+
     /// Returns true if the changeset exists.
     pub async fn exists(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<bool> {
         let edges = self.storage.fetch_edges(ctx, cs_id).await?;
         Ok(edges.is_some())
     }
 
+    /// Returns whether each of the changesets exists. The returned map
+    /// contains an entry for every changeset in cs_ids.
+    pub async fn exists_many(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, bool>> {
+        let all_edges = self
+            .storage
+            .fetch_many_edges(ctx, &cs_ids, Prefetch::None)
+            .await?;
+        Ok(cs_ids
+            .into_iter()
+            .map(|cs_id| (cs_id, all_edges.contains_key(&cs_id)))
+            .collect())
+    }
+
+    /// Returns the raw commit graph edges of a single changeset, or None if
+    /// it doesn't exist.
+    ///
+    /// This is a lower-level API than the ancestry methods of the commit
+    /// graph, intended for callers that implement their own algorithms on
+    /// top of the skip tree and p1-linear tree edges.
+    pub async fn fetch_edges(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Option<ChangesetEdges>> {
+        self.storage.fetch_edges(ctx, cs_id).await
+    }
+
+    /// Returns the raw commit graph edges of many changesets. Changesets
+    /// that don't exist are absent from the returned map.
+    ///
+    /// Prefetch hints that the request is part of a traversal, so the
+    /// storage may fetch more edges into its caches. Like `fetch_edges`,
+    /// this is a lower-level API than the ancestry methods.
+    pub async fn fetch_many_edges(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+        prefetch: Prefetch,
+    ) -> Result<HashMap<ChangesetId, ChangesetEdges>> {
+        self.storage.fetch_many_edges(ctx, cs_ids, prefetch).await
+    }
+
     /// Returns the parents of a single changeset.
     pub async fn changeset_parents(
         &self,
@@ -121,6 +381,105 @@ impl CommitGraph {
             .ok_or_else(|| anyhow!("Missing changeset in commit graph: {}", cs_id))
     }
 
+    /// Returns the parents of multiple changesets. Changesets that are
+    /// missing from the commit graph are absent from the returned map.
+    pub async fn changeset_parents_many(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, ChangesetParents>> {
+        let all_edges = self
+            .storage
+            .fetch_many_edges(ctx, &cs_ids, Prefetch::None)
+            .await?;
+
+        Ok(all_edges
+            .into_iter()
+            .map(|(cs_id, edges)| {
+                (
+                    cs_id,
+                    edges
+                        .parents
+                        .into_iter()
+                        .map(|parent| parent.cs_id)
+                        .collect(),
+                )
+            })
+            .collect())
+    }
+
+    /// Returns the parents of multiple changesets that must exist. The
+    /// error lists all of the changesets that are missing.
+    pub async fn changeset_parents_many_required(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, ChangesetParents>> {
+        let parents = self.changeset_parents_many(ctx, cs_ids.clone()).await?;
+
+        let missing_changesets = cs_ids
+            .iter()
+            .filter(|cs_id| !parents.contains_key(cs_id))
+            .collect::<BTreeSet<_>>();
+        if !missing_changesets.is_empty() {
+            return Err(anyhow!(
+                "Missing changesets in commit graph: {}",
+                missing_changesets
+                    .into_iter()
+                    .map(|cs_id| cs_id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        Ok(parents)
+    }
+
+    /// Returns true if the changeset, which must exist, has no parents.
+    pub async fn is_root(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<bool> {
+        Ok(self
+            .changeset_parents_required(ctx, cs_id)
+            .await?
+            .is_empty())
+    }
+
+    /// Returns the skip tree skew ancestor of a single changeset that must
+    /// exist.
+    ///
+    /// The skip tree skew ancestor is an ancestor of the common ancestors
+    /// of the changeset's parents, chosen so that repeatedly following skew
+    /// ancestors and skip tree parents forms a skew-binary search tree over
+    /// the commit graph. This allows reaching an ancestor at any generation
+    /// in a logarithmic number of steps. Returns None if no such ancestor
+    /// exists, which is always the case for root changesets.
+    pub async fn skip_tree_skew_ancestor(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Option<ChangesetNode>> {
+        Ok(self
+            .storage
+            .fetch_edges_required(ctx, cs_id)
+            .await?
+            .skip_tree_skew_ancestor)
+    }
+
+    /// Returns the parents of a single changeset that must exist, excluding
+    /// its first parent, in the order they were added. For non-merge
+    /// changesets this is an empty vec.
+    pub async fn merge_parents(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Vec<ChangesetId>> {
+        Ok(self
+            .changeset_parents_required(ctx, cs_id)
+            .await?
+            .into_iter()
+            .skip(1)
+            .collect())
+    }
+
     /// Returns the generation number of a single changeset.
     pub async fn changeset_generation(
         &self,
@@ -142,6 +501,93 @@ impl CommitGraph {
             .ok_or_else(|| anyhow!("Missing changeset in commit graph: {}", cs_id))
     }
 
+    /// Returns the number of first parent hops from a changeset that must
+    /// exist to the root of its first parent history. Root changesets have
+    /// a first parent depth of 0.
+    ///
+    /// Unlike the generation number, which is one more than the maximum
+    /// generation over all parents, this only follows first parents, so it
+    /// is the position of the changeset along its mainline. For a merge it
+    /// can be much lower than the generation, if the longer history comes
+    /// in through a merge parent. The depth is stored in the changeset's
+    /// edges, so only a single changeset is fetched.
+    pub async fn first_parent_depth(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<u64> {
+        let edges = self.storage.fetch_edges(ctx, cs_id).await?;
+        edges
+            .map(|edges| edges.node.p1_linear_depth)
+            .ok_or_else(|| anyhow!("Missing changeset in commit graph: {}", cs_id))
+    }
+
+    /// Returns the generation numbers of many changesets. Changesets that
+    /// don't exist are absent from the returned map.
+    pub async fn changeset_generation_many(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, Generation>> {
+        let all_edges = self
+            .storage
+            .fetch_many_edges(ctx, &cs_ids, Prefetch::None)
+            .await?;
+        Ok(all_edges
+            .into_iter()
+            .map(|(cs_id, edges)| (cs_id, edges.node.generation))
+            .collect())
+    }
+
+    /// Returns the generation numbers of many changesets that must all
+    /// exist. If any are missing, the error lists all of them.
+    pub async fn changeset_generation_many_required(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, Generation>> {
+        let all_edges = self
+            .storage
+            .fetch_many_edges(ctx, &cs_ids, Prefetch::None)
+            .await?;
+
+        let missing_changesets = cs_ids
+            .iter()
+            .filter(|cs_id| !all_edges.contains_key(cs_id))
+            .collect::<BTreeSet<_>>();
+        if !missing_changesets.is_empty() {
+            return Err(anyhow!(
+                "Missing changesets in commit graph: {}",
+                missing_changesets
+                    .into_iter()
+                    .map(|cs_id| cs_id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        Ok(all_edges
+            .into_iter()
+            .map(|(cs_id, edges)| (cs_id, edges.node.generation))
+            .collect())
+    }
+
+    /// Returns the changeset with the highest generation number out of
+    /// heads, breaking ties by the largest changeset id. All heads must
+    /// exist. Returns None if heads is empty.
+    pub async fn highest_generation_head(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+    ) -> Result<Option<ChangesetId>> {
+        let all_edges = self
+            .storage
+            .fetch_many_edges_required(ctx, &heads, Prefetch::None)
+            .await?;
+
+        Ok(all_edges
+            .into_values()
+            .map(|edges| (edges.node.generation, edges.node.cs_id))
+            .max()
+            .map(|(_, cs_id)| cs_id))
+    }
+
     /// Returns a frontier for the ancestors of heads
     /// that satisfy a given property.
     ///
@@ -159,7 +605,7 @@ impl CommitGraph {
         Out: Future<Output = Result<bool>>,
     {
         let mut ancestors_frontier = vec![];
-        let mut frontier = self.frontier(ctx, heads).await?;
+        let mut frontier = self.changeset_frontier(ctx, heads).await?;
 
         let monotonic_property = move |node: ChangesetNode| {
             borrowed!(monotonic_property);
@@ -176,6 +622,235 @@ impl CommitGraph {
         Ok(ancestors_frontier.into_iter().collect())
     }
 
+    /// Same as `ancestors_frontier_with`, but evaluates the property for up
+    /// to `concurrency` changesets of the same generation concurrently.
+    ///
+    /// This is useful for properties that are expensive to evaluate, e.g.
+    /// that require a database look-up. The result is the same as for
+    /// `ancestors_frontier_with`.
+    ///
+    /// Note: The property needs to be monotonic i.e. if the
+    /// property holds for one changeset then it has to hold
+    /// for all its parents.
+    pub async fn ancestors_frontier_with_concurrency<MonotonicProperty, Out>(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        monotonic_property: MonotonicProperty,
+        concurrency: usize,
+    ) -> Result<Vec<ChangesetId>>
+    where
+        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+        Out: Future<Output = Result<bool>>,
+    {
+        let mut ancestors_frontier = vec![];
+        let mut frontier = self.changeset_frontier(ctx, heads).await?;
+
+        let monotonic_property = move |node: ChangesetNode| {
+            borrowed!(monotonic_property);
+            monotonic_property(node.cs_id)
+        };
+
+        while let Some(ancestors_frontier_extension) = self
+            .lower_frontier_step_concurrent(
+                ctx,
+                &mut frontier,
+                &monotonic_property,
+                Prefetch::None,
+                concurrency,
+            )
+            .await?
+        {
+            ancestors_frontier.extend(ancestors_frontier_extension);
+        }
+
+        Ok(ancestors_frontier)
+    }
+
+    /// Returns an ancestor of head that satisfies a given property, such
+    /// that none of its parents satisfy it, or None if head itself doesn't
+    /// satisfy the property.
+    ///
+    /// This is useful for finding the first "bad" commit when bisecting.
+    /// For linear history the result is the lowest generation ancestor of
+    /// head that satisfies the property. For merges, it is one of the
+    /// ancestors at the boundary of the property.
+    ///
+    /// The search jumps along skip tree edges, so for linear history the
+    /// number of property evaluations is roughly logarithmic in the depth
+    /// of history between head and the result.
+    ///
+    /// Note: The property needs to be monotonic in the opposite direction
+    /// to other methods i.e. if the property holds for one changeset then
+    /// it has to hold for all its descendants.
+    pub async fn first_ancestor_satisfying<MonotonicProperty, Out>(
+        &self,
+        ctx: &CoreContext,
+        head: ChangesetId,
+        monotonic_property: MonotonicProperty,
+    ) -> Result<Option<ChangesetId>>
+    where
+        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync,
+        Out: Future<Output = Result<bool>>,
+    {
+        if !monotonic_property(head).await? {
+            return Ok(None);
+        }
+
+        let mut edges = self.storage.fetch_edges_required(ctx, head).await?;
+
+        loop {
+            // Try to jump as far as possible along the skip tree.
+            if let Some(ancestor) = edges
+                .lowest_skip_tree_edge_with(|node| monotonic_property(node.cs_id))
+                .await?
+            {
+                edges = self
+                    .storage
+                    .fetch_edges_required(ctx, ancestor.cs_id)
+                    .await?;
+                continue;
+            }
+
+            // For non-merge commits the skip tree parent is the only parent,
+            // which we already know doesn't satisfy the property.
+            if edges.parents.len() <= 1 {
+                return Ok(Some(edges.node.cs_id));
+            }
+
+            let mut next_parent = None;
+            for parent in edges.parents.iter() {
+                if monotonic_property(parent.cs_id).await? {
+                    next_parent = Some(parent.cs_id);
+                    break;
+                }
+            }
+
+            match next_parent {
+                Some(parent) => {
+                    edges = self.storage.fetch_edges_required(ctx, parent).await?;
+                }
+                None => return Ok(Some(edges.node.cs_id)),
+            }
+        }
+    }
+
+    /// Returns all ancestors of any changeset in heads whose generation number
+    /// is strictly greater than min_gen, e.g. the changesets added since a
+    /// previously recorded generation watermark.
+    ///
+    /// Changesets with generation exactly min_gen are excluded, and heads at
+    /// or below min_gen contribute nothing. Ancestors below min_gen are not
+    /// traversed.
+    pub async fn ancestors_above_generation(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        min_gen: Generation,
+    ) -> Result<Vec<ChangesetId>> {
+        self.ancestors_in_generation_range(
+            ctx,
+            heads,
+            Generation::new(min_gen.value().saturating_add(1))..Generation::max_gen(),
+        )
+        .await
+    }
+
+    /// Returns all ancestors of any changeset in heads whose generation
+    /// number is within the given range.
+    ///
+    /// Ancestors with generation numbers lower than the start of the range
+    /// are not traversed.
+    pub async fn ancestors_in_generation_range(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        range: Range<Generation>,
+    ) -> Result<Vec<ChangesetId>> {
+        // Generation numbers start at 1, so there are no
+        // changesets below the generation of range.end.
+        if range.start >= range.end || range.end.value() <= 1 {
+            return Ok(vec![]);
+        }
+
+        let mut frontier = self.changeset_frontier(ctx, heads).await?;
+        self.lower_frontier(ctx, &mut frontier, Generation::new(range.end.value() - 1))
+            .await?;
+
+        let mut ancestors = vec![];
+        while let Some((generation, cs_ids)) = frontier.pop_last() {
+            if generation < range.start {
+                break;
+            }
+
+            let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
+
+            // Parents of changesets at the start of the range
+            // are below it, so there's no need to fetch them.
+            if generation > range.start {
+                let all_edges = self
+                    .storage
+                    .fetch_many_edges_required(ctx, &cs_ids, self.default_prefetch)
+                    .await?;
+
+                for (_, edges) in all_edges.into_iter() {
+                    for parent in edges.parents.into_iter() {
+                        frontier
+                            .entry(parent.generation)
+                            .or_default()
+                            .insert(parent.cs_id);
+                    }
+                }
+            }
+
+            ancestors.extend(cs_ids);
+        }
+
+        Ok(ancestors)
+    }
+
+    /// Returns all descendants of any changeset in roots whose generation
+    /// number is within the given range.
+    ///
+    /// Descendants with generation numbers greater than or equal to the end
+    /// of the range are not traversed.
+    ///
+    /// Note: This walks children edges, so it relies on the storage back-end
+    /// maintaining them (see `CommitGraphStorage::fetch_many_children`).
+    pub async fn descendants_in_generation_range(
+        &self,
+        ctx: &CoreContext,
+        roots: Vec<ChangesetId>,
+        range: Range<Generation>,
+    ) -> Result<Vec<ChangesetId>> {
+        if range.start >= range.end {
+            return Ok(vec![]);
+        }
+
+        let mut frontier = self.changeset_frontier(ctx, roots).await?;
+
+        let mut descendants = vec![];
+        while let Some((generation, cs_ids)) = frontier.pop_first() {
+            if generation >= range.end {
+                break;
+            }
+
+            let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
+
+            // Children of changesets at the end of the range
+            // are above it, so there's no need to fetch them.
+            if generation.add(1) < range.end {
+                frontier.extend(self.children_with_generations(ctx, &cs_ids).await?);
+            }
+
+            if generation >= range.start {
+                descendants.extend(cs_ids);
+            }
+        }
+
+        Ok(descendants)
+    }
+
     /// Returns true if the ancestor changeset is an ancestor of the descendant
     /// changeset.
     ///
@@ -195,15 +870,495 @@ impl CommitGraph {
         Ok(frontier.highest_generation_contains(ancestor, target_gen))
     }
 
-    pub async fn ancestors_difference_stream_with<MonotonicProperty, Out>(
+    /// Returns true if `to` is a fast-forward of `from`, i.e. if `from` is an
+    /// ancestor of `to` and the history between them has not diverged.
+    ///
+    /// The history has not diverged if every changeset that is an ancestor
+    /// of `to` but not of `from` is a descendant of `from`. Equivalently,
+    /// each of those changesets only has parents that are either `from` or
+    /// also among those changesets. A merge that brings in a branch that
+    /// forked off below `from` makes this false, even though `from` is still
+    /// an ancestor of `to`, which is all that `is_ancestor` checks.
+    ///
+    /// A changeset is a fast-forward of itself.
+    pub async fn is_fast_forward(
         &self,
         ctx: &CoreContext,
-        heads: Vec<ChangesetId>,
-        common: Vec<ChangesetId>,
-        monotonic_property: MonotonicProperty,
-    ) -> Result<BoxStream<'static, Result<ChangesetId>>>
-    where
-        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+        from: ChangesetId,
+        to: ChangesetId,
+    ) -> Result<bool> {
+        if !self.is_ancestor(ctx, from, to).await? {
+            return Ok(false);
+        }
+
+        let difference = self
+            .ancestors_difference_with_parents_stream(ctx, vec![to], vec![from])
+            .await?
+            .try_collect::<HashMap<_, _>>()
+            .await?;
+
+        Ok(difference.values().all(|parents| {
+            parents
+                .iter()
+                .all(|parent| *parent == from || difference.contains_key(parent))
+        }))
+    }
+
+    /// Returns a path of changesets from descendant to ancestor (both
+    /// inclusive) following parent edges, or None if ancestor is not an
+    /// ancestor of descendant.
+    ///
+    /// When there are multiple paths, the one that prefers earlier parents
+    /// at each merge is returned, so the result is deterministic.
+    ///
+    /// Only the ancestors of descendant with a higher generation than
+    /// ancestor are visited, each of them once.
+    pub async fn path_to_ancestor(
+        &self,
+        ctx: &CoreContext,
+        descendant: ChangesetId,
+        ancestor: ChangesetId,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        let (mut frontier, ancestor_generation) = futures::try_join!(
+            self.single_frontier(ctx, descendant),
+            self.changeset_generation_required(ctx, ancestor),
+        )?;
+
+        // Collect the parents of all ancestors of descendant that are above
+        // ancestor, in decreasing generation order.
+        let mut visited = vec![];
+        while let Some((generation, cs_ids)) = frontier.pop_last() {
+            if generation <= ancestor_generation {
+                break;
+            }
+
+            let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
+            let all_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &cs_ids, self.default_prefetch)
+                .await?;
+
+            for edges in all_edges.into_values() {
+                for parent in edges.parents.iter() {
+                    if parent.generation >= ancestor_generation {
+                        frontier
+                            .entry(parent.generation)
+                            .or_default()
+                            .insert(parent.cs_id);
+                    }
+                }
+                visited.push((
+                    edges.node.cs_id,
+                    edges
+                        .parents
+                        .into_iter()
+                        .map(|parent| parent.cs_id)
+                        .collect::<Vec<_>>(),
+                ));
+            }
+        }
+
+        // Parents have lower generations than their children, so visiting
+        // in increasing generation order finds which parents reach ancestor
+        // before their children are visited. Record the first of them.
+        let mut next_on_path = HashMap::new();
+        for (cs_id, parents) in visited.into_iter().rev() {
+            if let Some(parent) = parents
+                .into_iter()
+                .find(|parent| *parent == ancestor || next_on_path.contains_key(parent))
+            {
+                next_on_path.insert(cs_id, parent);
+            }
+        }
+
+        if descendant != ancestor && !next_on_path.contains_key(&descendant) {
+            return Ok(None);
+        }
+
+        let mut path = vec![descendant];
+        let mut current = descendant;
+        while let Some(next) = next_on_path.get(&current) {
+            current = *next;
+            path.push(current);
+        }
+
+        Ok(Some(path))
+    }
+
+    /// Returns true if neither u is an ancestor of v nor v is an ancestor
+    /// of u. As a changeset is its own ancestor, `are_independent(x, x)`
+    /// is false.
+    pub async fn are_independent(
+        &self,
+        ctx: &CoreContext,
+        u: ChangesetId,
+        v: ChangesetId,
+    ) -> Result<bool> {
+        let (u_gen, v_gen) = futures::try_join!(
+            self.changeset_generation_required(ctx, u),
+            self.changeset_generation_required(ctx, v)
+        )?;
+
+        if u_gen == v_gen {
+            return Ok(u != v);
+        }
+
+        // Only the changeset with the lower generation can be an ancestor of
+        // the other, so a single frontier lowering is enough.
+        let (ancestor, ancestor_gen, descendant, descendant_gen) = if u_gen < v_gen {
+            (u, u_gen, v, v_gen)
+        } else {
+            (v, v_gen, u, u_gen)
+        };
+
+        let mut frontier = ChangesetFrontier::new_single(descendant, descendant_gen);
+        self.lower_frontier(ctx, &mut frontier, ancestor_gen)
+            .await?;
+        Ok(!frontier.highest_generation_contains(ancestor, ancestor_gen))
+    }
+
+    /// Returns the frontier of the ancestors of heads at the target
+    /// generation: the highest ancestors of heads that have a generation
+    /// number less than or equal to target, sorted by changeset id.
+    ///
+    /// If an edge skips over the target generation (e.g. a merge whose
+    /// parent has a much lower generation), there's no commit at exactly
+    /// the target generation along that edge, and the parent below the
+    /// target is returned instead. Heads which already have a generation
+    /// number less than or equal to target are returned as is.
+    pub async fn frontier_at_generation(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        target: Generation,
+    ) -> Result<Vec<ChangesetId>> {
+        let mut frontier = self.changeset_frontier(ctx, heads).await?;
+        self.lower_frontier(ctx, &mut frontier, target).await?;
+
+        let mut cs_ids = frontier.changesets();
+        cs_ids.sort();
+        Ok(cs_ids)
+    }
+
+    /// Returns the ancestor of head with the highest generation number out
+    /// of the given candidates, or None if none of them is an ancestor of
+    /// head. A changeset is considered an ancestor of itself.
+    ///
+    /// If several candidate ancestors share the highest generation number,
+    /// the one with the smallest changeset id is returned. Candidates that
+    /// don't exist in the commit graph are ignored.
+    pub async fn nearest_ancestor_in_set(
+        &self,
+        ctx: &CoreContext,
+        head: ChangesetId,
+        candidates: HashSet<ChangesetId>,
+    ) -> Result<Option<ChangesetId>> {
+        let (mut frontier, candidate_generations) = futures::try_join!(
+            self.single_frontier(ctx, head),
+            self.changeset_generation_many(ctx, candidates.into_iter().collect())
+        )?;
+        let mut candidates: ChangesetFrontier = candidate_generations.into_iter().collect();
+
+        // Lower the frontier to the generation of each candidate in
+        // decreasing order, stopping as soon as one of them is found.
+        while let Some((generation, cs_ids)) = candidates.pop_last() {
+            self.lower_frontier(ctx, &mut frontier, generation).await?;
+
+            if let Some(cs_id) = cs_ids
+                .into_iter()
+                .filter(|cs_id| frontier.highest_generation_contains(*cs_id, generation))
+                .min()
+            {
+                return Ok(Some(cs_id));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the number of edges on the shortest path from descendant to
+    /// ancestor following parent edges, or None if ancestor is not an
+    /// ancestor of descendant.
+    ///
+    /// For linear history this is the difference between the generation
+    /// numbers of the two changesets, but merges can make the shortest path
+    /// shorter than that.
+    pub async fn distance(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetId,
+        descendant: ChangesetId,
+    ) -> Result<Option<u64>> {
+        let (ancestor_gen, descendant_gen) = futures::try_join!(
+            self.changeset_generation_required(ctx, ancestor),
+            self.changeset_generation_required(ctx, descendant)
+        )?;
+
+        if descendant_gen < ancestor_gen {
+            return Ok(None);
+        }
+
+        self.shortest_path_length(ctx, ancestor, ancestor_gen, descendant, u64::MAX)
+            .await
+    }
+
+    /// Returns true if ancestor is an ancestor of descendant that can be
+    /// reached from it by following at most max_depth parent edges.
+    ///
+    /// Note: A false result means that ancestor isn't reachable within
+    /// max_depth edges of descendant, not necessarily that it isn't an
+    /// ancestor of descendant.
+    pub async fn is_ancestor_within(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetId,
+        descendant: ChangesetId,
+        max_depth: u64,
+    ) -> Result<bool> {
+        let (ancestor_gen, descendant_gen) = futures::try_join!(
+            self.changeset_generation_required(ctx, ancestor),
+            self.changeset_generation_required(ctx, descendant)
+        )?;
+
+        if descendant_gen < ancestor_gen {
+            return Ok(false);
+        }
+
+        // We can't return early if the generation difference between the
+        // changesets is larger than max_depth, as merges can make the
+        // shortest path shorter than that. Instead the search is bounded
+        // to max_depth levels.
+        Ok(self
+            .shortest_path_length(ctx, ancestor, ancestor_gen, descendant, max_depth)
+            .await?
+            .is_some())
+    }
+
+    /// Returns the number of edges on the shortest path from descendant to
+    /// ancestor following parent edges, or None if there's no such path of
+    /// at most max_depth edges.
+    async fn shortest_path_length(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetId,
+        ancestor_gen: Generation,
+        descendant: ChangesetId,
+        max_depth: u64,
+    ) -> Result<Option<u64>> {
+        // Breadth-first search from the descendant, skipping any
+        // changesets with a lower generation than the ancestor.
+        let mut distance = 0;
+        let mut current_level = vec![descendant];
+        let mut visited: HashSet<ChangesetId> = HashSet::from([descendant]);
+
+        while !current_level.is_empty() {
+            if current_level.contains(&ancestor) {
+                return Ok(Some(distance));
+            }
+
+            if distance == max_depth {
+                break;
+            }
+
+            let all_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &current_level, self.default_prefetch)
+                .await?;
+
+            current_level = vec![];
+            for (_, edges) in all_edges.into_iter() {
+                for parent in edges.parents.into_iter() {
+                    if parent.generation >= ancestor_gen && visited.insert(parent.cs_id) {
+                        current_level.push(parent.cs_id);
+                    }
+                }
+            }
+            distance += 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Returns for each changeset in descendants whether the ancestor
+    /// changeset is an ancestor of it, in the same order as descendants.
+    ///
+    /// This is equivalent to calling is_ancestor for each descendant, but
+    /// lowers a single frontier built from all of the descendants.
+    pub async fn is_ancestor_batch(
+        &self,
+        ctx: &CoreContext,
+        ancestor: ChangesetId,
+        descendants: Vec<ChangesetId>,
+    ) -> Result<Vec<bool>> {
+        let (mut frontier, target_gen) = futures::try_join!(
+            self.changeset_frontier(ctx, descendants.clone()),
+            self.changeset_generation_required(ctx, ancestor)
+        )?;
+
+        // For each changeset in the frontier, the indices of the
+        // descendants that it is an ancestor of.
+        let mut origins: HashMap<ChangesetId, HashSet<usize>> = Default::default();
+        for (index, cs_id) in descendants.iter().enumerate() {
+            origins.entry(*cs_id).or_default().insert(index);
+        }
+
+        while let Some((generation, cs_ids)) = frontier.pop_last() {
+            if generation <= target_gen {
+                frontier.insert(generation, cs_ids);
+                break;
+            }
+
+            let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
+            let frontier_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &cs_ids, self.default_prefetch)
+                .await?;
+
+            for (cs_id, edges) in frontier_edges {
+                let cs_origins = origins.remove(&cs_id).unwrap_or_default();
+                let ancestors: ChangesetNodeParents = match edges
+                    .lowest_skip_tree_edge_with(|node| {
+                        future::ready(Ok(node.generation >= target_gen))
+                    })
+                    .await?
+                {
+                    Some(ancestor) => smallvec![ancestor],
+                    None => edges.parents,
+                };
+
+                for ancestor in ancestors {
+                    frontier
+                        .entry(ancestor.generation)
+                        .or_default()
+                        .insert(ancestor.cs_id);
+                    origins
+                        .entry(ancestor.cs_id)
+                        .or_default()
+                        .extend(cs_origins.iter().copied());
+                }
+            }
+        }
+
+        let mut result = vec![false; descendants.len()];
+        if frontier.highest_generation_contains(ancestor, target_gen) {
+            if let Some(indices) = origins.get(&ancestor) {
+                for index in indices {
+                    result[*index] = true;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    pub async fn ancestors_difference_stream_with<MonotonicProperty, Out>(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        monotonic_property: MonotonicProperty,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>>
+    where
+        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+        Out: Future<Output = Result<bool>> + Send,
+    {
+        Ok(self
+            .ancestors_difference_inner_stream(
+                ctx,
+                heads,
+                common,
+                monotonic_property,
+                self.default_prefetch,
+                None,
+            )
+            .await?
+            .map_ok(|(cs_id, _edges)| cs_id)
+            .boxed())
+    }
+
+    /// Same as `ancestors_difference_stream_with`, but checks the given
+    /// cancellation token before each step of the traversal. Once the token
+    /// is cancelled, the stream stops fetching from storage and terminates
+    /// with `CommitGraphError::Cancelled`.
+    pub async fn ancestors_difference_stream_with_cancellation<MonotonicProperty, Out>(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        monotonic_property: MonotonicProperty,
+        cancellation: CancellationToken,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>>
+    where
+        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+        Out: Future<Output = Result<bool>> + Send,
+    {
+        Ok(self
+            .ancestors_difference_inner_stream(
+                ctx,
+                heads,
+                common,
+                monotonic_property,
+                self.default_prefetch,
+                Some(cancellation),
+            )
+            .await?
+            .map_ok(|(cs_id, _edges)| cs_id)
+            .boxed())
+    }
+
+    /// Returns a stream of the edges of all ancestors of any changeset in
+    /// heads, excluding any ancestor of any changeset in common and any
+    /// changeset that satisfies a given property. The given prefetch hint
+    /// is used when fetching the edges of the ancestors, and the traversal
+    /// stops with `CommitGraphError::Cancelled` if the given cancellation
+    /// token is cancelled.
+    ///
+    /// Fails if the edges of any of the ancestors are missing.
+    async fn ancestors_difference_edges_stream_with<MonotonicProperty, Out>(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        monotonic_property: MonotonicProperty,
+        prefetch: Prefetch,
+        cancellation: Option<CancellationToken>,
+    ) -> Result<BoxStream<'static, Result<ChangesetEdges>>>
+    where
+        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+        Out: Future<Output = Result<bool>> + Send,
+    {
+        Ok(self
+            .ancestors_difference_inner_stream(
+                ctx,
+                heads,
+                common,
+                monotonic_property,
+                prefetch,
+                cancellation,
+            )
+            .await?
+            .and_then(|(cs_id, edges)| {
+                future::ready(
+                    edges.ok_or_else(|| anyhow!("Missing changeset in commit graph: {}", cs_id)),
+                )
+            })
+            .boxed())
+    }
+
+    /// Same as `ancestors_difference_edges_stream_with`, but yields each
+    /// ancestor together with its edges, if they were found. Ancestors
+    /// whose edges are missing are still yielded, but their parents are not
+    /// traversed.
+    async fn ancestors_difference_inner_stream<MonotonicProperty, Out>(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        monotonic_property: MonotonicProperty,
+        prefetch: Prefetch,
+        cancellation: Option<CancellationToken>,
+    ) -> Result<BoxStream<'static, Result<(ChangesetId, Option<ChangesetEdges>)>>>
+    where
+        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
         Out: Future<Output = Result<bool>> + Send,
     {
         struct AncestorsDifferenceState<P> {
@@ -212,10 +1367,14 @@ impl CommitGraph {
             heads: ChangesetFrontier,
             common: ChangesetFrontier,
             monotonic_property: P,
+            prefetch: Prefetch,
+            cancellation: Option<CancellationToken>,
         }
 
-        let (heads, common) =
-            futures::try_join!(self.frontier(ctx, heads), self.frontier(ctx, common))?;
+        let (heads, common) = futures::try_join!(
+            self.changeset_frontier(ctx, heads),
+            self.changeset_frontier(ctx, common)
+        )?;
 
         Ok(stream::try_unfold(
             Box::new(AncestorsDifferenceState {
@@ -224,6 +1383,8 @@ impl CommitGraph {
                 heads,
                 common,
                 monotonic_property,
+                prefetch,
+                cancellation,
             }),
             move |mut state| async move {
                 let AncestorsDifferenceState {
@@ -232,8 +1393,16 @@ impl CommitGraph {
                     heads,
                     common,
                     monotonic_property,
+                    prefetch,
+                    cancellation,
                 } = &mut *state;
 
+                if let Some(cancellation) = cancellation {
+                    if cancellation.is_cancelled() {
+                        return Err(CommitGraphError::Cancelled.into());
+                    }
+                }
+
                 if let Some((generation, cs_ids)) = heads.pop_last() {
                     commit_graph.lower_frontier(ctx, common, generation).await?;
 
@@ -246,17 +1415,13 @@ impl CommitGraph {
                         }
                     }
 
-                    let all_edges = commit_graph
+                    let mut all_edges = commit_graph
                         .storage
-                        .fetch_many_edges(
-                            ctx,
-                            &cs_ids_not_excluded,
-                            Prefetch::for_p1_linear_traversal(),
-                        )
+                        .fetch_many_edges(ctx, &cs_ids_not_excluded, *prefetch)
                         .await?;
 
-                    for (_, edges) in all_edges.into_iter() {
-                        for parent in edges.parents.into_iter() {
+                    for edges in all_edges.values() {
+                        for parent in edges.parents.iter() {
                             heads
                                 .entry(parent.generation)
                                 .or_default()
@@ -264,6 +1429,994 @@ impl CommitGraph {
                         }
                     }
 
+                    let ancestors = cs_ids_not_excluded
+                        .into_iter()
+                        .map(|cs_id| anyhow::Ok((cs_id, all_edges.remove(&cs_id))))
+                        .collect::<Vec<_>>();
+
+                    anyhow::Ok(Some((stream::iter(ancestors), state)))
+                } else {
+                    Ok(None)
+                }
+            },
+        )
+        .try_flatten()
+        .boxed())
+    }
+
+    pub async fn ancestors_difference_stream(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>> {
+        self.ancestors_difference_stream_with(ctx, heads, common, |_| future::ready(Ok(false)))
+            .await
+    }
+
+    /// Same as `ancestors_difference_stream`, but uses the given prefetch
+    /// hint when fetching the edges of the ancestors instead of prefetching
+    /// along first parents.
+    pub async fn ancestors_difference_stream_with_prefetch(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        prefetch: Prefetch,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>> {
+        Ok(self
+            .ancestors_difference_inner_stream(
+                ctx,
+                heads,
+                common,
+                |_| future::ready(Ok(false)),
+                prefetch,
+                None,
+            )
+            .await?
+            .map_ok(|(cs_id, _edges)| cs_id)
+            .boxed())
+    }
+
+    /// Returns a stream of all ancestors of any changeset in heads, excluding
+    /// any ancestor of any changeset in common, together with their parents.
+    ///
+    /// The parents are taken from the edges fetched during the traversal,
+    /// and include all parents of each changeset, even those that are not
+    /// part of the difference.
+    pub async fn ancestors_difference_with_parents_stream(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<BoxStream<'static, Result<(ChangesetId, ChangesetParents)>>> {
+        Ok(self
+            .ancestors_difference_edges_stream_with(
+                ctx,
+                heads,
+                common,
+                |_| future::ready(Ok(false)),
+                self.default_prefetch,
+                None,
+            )
+            .await?
+            .map_ok(|edges| {
+                (
+                    edges.node.cs_id,
+                    edges
+                        .parents
+                        .into_iter()
+                        .map(|parent| parent.cs_id)
+                        .collect(),
+                )
+            })
+            .boxed())
+    }
+
+    /// Returns the merge changesets (those with more than one parent) among
+    /// the ancestors of any changeset in heads, excluding any ancestor of any
+    /// changeset in common.
+    ///
+    /// The merges are returned in decreasing generation order, with ties
+    /// broken by changeset id. Parents are counted from the edges fetched
+    /// during the traversal, so no extra storage calls are made.
+    pub async fn merge_commits_in_difference(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        let mut merges = self
+            .ancestors_difference_edges_stream_with(
+                ctx,
+                heads,
+                common,
+                |_| future::ready(Ok(false)),
+                self.default_prefetch,
+                None,
+            )
+            .await?
+            .try_filter_map(|edges| {
+                future::ready(Ok(
+                    (edges.parents.len() > 1).then_some((edges.node.generation, edges.node.cs_id))
+                ))
+            })
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        merges.sort_by(|(gen1, cs_id1), (gen2, cs_id2)| gen2.cmp(gen1).then(cs_id1.cmp(cs_id2)));
+        Ok(merges.into_iter().map(|(_, cs_id)| cs_id).collect())
+    }
+
+    /// Returns all ancestors of any changeset in heads, excluding
+    /// any ancestor of any changeset in common and any changeset
+    /// that satisfies a given property.
+    ///
+    /// Note: The property needs to be monotonic i.e. if the
+    /// property holds for one changeset then it has to hold
+    /// for all its parents.
+    pub async fn ancestors_difference_with<MonotonicProperty, Out>(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        monotonic_property: MonotonicProperty,
+    ) -> Result<Vec<ChangesetId>>
+    where
+        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+        Out: Future<Output = Result<bool>> + Send,
+    {
+        self.ancestors_difference_stream_with(ctx, heads, common, monotonic_property)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// Returns all ancestors of any changeset in heads, excluding
+    /// any ancestor of any changeset in common.
+    pub async fn ancestors_difference(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        self.ancestors_difference_stream(ctx, heads, common)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// Returns a stream of the changesets reachable from any changeset in
+    /// heads by following only merge parent edges (i.e. parents other than
+    /// the first parent), including the heads themselves.
+    ///
+    /// Changesets are yielded in decreasing generation order.
+    pub async fn second_parent_ancestors_stream(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>> {
+        struct SecondParentAncestorsState {
+            commit_graph: CommitGraph,
+            ctx: CoreContext,
+            heads: ChangesetFrontier,
+        }
+
+        let heads = self.changeset_frontier(ctx, heads).await?;
+
+        Ok(stream::try_unfold(
+            Box::new(SecondParentAncestorsState {
+                commit_graph: self.clone(),
+                ctx: ctx.clone(),
+                heads,
+            }),
+            move |mut state| async move {
+                let SecondParentAncestorsState {
+                    commit_graph,
+                    ctx,
+                    heads,
+                } = &mut *state;
+
+                if let Some((_, cs_ids)) = heads.pop_last() {
+                    let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
+                    let all_edges = commit_graph
+                        .storage
+                        .fetch_many_edges_required(ctx, &cs_ids, Prefetch::None)
+                        .await?;
+
+                    for (_, edges) in all_edges.into_iter() {
+                        for parent in edges.parents.into_iter().skip(1) {
+                            heads
+                                .entry(parent.generation)
+                                .or_default()
+                                .insert(parent.cs_id);
+                        }
+                    }
+
+                    anyhow::Ok(Some((stream::iter(cs_ids).map(Ok), state)))
+                } else {
+                    Ok(None)
+                }
+            },
+        )
+        .try_flatten()
+        .boxed())
+    }
+
+    /// Returns all ancestors of any changeset in heads, excluding any
+    /// ancestor of any changeset in any of the commons.
+    ///
+    /// This is equivalent to `ancestors_difference(heads, commons.concat())`,
+    /// but lowers a separate frontier for each group in commons, which can
+    /// be cheaper when the groups have very different generation numbers.
+    pub async fn ancestors_difference_multi_common(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        commons: Vec<Vec<ChangesetId>>,
+    ) -> Result<Vec<ChangesetId>> {
+        let (mut heads, mut commons) = futures::try_join!(
+            self.changeset_frontier(ctx, heads),
+            future::try_join_all(
+                commons
+                    .into_iter()
+                    .map(|common| self.changeset_frontier(ctx, common))
+            ),
+        )?;
+
+        let mut ancestors = vec![];
+        while let Some((generation, cs_ids)) = heads.pop_last() {
+            future::try_join_all(
+                commons
+                    .iter_mut()
+                    .map(|common| self.lower_frontier(ctx, common, generation)),
+            )
+            .await?;
+            // Frontiers that have been lowered past all roots can't exclude
+            // anything anymore.
+            commons.retain(|common| !common.is_empty());
+
+            let cs_ids_not_excluded = cs_ids
+                .into_iter()
+                .filter(|cs_id| {
+                    !commons
+                        .iter()
+                        .any(|common| common.highest_generation_contains(*cs_id, generation))
+                })
+                .collect::<Vec<_>>();
+
+            let all_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &cs_ids_not_excluded, self.default_prefetch)
+                .await?;
+
+            for edges in all_edges.values() {
+                for parent in edges.parents.iter() {
+                    heads
+                        .entry(parent.generation)
+                        .or_default()
+                        .insert(parent.cs_id);
+                }
+            }
+
+            ancestors.extend(cs_ids_not_excluded);
+        }
+
+        Ok(ancestors)
+    }
+
+    /// Returns the ancestors of any changeset in heads, excluding any
+    /// ancestor of any changeset in common, grouped by the head they are
+    /// attributed to.
+    ///
+    /// A changeset that is an ancestor of several heads is attributed to the
+    /// smallest of them by changeset id, so that each changeset is returned
+    /// once and the attribution doesn't depend on the order of heads. Every
+    /// head is a key of the returned map, even if no changeset is attributed
+    /// to it. The changesets of each head are in decreasing generation order.
+    pub async fn ancestors_difference_by_head(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        let heads: BTreeSet<ChangesetId> = heads.into_iter().collect();
+        let mut excluded = common;
+        let mut by_head = HashMap::with_capacity(heads.len());
+
+        for head in heads {
+            let ancestors = self
+                .ancestors_difference(ctx, vec![head], excluded.clone())
+                .await?;
+            by_head.insert(head, ancestors);
+            excluded.push(head);
+        }
+
+        Ok(by_head)
+    }
+
+    /// Returns the ancestors of any changeset in `a` that are not ancestors
+    /// of any changeset in `b`, and the ancestors of any changeset in `b`
+    /// that are not ancestors of any changeset in `a`.
+    ///
+    /// This is equivalent to calling `ancestors_difference` twice with
+    /// swapped arguments, but both sides are traversed together in a single
+    /// pass. Changesets reachable from both sides are tracked in a shared
+    /// frontier that is lowered as the traversal goes, exactly like the
+    /// common frontier of `ancestors_difference`.
+    ///
+    /// Both outputs are in decreasing generation order.
+    pub async fn ancestors_symmetric_difference(
+        &self,
+        ctx: &CoreContext,
+        a: Vec<ChangesetId>,
+        b: Vec<ChangesetId>,
+    ) -> Result<(Vec<ChangesetId>, Vec<ChangesetId>)> {
+        let (mut frontier_a, mut frontier_b) = futures::try_join!(
+            self.changeset_frontier(ctx, a),
+            self.changeset_frontier(ctx, b)
+        )?;
+        let mut shared = ChangesetFrontier::new();
+
+        let mut only_a = vec![];
+        let mut only_b = vec![];
+        loop {
+            let generation = match (frontier_a.last_key_value(), frontier_b.last_key_value()) {
+                (None, None) => break,
+                (Some((generation, _)), None) | (None, Some((generation, _))) => *generation,
+                (Some((generation_a, _)), Some((generation_b, _))) => {
+                    std::cmp::max(*generation_a, *generation_b)
+                }
+            };
+
+            let pop_generation = |frontier: &mut ChangesetFrontier| match frontier.last_key_value()
+            {
+                Some((gen, _)) if *gen == generation => frontier
+                    .pop_last()
+                    .map(|(_, cs_ids)| cs_ids)
+                    .unwrap_or_default(),
+                _ => HashSet::new(),
+            };
+            let cs_ids_a = pop_generation(&mut frontier_a);
+            let cs_ids_b = pop_generation(&mut frontier_b);
+
+            self.lower_frontier(ctx, &mut shared, generation).await?;
+
+            // Changesets that are reached from both sides at this generation
+            // are shared, and so are all of their ancestors.
+            for cs_id in cs_ids_a.intersection(&cs_ids_b) {
+                shared.entry(generation).or_default().insert(*cs_id);
+            }
+
+            let new_a = cs_ids_a
+                .into_iter()
+                .filter(|cs_id| !shared.highest_generation_contains(*cs_id, generation))
+                .collect::<Vec<_>>();
+            let new_b = cs_ids_b
+                .into_iter()
+                .filter(|cs_id| !shared.highest_generation_contains(*cs_id, generation))
+                .collect::<Vec<_>>();
+
+            let all_edges = self
+                .storage
+                .fetch_many_edges_required(
+                    ctx,
+                    &[new_a.as_slice(), new_b.as_slice()].concat(),
+                    self.default_prefetch,
+                )
+                .await?;
+
+            for (cs_ids, frontier) in [(&new_a, &mut frontier_a), (&new_b, &mut frontier_b)] {
+                for cs_id in cs_ids {
+                    let edges = all_edges
+                        .get(cs_id)
+                        .ok_or_else(|| anyhow!("Missing changeset edges for {}", cs_id))?;
+                    for parent in edges.parents.iter() {
+                        frontier
+                            .entry(parent.generation)
+                            .or_default()
+                            .insert(parent.cs_id);
+                    }
+                }
+            }
+
+            only_a.extend(new_a);
+            only_b.extend(new_b);
+        }
+
+        Ok((only_a, only_b))
+    }
+
+    /// Returns all ancestors of any changeset in heads, excluding any
+    /// ancestor of any changeset in common, as long as there are at most
+    /// cap of them.
+    ///
+    /// Returns `CommitGraphError::AncestorsDifferenceCapExceeded` as soon as
+    /// more than cap changesets are found, without computing the rest of
+    /// the difference.
+    pub async fn ancestors_difference_capped(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        cap: usize,
+    ) -> Result<Vec<ChangesetId>> {
+        let mut stream = self.ancestors_difference_stream(ctx, heads, common).await?;
+        let mut cs_ids = vec![];
+
+        while let Some(cs_id) = stream.try_next().await? {
+            if cs_ids.len() == cap {
+                return Err(CommitGraphError::AncestorsDifferenceCapExceeded { cap }.into());
+            }
+            cs_ids.push(cs_id);
+        }
+
+        Ok(cs_ids)
+    }
+
+    /// Returns a stream of all ancestors of any changeset in heads that are
+    /// at most max_depth edges away from a head, together with their depth.
+    ///
+    /// The depth of a changeset is the minimum number of parent edges
+    /// between it and any of the heads, so heads themselves have depth 0.
+    /// Changesets are yielded in increasing order of depth.
+    pub async fn ancestors_with_depth_stream(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        max_depth: u64,
+    ) -> Result<BoxStream<'static, Result<(ChangesetId, u64)>>> {
+        struct AncestorsWithDepthState {
+            commit_graph: CommitGraph,
+            ctx: CoreContext,
+            current_level: Vec<ChangesetId>,
+            depth: u64,
+            visited: HashSet<ChangesetId>,
+        }
+
+        let heads = self.changeset_frontier(ctx, heads).await?.changesets();
+
+        Ok(stream::try_unfold(
+            Box::new(AncestorsWithDepthState {
+                commit_graph: self.clone(),
+                ctx: ctx.clone(),
+                visited: heads.iter().copied().collect(),
+                current_level: heads,
+                depth: 0,
+            }),
+            move |mut state| async move {
+                let AncestorsWithDepthState {
+                    commit_graph,
+                    ctx,
+                    current_level,
+                    depth,
+                    visited,
+                } = &mut *state;
+
+                if current_level.is_empty() {
+                    return Ok(None);
+                }
+
+                let level = std::mem::take(current_level);
+                let level_depth = *depth;
+
+                // Breadth-first search guarantees that each changeset is
+                // first visited at its minimum depth.
+                if level_depth < max_depth {
+                    let all_edges = commit_graph
+                        .storage
+                        .fetch_many_edges_required(ctx, &level, commit_graph.default_prefetch)
+                        .await?;
+
+                    for edges in all_edges.values() {
+                        for parent in edges.parents.iter() {
+                            if visited.insert(parent.cs_id) {
+                                current_level.push(parent.cs_id);
+                            }
+                        }
+                    }
+                    *depth += 1;
+                }
+
+                anyhow::Ok(Some((
+                    stream::iter(level).map(move |cs_id| Ok((cs_id, level_depth))),
+                    state,
+                )))
+            },
+        )
+        .try_flatten()
+        .boxed())
+    }
+
+    /// Returns up to `count` consecutive changesets of the first-parent
+    /// history starting `distance` first-parent steps back from `known`.
+    ///
+    /// This addresses changesets by their location relative to a known
+    /// changeset: for example `(known, 0, 1)` is `known` itself, and
+    /// `(known, 2, 3)` is its second, third and fourth first-parent
+    /// ancestors. The starting changeset is found through the first-parent
+    /// skew ancestors, so going back a large distance does not fetch every
+    /// changeset on the way.
+    ///
+    /// If the root is reached before `count` changesets, fewer are returned.
+    /// If `known` has fewer than `distance` first-parent ancestors, an error
+    /// is returned.
+    pub async fn location_to_changesets(
+        &self,
+        ctx: &CoreContext,
+        known: ChangesetId,
+        distance: u64,
+        count: u64,
+    ) -> Result<Vec<ChangesetId>> {
+        let depth = self.first_parent_depth(ctx, known).await?;
+        let target_depth = depth.checked_sub(distance).ok_or_else(|| {
+            anyhow!(
+                "Changeset {} has {} first-parent ancestors, cannot go back {} steps",
+                known,
+                depth,
+                distance
+            )
+        })?;
+        let start = self
+            .p1_linear_level_ancestor(ctx, known, target_depth)
+            .await?
+            .ok_or_else(|| {
+                anyhow!(
+                    "Missing first-parent ancestor of {} at depth {}",
+                    known,
+                    target_depth
+                )
+            })?;
+
+        self.first_parent_history_stream(ctx, start.cs_id)
+            .await?
+            .take(usize::try_from(count)?)
+            .try_collect()
+            .await
+    }
+
+    /// Returns the location of a changeset relative to the given master
+    /// heads, i.e. a `(head, distance)` pair such that `cs_id` is `distance`
+    /// first-parent steps back from `head`. This is the inverse of
+    /// `location_to_changesets`.
+    ///
+    /// If the changeset is on the first-parent history of several heads, the
+    /// one with the smallest distance is used, with ties broken by the order
+    /// of the heads. Returns `None` if the changeset is not on the
+    /// first-parent history of any of the heads.
+    pub async fn changeset_to_location(
+        &self,
+        ctx: &CoreContext,
+        master_heads: Vec<ChangesetId>,
+        cs_id: ChangesetId,
+    ) -> Result<Option<(ChangesetId, u64)>> {
+        let depth = self.first_parent_depth(ctx, cs_id).await?;
+
+        let locations = future::try_join_all(master_heads.into_iter().map(|head| async move {
+            let head_depth = self.first_parent_depth(ctx, head).await?;
+            if head_depth < depth {
+                return anyhow::Ok(None);
+            }
+            let ancestor = self.p1_linear_level_ancestor(ctx, head, depth).await?;
+            Ok(ancestor
+                .filter(|ancestor| ancestor.cs_id == cs_id)
+                .map(|_| (head, head_depth - depth)))
+        }))
+        .await?;
+
+        Ok(locations
+            .into_iter()
+            .flatten()
+            .min_by_key(|(_, distance)| *distance))
+    }
+
+    /// Returns a stream of the first-parent history of head, i.e. head,
+    /// its first parent, the first parent of that, and so on until a root
+    /// changeset is reached.
+    ///
+    /// Changesets are yielded in decreasing generation order.
+    pub async fn first_parent_history_stream(
+        &self,
+        ctx: &CoreContext,
+        head: ChangesetId,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>> {
+        struct FirstParentHistoryState {
+            commit_graph: CommitGraph,
+            ctx: CoreContext,
+            next: Option<ChangesetId>,
+        }
+
+        Ok(stream::try_unfold(
+            Box::new(FirstParentHistoryState {
+                commit_graph: self.clone(),
+                ctx: ctx.clone(),
+                next: Some(head),
+            }),
+            move |mut state| async move {
+                let FirstParentHistoryState {
+                    commit_graph,
+                    ctx,
+                    next,
+                } = &mut *state;
+
+                match next.take() {
+                    Some(cs_id) => {
+                        let edges = commit_graph
+                            .storage
+                            .fetch_many_edges_required(ctx, &[cs_id], commit_graph.default_prefetch)
+                            .await?
+                            .remove(&cs_id)
+                            .ok_or_else(|| {
+                                anyhow!("Missing changeset in commit graph: {}", cs_id)
+                            })?;
+
+                        *next = edges.parents.first().map(|parent| parent.cs_id);
+
+                        anyhow::Ok(Some((cs_id, state)))
+                    }
+                    None => Ok(None),
+                }
+            },
+        )
+        .boxed())
+    }
+
+    /// Returns all root changesets (changesets with no parents) that are
+    /// ancestors of any changeset in heads, sorted by changeset id.
+    pub async fn roots_reachable_from(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        let mut frontier = self.changeset_frontier(ctx, heads).await?;
+        let mut roots = vec![];
+
+        while let Some((_, cs_ids)) = frontier.pop_last() {
+            let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
+            let all_edges = self
+                .storage
+                .fetch_many_edges_required(ctx, &cs_ids, Prefetch::None)
+                .await?;
+
+            for (cs_id, edges) in all_edges {
+                match (edges.parents.as_slice(), edges.merge_ancestor) {
+                    ([], _) => roots.push(cs_id),
+                    // The merge ancestor of a single-parent changeset is the
+                    // most recent ancestor that is a merge or a root, so we
+                    // can jump straight to it.
+                    ([_], Some(merge_ancestor)) => frontier
+                        .entry(merge_ancestor.generation)
+                        .or_default()
+                        .insert(merge_ancestor.cs_id),
+                    (parents, _) => {
+                        for parent in parents {
+                            frontier
+                                .entry(parent.generation)
+                                .or_default()
+                                .insert(parent.cs_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        roots.sort();
+        Ok(roots)
+    }
+
+    /// Returns the number of ancestors of any changeset in heads, excluding
+    /// any ancestor of any changeset in common, without collecting them.
+    pub async fn count_ancestors_difference(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<u64> {
+        self.ancestors_difference_stream(ctx, heads, common)
+            .await?
+            .try_fold(0, |count, _| future::ready(Ok(count + 1)))
+            .await
+    }
+
+    /// Returns a histogram of the generations of all ancestors of any
+    /// changeset in heads, mapping the start of each generation bucket
+    /// (a multiple of `bucket_size`) to the number of ancestors whose
+    /// generation falls in it. Buckets without any ancestors are omitted.
+    ///
+    /// Each ancestor is counted once, so the counts sum up to what
+    /// `count_ancestors_difference(heads, [])` would return.
+    pub async fn generation_histogram(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        bucket_size: u64,
+    ) -> Result<BTreeMap<u64, u64>> {
+        if bucket_size == 0 {
+            return Err(anyhow!("Generation histogram bucket size must be positive"));
+        }
+
+        self.ancestors_difference_edges_stream_with(
+            ctx,
+            heads,
+            vec![],
+            |_| future::ready(Ok(false)),
+            self.default_prefetch,
+            None,
+        )
+        .await?
+        .try_fold(BTreeMap::new(), |mut histogram, edges| {
+            let generation = edges.node.generation.value();
+            *histogram
+                .entry(generation - generation % bucket_size)
+                .or_insert(0) += 1;
+            future::ready(Ok(histogram))
+        })
+        .await
+    }
+
+    /// Returns the given changesets reordered so that ancestors come before
+    /// their descendants, with ties broken by generation number and then by
+    /// changeset id, the same order as `topo_order_stream`.
+    ///
+    /// A changeset always has a higher generation than any of its ancestors,
+    /// and changesets with the same generation can't be ancestors of each
+    /// other. So sorting by generation is enough, and only the generations
+    /// of the given changesets are fetched, in a single batch. All of the
+    /// changesets must exist.
+    pub async fn sort_topological(
+        &self,
+        ctx: &CoreContext,
+        mut cs_ids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        let generations = self
+            .changeset_generation_many_required(ctx, cs_ids.clone())
+            .await?;
+        cs_ids.sort_by_key(|cs_id| (generations[cs_id], *cs_id));
+        Ok(cs_ids)
+    }
+
+    /// Returns a stream of all ancestors of any changeset in heads, excluding
+    /// any ancestor of any changeset in common, in topological order (parents
+    /// before children).
+    ///
+    /// Ties are broken by generation number and then by changeset id, so
+    /// the order is reproducible across runs.
+    ///
+    /// Note: This buffers the whole difference set together with its edges
+    /// in memory before yielding anything, so it should only be used when
+    /// the difference is known to be bounded.
+    pub async fn topo_order_stream(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<BoxStream<'static, ChangesetId>> {
+        let cs_ids: Vec<ChangesetId> = self
+            .ancestors_difference_stream(ctx, heads, common)
+            .await?
+            .try_collect()
+            .await?;
+        let all_edges = self
+            .storage
+            .fetch_many_edges_required(ctx, &cs_ids, Prefetch::None)
+            .await?;
+
+        let mut in_degree: HashMap<ChangesetId, usize> = Default::default();
+        let mut children: HashMap<ChangesetId, Vec<ChangesetNode>> = Default::default();
+        let mut ready: BTreeSet<(Generation, ChangesetId)> = Default::default();
+
+        for (cs_id, edges) in all_edges.iter() {
+            let mut degree = 0;
+            for parent in edges.parents.iter() {
+                if all_edges.contains_key(&parent.cs_id) {
+                    children.entry(parent.cs_id).or_default().push(edges.node);
+                    degree += 1;
+                }
+            }
+            if degree == 0 {
+                ready.insert((edges.node.generation, *cs_id));
+            } else {
+                in_degree.insert(*cs_id, degree);
+            }
+        }
+
+        let mut sorted_cs_ids = Vec::with_capacity(all_edges.len());
+        while let Some((_, cs_id)) = ready.pop_first() {
+            sorted_cs_ids.push(cs_id);
+            for child in children.remove(&cs_id).unwrap_or_default() {
+                if let Some(degree) = in_degree.get_mut(&child.cs_id) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        in_degree.remove(&child.cs_id);
+                        ready.insert((child.generation, child.cs_id));
+                    }
+                }
+            }
+        }
+
+        Ok(stream::iter(sorted_cs_ids).boxed())
+    }
+
+    /// Returns the subgraph induced by the ancestors of any changeset in
+    /// heads, excluding any ancestor of any changeset in common.
+    ///
+    /// Each changeset is returned together with the subset of its parents
+    /// that are also in the subgraph, so the result doesn't reference any
+    /// changeset outside of it. Changesets are returned in topological order
+    /// (parents before children), with ties broken by generation number and
+    /// then by changeset id.
+    pub async fn extract_subgraph(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<Vec<(ChangesetId, ChangesetParents)>> {
+        let cs_ids = self.ancestors_difference(ctx, heads, common).await?;
+        let all_edges = self
+            .storage
+            .fetch_many_edges_required(ctx, &cs_ids, Prefetch::None)
+            .await?;
+
+        // A parent always has a lower generation number than its children,
+        // so sorting by generation number gives a topological order.
+        let mut nodes = all_edges
+            .values()
+            .map(|edges| edges.node)
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|node| (node.generation, node.cs_id));
+
+        Ok(nodes
+            .into_iter()
+            .map(|node| {
+                let parents = all_edges[&node.cs_id]
+                    .parents
+                    .iter()
+                    .map(|parent| parent.cs_id)
+                    .filter(|parent| all_edges.contains_key(parent))
+                    .collect();
+                (node.cs_id, parents)
+            })
+            .collect())
+    }
+
+    /// Returns the ancestors of any changeset in heads, excluding any
+    /// ancestor of any changeset in common, encoded as linear segments.
+    ///
+    /// Each segment is returned as (low, high, length), where low is the
+    /// first changeset of the segment and high is the last. Every changeset
+    /// in a segment other than low has exactly one parent, which is the
+    /// previous changeset in the segment, and every changeset in a segment
+    /// other than high has exactly one child in the difference, which is
+    /// the next changeset in the segment. Merges and branch points
+    /// therefore always start a new segment.
+    ///
+    /// Segments are returned in topological order of their low changesets.
+    pub async fn ancestors_segments(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+    ) -> Result<Vec<(ChangesetId, ChangesetId, u64)>> {
+        let cs_ids = self.ancestors_difference(ctx, heads, common).await?;
+        let all_edges = self
+            .storage
+            .fetch_many_edges_required(ctx, &cs_ids, Prefetch::None)
+            .await?;
+
+        let mut children_count: HashMap<ChangesetId, usize> = Default::default();
+        for edges in all_edges.values() {
+            for parent in edges.parents.iter() {
+                if all_edges.contains_key(&parent.cs_id) {
+                    *children_count.entry(parent.cs_id).or_default() += 1;
+                }
+            }
+        }
+
+        let mut nodes = all_edges
+            .values()
+            .map(|edges| edges.node)
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|node| (node.generation, node.cs_id));
+
+        let mut segments: Vec<(ChangesetId, ChangesetId, u64)> = vec![];
+        // Index into segments of the segment that each changeset is the
+        // current high of.
+        let mut segment_index: HashMap<ChangesetId, usize> = Default::default();
+
+        for node in nodes {
+            let parents = &all_edges[&node.cs_id].parents;
+            let extended_segment = match parents.as_slice() {
+                [parent] if children_count.get(&parent.cs_id) == Some(&1) => {
+                    segment_index.remove(&parent.cs_id)
+                }
+                _ => None,
+            };
+
+            match extended_segment {
+                Some(index) => {
+                    let (_, high, length) = &mut segments[index];
+                    *high = node.cs_id;
+                    *length += 1;
+                    segment_index.insert(node.cs_id, index);
+                }
+                None => {
+                    segment_index.insert(node.cs_id, segments.len());
+                    segments.push((node.cs_id, node.cs_id, 1));
+                }
+            }
+        }
+
+        Ok(segments)
+    }
+
+    /// Returns a stream of all descendants of any changeset in heads,
+    /// excluding any descendant of any changeset in common and any
+    /// changeset that satisfies a given property.
+    ///
+    /// Changesets are yielded in increasing generation order.
+    ///
+    /// Note: The property needs to be monotonic i.e. if the
+    /// property holds for one changeset then it has to hold
+    /// for all its children.
+    pub async fn descendants_difference_stream_with<MonotonicProperty, Out>(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        common: Vec<ChangesetId>,
+        monotonic_property: MonotonicProperty,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>>
+    where
+        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+        Out: Future<Output = Result<bool>> + Send,
+    {
+        struct DescendantsDifferenceState<P> {
+            commit_graph: CommitGraph,
+            ctx: CoreContext,
+            heads: ChangesetFrontier,
+            common: ChangesetFrontier,
+            monotonic_property: P,
+        }
+
+        let (heads, common) = futures::try_join!(
+            self.changeset_frontier(ctx, heads),
+            self.changeset_frontier(ctx, common)
+        )?;
+
+        Ok(stream::try_unfold(
+            Box::new(DescendantsDifferenceState {
+                commit_graph: self.clone(),
+                ctx: ctx.clone(),
+                heads,
+                common,
+                monotonic_property,
+            }),
+            move |mut state| async move {
+                let DescendantsDifferenceState {
+                    commit_graph,
+                    ctx,
+                    heads,
+                    common,
+                    monotonic_property,
+                } = &mut *state;
+
+                if let Some((generation, cs_ids)) = heads.pop_first() {
+                    commit_graph.raise_frontier(ctx, common, generation).await?;
+
+                    let mut cs_ids_not_excluded = vec![];
+                    for cs_id in cs_ids {
+                        if !common.lowest_generation_contains(cs_id, generation)
+                            && !monotonic_property(cs_id).await?
+                        {
+                            cs_ids_not_excluded.push(cs_id)
+                        }
+                    }
+
+                    heads.extend(
+                        commit_graph
+                            .children_with_generations(ctx, &cs_ids_not_excluded)
+                            .await?,
+                    );
+
                     anyhow::Ok(Some((stream::iter(cs_ids_not_excluded).map(Ok), state)))
                 } else {
                     Ok(None)
@@ -274,59 +2427,51 @@ impl CommitGraph {
         .boxed())
     }
 
-    pub async fn ancestors_difference_stream(
+    /// Returns a stream of all descendants of any changeset in heads,
+    /// excluding any descendant of any changeset in common.
+    ///
+    /// Changesets are yielded in increasing generation order.
+    pub async fn descendants_difference_stream(
         &self,
         ctx: &CoreContext,
         heads: Vec<ChangesetId>,
         common: Vec<ChangesetId>,
     ) -> Result<BoxStream<'static, Result<ChangesetId>>> {
-        self.ancestors_difference_stream_with(ctx, heads, common, |_| future::ready(Ok(false)))
+        self.descendants_difference_stream_with(ctx, heads, common, |_| future::ready(Ok(false)))
             .await
     }
 
-    /// Returns all ancestors of any changeset in heads, excluding
-    /// any ancestor of any changeset in common and any changeset
-    /// that satisfies a given property.
-    ///
-    /// Note: The property needs to be monotonic i.e. if the
-    /// property holds for one changeset then it has to hold
-    /// for all its parents.
-    pub async fn ancestors_difference_with<MonotonicProperty, Out>(
+    pub async fn range_stream(
         &self,
         ctx: &CoreContext,
-        heads: Vec<ChangesetId>,
-        common: Vec<ChangesetId>,
-        monotonic_property: MonotonicProperty,
-    ) -> Result<Vec<ChangesetId>>
-    where
-        MonotonicProperty: Fn(ChangesetId) -> Out + Send + Sync + 'static,
-        Out: Future<Output = Result<bool>> + Send,
-    {
-        self.ancestors_difference_stream_with(ctx, heads, common, monotonic_property)
-            .await?
-            .try_collect()
-            .await
+        start_id: ChangesetId,
+        end_id: ChangesetId,
+    ) -> Result<BoxStream<'static, ChangesetId>> {
+        self.range_stream_impl(ctx, start_id, end_id, None).await
     }
 
-    /// Returns all ancestors of any changeset in heads, excluding
-    /// any ancestor of any changeset in common.
-    pub async fn ancestors_difference(
+    /// Same as `range_stream`, but checks the given cancellation token
+    /// before each step of the walk from `end_id` down to `start_id`, which
+    /// is the part of `range_stream` that fetches from storage. Once the
+    /// token is cancelled, no more edges are fetched and
+    /// `CommitGraphError::Cancelled` is returned.
+    pub async fn range_stream_with_cancellation(
         &self,
         ctx: &CoreContext,
-        heads: Vec<ChangesetId>,
-        common: Vec<ChangesetId>,
-    ) -> Result<Vec<ChangesetId>> {
-        self.ancestors_difference_stream(ctx, heads, common)
-            .await?
-            .try_collect()
+        start_id: ChangesetId,
+        end_id: ChangesetId,
+        cancellation: CancellationToken,
+    ) -> Result<BoxStream<'static, ChangesetId>> {
+        self.range_stream_impl(ctx, start_id, end_id, Some(cancellation))
             .await
     }
 
-    pub async fn range_stream(
+    async fn range_stream_impl(
         &self,
         ctx: &CoreContext,
         start_id: ChangesetId,
         end_id: ChangesetId,
+        cancellation: Option<CancellationToken>,
     ) -> Result<BoxStream<'static, ChangesetId>> {
         let (start_generation, mut frontier) = futures::try_join!(
             self.changeset_generation_required(ctx, start_id),
@@ -337,10 +2482,16 @@ impl CommitGraph {
         let mut reached_start = false;
 
         while let Some((gen, cs_ids)) = frontier.pop_last() {
+            if let Some(cancellation) = &cancellation {
+                if cancellation.is_cancelled() {
+                    return Err(CommitGraphError::Cancelled.into());
+                }
+            }
+
             let cs_ids = cs_ids.into_iter().collect::<Vec<_>>();
             let all_edges = self
                 .storage
-                .fetch_many_edges_required(ctx, &cs_ids, Prefetch::for_p1_linear_traversal())
+                .fetch_many_edges_required(ctx, &cs_ids, self.default_prefetch)
                 .await?;
 
             reached_start |= cs_ids.contains(&start_id);
@@ -398,6 +2549,33 @@ impl CommitGraph {
         .boxed())
     }
 
+    /// Same as `range_stream`, but stops after yielding `limit` changesets.
+    ///
+    /// The changesets are yielded in the same order as `range_stream`, so
+    /// the output is a topologically ordered prefix of the full range.
+    pub async fn range_stream_limited(
+        &self,
+        ctx: &CoreContext,
+        start_id: ChangesetId,
+        end_id: ChangesetId,
+        limit: usize,
+    ) -> Result<BoxStream<'static, ChangesetId>> {
+        if limit == 0 {
+            // Check that both changesets exist, without walking the range.
+            futures::try_join!(
+                self.changeset_generation_required(ctx, start_id),
+                self.changeset_generation_required(ctx, end_id)
+            )?;
+            return Ok(stream::empty().boxed());
+        }
+
+        Ok(self
+            .range_stream(ctx, start_id, end_id)
+            .await?
+            .take(limit)
+            .boxed())
+    }
+
     /// Returns all of the highest generation changesets that
     /// are ancestors of both u and v, sorted by changeset id.
     pub async fn common_base(
@@ -405,6 +2583,36 @@ impl CommitGraph {
         ctx: &CoreContext,
         u: ChangesetId,
         v: ChangesetId,
+    ) -> Result<Vec<ChangesetId>> {
+        let mut common_base = self.common_base_unsorted(ctx, u, v).await?;
+        common_base.sort();
+        Ok(common_base)
+    }
+
+    /// Returns the common base of u and v that has the smallest changeset
+    /// id, or None if u and v have no common ancestors.
+    ///
+    /// This is a deterministic single result version of common_base.
+    pub async fn lowest_common_ancestor(
+        &self,
+        ctx: &CoreContext,
+        u: ChangesetId,
+        v: ChangesetId,
+    ) -> Result<Option<ChangesetId>> {
+        Ok(self
+            .common_base_unsorted(ctx, u, v)
+            .await?
+            .into_iter()
+            .min())
+    }
+
+    /// Returns all of the highest generation changesets that
+    /// are ancestors of both u and v, in no particular order.
+    async fn common_base_unsorted(
+        &self,
+        ctx: &CoreContext,
+        u: ChangesetId,
+        v: ChangesetId,
     ) -> Result<Vec<ChangesetId>> {
         let (mut u_frontier, mut v_frontier) =
             futures::try_join!(self.single_frontier(ctx, u), self.single_frontier(ctx, v))?;
@@ -421,9 +2629,8 @@ impl CommitGraph {
 
             // Check if the highest generation of u_frontier intersects with v_frontier
             // and return the intersection if so.
-            let mut intersection = u_frontier.highest_generation_intersection(&v_frontier);
+            let intersection = u_frontier.highest_generation_intersection(&v_frontier);
             if !intersection.is_empty() {
-                intersection.sort();
                 return Ok(intersection);
             }
 
@@ -465,6 +2672,181 @@ impl CommitGraph {
         }
     }
 
+    /// Returns all of the highest generation changesets that are
+    /// ancestors of every changeset in cs_ids, sorted by changeset id.
+    ///
+    /// Duplicates in cs_ids are ignored, so if cs_ids contains a single
+    /// distinct changeset, that changeset is returned. If cs_ids is empty
+    /// then an empty vec is returned.
+    pub async fn common_base_multi(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        let cs_ids = cs_ids
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let mut frontiers =
+            future::try_join_all(cs_ids.iter().map(|cs_id| self.single_frontier(ctx, *cs_id)))
+                .await?;
+
+        if frontiers.len() <= 1 {
+            return Ok(cs_ids);
+        }
+
+        loop {
+            let u_gen = match frontiers[0].last_key_value() {
+                Some((gen, _)) => *gen,
+                // if the first frontier is empty then there are no common ancestors.
+                None => return Ok(vec![]),
+            };
+
+            // lower all other frontiers to the highest generation of the first
+            // frontier. If any of them becomes empty then there are no common
+            // ancestors.
+            for frontier in frontiers[1..].iter_mut() {
+                self.lower_frontier(ctx, frontier, u_gen).await?;
+                if frontier.is_empty() {
+                    return Ok(vec![]);
+                }
+            }
+
+            // Check if the highest generation of the first frontier intersects
+            // with all other frontiers and return the intersection if so.
+            let mut intersection = frontiers[0].highest_generation_intersection(&frontiers[1]);
+            intersection.retain(|cs_id| {
+                frontiers[2..]
+                    .iter()
+                    .all(|frontier| frontier.highest_generation_contains(*cs_id, u_gen))
+            });
+            if !intersection.is_empty() {
+                intersection.sort();
+                return Ok(intersection);
+            }
+
+            let u_highest_generation_edges = match frontiers[0]
+                .last_key_value()
+                .and_then(|(_, cs_ids)| cs_ids.iter().next())
+            {
+                Some(cs_id) => self.storage.fetch_edges_required(ctx, *cs_id).await?,
+                None => return Ok(vec![]),
+            };
+
+            // Try to lower all frontiers to the generation of one of the first
+            // frontier's highest generation changesets' skip tree skew ancestor.
+            if let Some(ancestor) = u_highest_generation_edges.skip_tree_skew_ancestor {
+                let mut lowered_frontiers = frontiers.clone();
+                for frontier in lowered_frontiers.iter_mut() {
+                    self.lower_frontier(ctx, frontier, ancestor.generation)
+                        .await?;
+                }
+
+                // If no changeset is shared by all lowered frontiers then it's safe
+                // to lower, otherwise there might be a higher generation common ancestor.
+                if lowered_frontiers[0].is_disjoint_from_all(&lowered_frontiers[1..]) {
+                    frontiers = lowered_frontiers;
+
+                    continue;
+                }
+            }
+
+            // If we couldn't lower using the skip tree skew ancestor
+            // lower only the highest generation of the first frontier instead.
+            self.lower_frontier_highest_generation(ctx, &mut frontiers[0])
+                .await?;
+        }
+    }
+
+    /// Returns the changesets in cs_ids that are not ancestors of any other
+    /// changeset in cs_ids, sorted by changeset id.
+    pub async fn heads_of_set(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        let all_edges = self
+            .storage
+            .fetch_many_edges_required(ctx, &cs_ids, Prefetch::None)
+            .await?;
+
+        let mut members: ChangesetFrontier = all_edges
+            .values()
+            .map(|edges| (edges.node.cs_id, edges.node.generation))
+            .collect();
+
+        // Frontier of the strict ancestors of the heads found so far.
+        let mut ancestors_frontier = ChangesetFrontier::new();
+        let mut heads = vec![];
+
+        // Process members in decreasing order of generation, so that any
+        // member that is an ancestor of another member is covered by the
+        // ancestors frontier by the time we reach its generation.
+        while let Some((generation, cs_ids)) = members.pop_last() {
+            self.lower_frontier(ctx, &mut ancestors_frontier, generation)
+                .await?;
+
+            for cs_id in cs_ids {
+                if ancestors_frontier.highest_generation_contains(cs_id, generation) {
+                    continue;
+                }
+
+                heads.push(cs_id);
+                if let Some(edges) = all_edges.get(&cs_id) {
+                    ancestors_frontier.extend(
+                        edges
+                            .parents
+                            .iter()
+                            .map(|parent| (parent.cs_id, parent.generation)),
+                    );
+                }
+            }
+        }
+
+        heads.sort();
+        Ok(heads)
+    }
+
+    /// Returns the changesets in cs_ids that are not descendants of any other
+    /// changeset in cs_ids, sorted by changeset id.
+    ///
+    /// If no changeset in cs_ids is an ancestor of another (i.e. cs_ids is
+    /// an antichain), all of them are returned unchanged.
+    pub async fn roots_of_set(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Vec<ChangesetId>> {
+        let mut members = self.changeset_frontier(ctx, cs_ids).await?;
+
+        // Frontier of the strict descendants of the roots found so far.
+        let mut descendants_frontier = ChangesetFrontier::new();
+        let mut roots = vec![];
+
+        // Process members in increasing order of generation, so that any
+        // member that is a descendant of another member is covered by the
+        // descendants frontier by the time we reach its generation.
+        while let Some((generation, cs_ids)) = members.pop_first() {
+            self.raise_frontier(ctx, &mut descendants_frontier, generation)
+                .await?;
+
+            let new_roots = cs_ids
+                .into_iter()
+                .filter(|cs_id| {
+                    !descendants_frontier.lowest_generation_contains(*cs_id, generation)
+                })
+                .collect::<Vec<_>>();
+
+            descendants_frontier.extend(self.children_with_generations(ctx, &new_roots).await?);
+            roots.extend(new_roots);
+        }
+
+        roots.sort();
+        Ok(roots)
+    }
+
     /// Slices ancestors of heads into a sequence of slices for processing.
     ///
     /// Each slice contains a frontier of changesets within a generation range, returning
@@ -488,51 +2870,128 @@ impl CommitGraph {
         NeedsProcessing: Fn(Vec<ChangesetId>) -> Out,
         Out: Future<Output = Result<HashSet<ChangesetId>>>,
     {
-        let mut frontier = self.frontier(ctx, heads).await?;
+        let mut state = self.slice_ancestors_state(ctx, heads, slice_size).await?;
 
-        let max_gen = match frontier.last_key_value() {
-            Some((gen, _)) => gen,
-            None => return Ok(vec![]),
-        };
+        let mut slices = vec![];
+        while let Some(slice) = self
+            .next_ancestors_slice(ctx, &mut state, &needs_processing)
+            .await?
+        {
+            slices.push(slice);
+        }
+
+        Ok(slices.into_iter().rev().collect())
+    }
+
+    /// Same as slice_ancestors, but returns a stream of the slices as they are
+    /// produced instead of collecting them.
+    ///
+    /// Unlike slice_ancestors, the slices are yielded in decreasing order of
+    /// slice_start, as that is the order in which they are discovered.
+    pub async fn slice_ancestors_stream<'a, NeedsProcessing, Out>(
+        &'a self,
+        ctx: &'a CoreContext,
+        heads: Vec<ChangesetId>,
+        needs_processing: NeedsProcessing,
+        slice_size: u64,
+    ) -> Result<BoxStream<'a, Result<(u64, Vec<ChangesetId>)>>>
+    where
+        NeedsProcessing: Fn(Vec<ChangesetId>) -> Out + Send + Sync + 'a,
+        Out: Future<Output = Result<HashSet<ChangesetId>>> + Send + 'a,
+    {
+        let state = self.slice_ancestors_state(ctx, heads, slice_size).await?;
+
+        Ok(stream::try_unfold(
+            Box::new((state, needs_processing)),
+            move |mut state| async move {
+                let (slice_state, needs_processing) = &mut *state;
+                let slice = self
+                    .next_ancestors_slice(ctx, slice_state, needs_processing)
+                    .await?;
+                anyhow::Ok(slice.map(|slice| (slice, state)))
+            },
+        )
+        .boxed())
+    }
+
+    async fn slice_ancestors_state(
+        &self,
+        ctx: &CoreContext,
+        heads: Vec<ChangesetId>,
+        slice_size: u64,
+    ) -> Result<SliceAncestorsState> {
+        let frontier = self.changeset_frontier(ctx, heads).await?;
 
         // The start of the slice is largest number in the sequence
         // 1, slice_size + 1, 2 * slice_size + 1 ...
-        let mut slice_start = ((max_gen.value() - 1) / slice_size) * slice_size + 1;
+        let slice_start = match frontier.last_key_value() {
+            Some((max_gen, _)) => ((max_gen.value() - 1) / slice_size) * slice_size + 1,
+            None => 0,
+        };
+        let finished = frontier.is_empty();
 
-        let mut slices = vec![];
+        Ok(SliceAncestorsState {
+            frontier,
+            slice_start,
+            slice_size,
+            lower_to: None,
+            finished,
+        })
+    }
 
-        // Loop over slices in decreasing order of start generation.
-        loop {
-            let needed_cs_ids = needs_processing(frontier.changesets()).await?;
-            frontier = frontier
-                .into_flat_iter()
-                .filter(|(cs_id, _)| needed_cs_ids.contains(cs_id))
-                .collect();
+    /// Produces the next slice of a slicing traversal, in decreasing order of
+    /// start generation, or None once a fully processed frontier is reached.
+    async fn next_ancestors_slice<NeedsProcessing, Out>(
+        &self,
+        ctx: &CoreContext,
+        state: &mut SliceAncestorsState,
+        needs_processing: &NeedsProcessing,
+    ) -> Result<Option<(u64, Vec<ChangesetId>)>>
+    where
+        NeedsProcessing: Fn(Vec<ChangesetId>) -> Out,
+        Out: Future<Output = Result<HashSet<ChangesetId>>>,
+    {
+        if state.finished {
+            return Ok(None);
+        }
 
-            if frontier.is_empty() {
-                break;
-            }
+        // Lower the frontier to the end of this slice, which was deferred
+        // from the previous slice.
+        if let Some(target_generation) = state.lower_to.take() {
+            self.lower_frontier(ctx, &mut state.frontier, target_generation)
+                .await?;
+        }
 
-            // Only push changesets that are in this slice's range.
-            // Any remaining changesets will be pushed in the next iterations.
-            slices.push((
-                slice_start,
-                frontier.changesets_in_range(
-                    Generation::new(slice_start)..Generation::new(slice_start + slice_size),
-                ),
-            ));
+        let needed_cs_ids = needs_processing(state.frontier.changesets()).await?;
+        state.frontier = std::mem::replace(&mut state.frontier, ChangesetFrontier::new())
+            .into_flat_iter()
+            .filter(|(cs_id, _)| needed_cs_ids.contains(cs_id))
+            .collect();
 
-            if slice_start > 1 {
-                // Lower the frontier to the end of the next slice (current slice_start - 1).
-                self.lower_frontier(ctx, &mut frontier, Generation::new(slice_start - 1))
-                    .await?;
-                slice_start -= slice_size;
-            } else {
-                break;
-            }
+        if state.frontier.is_empty() {
+            state.finished = true;
+            return Ok(None);
         }
 
-        Ok(slices.into_iter().rev().collect())
+        // Only include changesets that are in this slice's range.
+        // Any remaining changesets will be included in the next slices.
+        let slice_start = state.slice_start;
+        let slice = (
+            slice_start,
+            state.frontier.changesets_in_range(
+                Generation::new(slice_start)..Generation::new(slice_start + state.slice_size),
+            ),
+        );
+
+        if slice_start > 1 {
+            // The next slice ends at the current slice_start - 1.
+            state.lower_to = Some(Generation::new(slice_start - 1));
+            state.slice_start -= state.slice_size;
+        } else {
+            state.finished = true;
+        }
+
+        Ok(Some(slice))
     }
 
     /// Returns the children of a single changeset.
@@ -543,4 +3002,171 @@ impl CommitGraph {
     ) -> Result<Vec<ChangesetId>> {
         self.storage.fetch_children(ctx, cs_id).await
     }
+
+    /// Returns the number of children of a single changeset.
+    ///
+    /// Uses `CommitGraphStorage::count_children`, so storage back-ends
+    /// that can count children without fetching them don't transfer the
+    /// children's ids.
+    pub async fn changeset_children_count(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<usize> {
+        self.storage.count_children(ctx, cs_id).await
+    }
+
+    /// Returns a stream of the children of a single changeset.
+    ///
+    /// Note: This is only truly incremental if the storage back-end
+    /// overrides `fetch_children_stream`. None of the existing back-ends
+    /// do, so the children are currently fetched in a single batch.
+    pub async fn changeset_children_stream(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>> {
+        self.storage.fetch_children_stream(ctx, cs_id).await
+    }
+
+    /// Returns the children of multiple changesets.
+    ///
+    /// The returned map contains an entry for every changeset in cs_ids,
+    /// which is empty if the changeset has no children.
+    pub async fn changeset_children_many(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        let mut children = self.storage.fetch_many_children(ctx, &cs_ids).await?;
+        for cs_id in cs_ids {
+            children.entry(cs_id).or_default();
+        }
+        Ok(children)
+    }
+
+    /// Verify that the stored edges of a changeset, which must exist, are
+    /// consistent with the rest of the graph:
+    ///
+    /// - all of its parents exist in the commit graph,
+    /// - its generation is one more than the highest parent generation,
+    /// - its skip tree skew ancestor, if any, has a lower generation and is
+    ///   an ancestor of one of its parents.
+    ///
+    /// Returns a `CommitGraphError` describing the first violated invariant.
+    /// Ancestry of the skew ancestor is checked using the parents' edges, so
+    /// the edges being verified are not relied upon.
+    pub async fn verify_edges(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<()> {
+        let edges = self.storage.fetch_edges_required(ctx, cs_id).await?;
+        let parents: Vec<ChangesetId> = edges.parents.iter().map(|parent| parent.cs_id).collect();
+        let parent_edges = self
+            .storage
+            .fetch_many_edges(ctx, &parents, Prefetch::None)
+            .await?;
+
+        let mut max_parent_gen = 0;
+        for parent in &parents {
+            let parent_edges = parent_edges
+                .get(parent)
+                .ok_or(CommitGraphError::MissingParent {
+                    cs_id,
+                    parent: *parent,
+                })?;
+            max_parent_gen = max_parent_gen.max(parent_edges.node.generation.value());
+        }
+        let expected = max_parent_gen + 1;
+        if edges.node.generation.value() != expected {
+            return Err(CommitGraphError::InconsistentGeneration {
+                cs_id,
+                generation: edges.node.generation.value(),
+                expected,
+            }
+            .into());
+        }
+
+        if let Some(skew_ancestor) = edges.skip_tree_skew_ancestor {
+            if skew_ancestor.generation >= edges.node.generation {
+                return Err(CommitGraphError::SkewAncestorGenerationNotLower {
+                    cs_id,
+                    skew_ancestor: skew_ancestor.cs_id,
+                }
+                .into());
+            }
+            let is_ancestor = future::try_join_all(
+                parents
+                    .iter()
+                    .map(|parent| self.is_ancestor(ctx, skew_ancestor.cs_id, *parent)),
+            )
+            .await?;
+            if !is_ancestor.into_iter().any(|is_ancestor| is_ancestor) {
+                return Err(CommitGraphError::SkewAncestorNotAncestor {
+                    cs_id,
+                    skew_ancestor: skew_ancestor.cs_id,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Walk the ancestry of `start` depth-first, looking for a cycle of
+    /// parent edges, which a well-formed commit graph never contains.
+    ///
+    /// Returns the changesets of the first cycle found, in the order they
+    /// were walked from child to parent, starting and ending with the
+    /// changeset that was revisited. Changesets reachable through several
+    /// paths, as happens below merges, are not cycles and are only walked
+    /// once. Returns None if there is no cycle among the first `max_steps`
+    /// changesets walked, so that deep histories are not walked in full.
+    ///
+    /// This is meant for checking the integrity of a commit graph and
+    /// fetches the parents of one changeset at a time.
+    pub async fn find_cycle(
+        &self,
+        ctx: &CoreContext,
+        start: ChangesetId,
+        max_steps: u64,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        // The path from start to the changeset being walked, along with the
+        // parents of each changeset on it that are yet to be walked.
+        let mut path: Vec<(ChangesetId, Vec<ChangesetId>)> = Vec::new();
+        let mut on_path: HashSet<ChangesetId> = HashSet::new();
+        let mut visited: HashSet<ChangesetId> = HashSet::new();
+        let mut steps = 0;
+        let mut next = Some(start);
+
+        loop {
+            if let Some(cs_id) = next.take() {
+                if on_path.contains(&cs_id) {
+                    let cycle = path
+                        .iter()
+                        .map(|(path_cs_id, _)| *path_cs_id)
+                        .skip_while(|path_cs_id| *path_cs_id != cs_id)
+                        .chain(std::iter::once(cs_id))
+                        .collect();
+                    return Ok(Some(cycle));
+                }
+                if visited.insert(cs_id) {
+                    if steps >= max_steps {
+                        return Ok(None);
+                    }
+                    steps += 1;
+                    let parents = self.changeset_parents_required(ctx, cs_id).await?;
+                    on_path.insert(cs_id);
+                    path.push((cs_id, parents.into_iter().rev().collect()));
+                }
+            }
+
+            match path.last_mut() {
+                Some((_, parents)) if !parents.is_empty() => next = parents.pop(),
+                Some(_) => {
+                    if let Some((cs_id, _)) = path.pop() {
+                        on_path.remove(&cs_id);
+                    }
+                }
+                None => return Ok(None),
+            }
+        }
+    }
 }
@@ -5,20 +5,34 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Poll;
 
 use anyhow::Result;
 use cloned::cloned;
 use commit_graph::CommitGraph;
+use commit_graph::CommitGraphBuilder;
+use commit_graph::CommitGraphError;
+use commit_graph::Frontier;
+use commit_graph_types::edges::ChangesetEdges;
+use commit_graph_types::edges::ChangesetNode;
 use commit_graph_types::storage::CommitGraphStorage;
+use commit_graph_types::storage::Prefetch;
 use context::CoreContext;
+use futures::future;
+use futures::StreamExt;
+use futures::TryStreamExt;
 use in_memory_commit_graph_storage::InMemoryCommitGraphStorage;
 use mononoke_types::ChangesetIdPrefix;
 use mononoke_types::ChangesetIdsResolvedFromPrefix;
+use mononoke_types::Generation;
 use mononoke_types::RepositoryId;
 use smallvec::smallvec;
+use tokio_util::sync::CancellationToken;
 use vec1::vec1;
 
 use crate::utils::*;
@@ -33,15 +47,58 @@ macro_rules! impl_commit_graph_tests {
             test_storage_store_and_fetch,
             test_skip_tree,
             test_p1_linear_tree,
+            test_is_ancestor_batch,
+            test_is_fast_forward,
+            test_distance,
+            test_path_to_ancestor,
             test_ancestors_difference,
+            test_ancestors_difference_missing_edges,
+            test_count_ancestors_difference,
+            test_generation_histogram,
+            test_descendants_difference,
             test_find_by_prefix,
             test_add_recursive,
             test_add_recursive_many_changesets,
+            test_add_many,
             test_ancestors_frontier_with,
+            test_ancestors_frontier_with_concurrency,
+            test_first_ancestor_satisfying,
+            test_ancestors_in_generation_range,
+            test_ancestors_above_generation,
+            test_descendants_in_generation_range,
             test_range_stream,
+            test_traversal_cancellation,
+            test_topo_order_stream,
+            test_sort_topological,
             test_common_base,
+            test_common_base_multi,
+            test_lowest_common_ancestor,
+            test_heads_of_set,
+            test_roots_of_set,
+            test_extract_subgraph,
+            test_highest_generation_head,
+            test_merge_parents,
+            test_merge_commits_in_difference,
+            test_ancestors_segments,
+            test_are_independent,
+            test_ancestors_difference_capped,
+            test_frontier_at_generation,
+            test_frontier_operations,
+            test_ancestors_difference_multi_common,
+            test_ancestors_symmetric_difference,
+            test_ancestors_difference_by_head,
+            test_ancestors_with_depth,
+            test_first_parent_history,
+            test_first_parent_depth,
+            test_location_to_changesets,
+            test_changeset_to_location,
+            test_nearest_ancestor_in_set,
+            test_roots,
             test_slice_ancestors,
             test_children,
+            test_verify_edges,
+            test_find_cycle,
+            test_builder,
         );
     };
 }
@@ -77,6 +134,26 @@ pub async fn test_storage_store_and_fetch(
     assert!(graph.exists(&ctx, name_cs_id("A")).await?);
 
     assert!(!graph.exists(&ctx, name_cs_id("nonexistent")).await?);
+    assert_eq!(
+        graph
+            .exists_many(
+                &ctx,
+                vec![
+                    name_cs_id("A"),
+                    name_cs_id("nonexistent1"),
+                    name_cs_id("G"),
+                    name_cs_id("nonexistent2"),
+                ]
+            )
+            .await?,
+        HashMap::from([
+            (name_cs_id("A"), true),
+            (name_cs_id("nonexistent1"), false),
+            (name_cs_id("G"), true),
+            (name_cs_id("nonexistent2"), false),
+        ])
+    );
+    assert!(graph.exists_many(&ctx, vec![]).await?.is_empty());
     assert_eq!(
         graph
             .changeset_generation(&ctx, name_cs_id("G"))
@@ -85,6 +162,50 @@ pub async fn test_storage_store_and_fetch(
             .value(),
         5
     );
+    assert_eq!(
+        graph
+            .changeset_generation_many(
+                &ctx,
+                vec![
+                    name_cs_id("A"),
+                    name_cs_id("G"),
+                    name_cs_id("F"),
+                    name_cs_id("nonexistent")
+                ]
+            )
+            .await?
+            .into_iter()
+            .map(|(cs_id, generation)| (cs_id, generation.value()))
+            .collect::<HashMap<_, _>>(),
+        HashMap::from([
+            (name_cs_id("A"), 1),
+            (name_cs_id("G"), 5),
+            (name_cs_id("F"), 3)
+        ])
+    );
+    assert_eq!(
+        graph
+            .changeset_generation_many_required(&ctx, vec![name_cs_id("A"), name_cs_id("G")])
+            .await?
+            .into_iter()
+            .map(|(cs_id, generation)| (cs_id, generation.value()))
+            .collect::<HashMap<_, _>>(),
+        HashMap::from([(name_cs_id("A"), 1), (name_cs_id("G"), 5)])
+    );
+    let missing_error = graph
+        .changeset_generation_many_required(
+            &ctx,
+            vec![
+                name_cs_id("A"),
+                name_cs_id("nonexistent1"),
+                name_cs_id("nonexistent2"),
+            ],
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(missing_error.contains(&name_cs_id("nonexistent1").to_string()));
+    assert!(missing_error.contains(&name_cs_id("nonexistent2").to_string()));
     assert_eq!(
         graph
             .changeset_parents(&ctx, name_cs_id("A"))
@@ -109,6 +230,70 @@ pub async fn test_storage_store_and_fetch(
             .as_slice(),
         &[name_cs_id("D"), name_cs_id("F")]
     );
+    assert_eq!(
+        graph
+            .changeset_parents_many(
+                &ctx,
+                vec![name_cs_id("A"), name_cs_id("G"), name_cs_id("nonexistent")]
+            )
+            .await?
+            .into_iter()
+            .map(|(cs_id, parents)| (cs_id, parents.into_vec()))
+            .collect::<HashMap<_, _>>(),
+        HashMap::from([
+            (name_cs_id("A"), vec![]),
+            (name_cs_id("G"), vec![name_cs_id("D"), name_cs_id("F")]),
+        ])
+    );
+    assert!(graph.changeset_parents_many(&ctx, vec![]).await?.is_empty());
+    assert_eq!(
+        graph
+            .changeset_parents_many_required(&ctx, vec![name_cs_id("E")])
+            .await?
+            .into_iter()
+            .map(|(cs_id, parents)| (cs_id, parents.into_vec()))
+            .collect::<HashMap<_, _>>(),
+        HashMap::from([(name_cs_id("E"), vec![name_cs_id("A")])])
+    );
+    let missing_error = graph
+        .changeset_parents_many_required(
+            &ctx,
+            vec![
+                name_cs_id("A"),
+                name_cs_id("nonexistent1"),
+                name_cs_id("nonexistent2"),
+            ],
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(missing_error.contains(&name_cs_id("nonexistent1").to_string()));
+    assert!(missing_error.contains(&name_cs_id("nonexistent2").to_string()));
+    assert_eq!(
+        graph.fetch_edges(&ctx, name_cs_id("G")).await?,
+        storage.fetch_edges(&ctx, name_cs_id("G")).await?
+    );
+    assert!(graph
+        .fetch_edges(&ctx, name_cs_id("nonexistent"))
+        .await?
+        .is_none());
+    let many_edges = graph
+        .fetch_many_edges(
+            &ctx,
+            &[name_cs_id("A"), name_cs_id("G"), name_cs_id("nonexistent")],
+            Prefetch::None,
+        )
+        .await?;
+    assert_eq!(many_edges.len(), 2);
+    assert_eq!(many_edges[&name_cs_id("A")].node.generation.value(), 1);
+    assert_eq!(
+        many_edges[&name_cs_id("G")]
+            .parents
+            .iter()
+            .map(|parent| parent.cs_id)
+            .collect::<Vec<_>>(),
+        vec![name_cs_id("D"), name_cs_id("F")]
+    );
 
     assert!(
         graph
@@ -215,6 +400,16 @@ pub async fn test_skip_tree(ctx: CoreContext, storage: Arc<dyn CommitGraphStorag
     assert_skip_tree_skew_ancestor(&storage, &ctx, "T", "S").await?;
     assert_skip_tree_skew_ancestor(&storage, &ctx, "S", "L").await?;
 
+    assert_commit_graph_skip_tree_skew_ancestor(&graph, &ctx, "A", None).await?;
+    assert_commit_graph_skip_tree_skew_ancestor(&graph, &ctx, "L", None).await?;
+    assert_commit_graph_skip_tree_skew_ancestor(&graph, &ctx, "H", Some("A")).await?;
+    assert_commit_graph_skip_tree_skew_ancestor(&graph, &ctx, "U", Some("T")).await?;
+    assert_commit_graph_skip_tree_skew_ancestor(&graph, &ctx, "S", Some("L")).await?;
+    assert!(graph
+        .skip_tree_skew_ancestor(&ctx, name_cs_id("nonexistent"))
+        .await
+        .is_err());
+
     assert_skip_tree_level_ancestor(&graph, &ctx, "S", 4, Some("P")).await?;
     assert_skip_tree_level_ancestor(&graph, &ctx, "U", 7, Some("S")).await?;
     assert_skip_tree_level_ancestor(&graph, &ctx, "T", 7, Some("S")).await?;
@@ -276,6 +471,119 @@ pub async fn test_p1_linear_tree(
     Ok(())
 }
 
+pub async fn test_is_ancestor_batch(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-D-F
+          \ /
+           C-E
+
+         G-H
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_is_ancestor_batch(
+        &graph,
+        &ctx,
+        "C",
+        vec!["D", "E", "B", "F", "C", "H", "A"],
+        vec![true, true, false, true, true, false, false],
+    )
+    .await?;
+    assert_is_ancestor_batch(
+        &graph,
+        &ctx,
+        "A",
+        vec!["F", "E", "H", "F"],
+        vec![true, true, false, true],
+    )
+    .await?;
+    assert_is_ancestor_batch(&graph, &ctx, "F", vec!["A", "D"], vec![false, false]).await?;
+    assert_is_ancestor_batch(&graph, &ctx, "G", vec![], vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_distance(ctx: CoreContext, storage: Arc<dyn CommitGraphStorage>) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-E-F
+          \       /
+           G------
+
+         H-I
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_distance(&graph, &ctx, "C", "C", Some(0)).await?;
+    assert_distance(&graph, &ctx, "B", "E", Some(3)).await?;
+    assert_distance(&graph, &ctx, "B", "F", Some(4)).await?;
+    assert_distance(&graph, &ctx, "G", "F", Some(1)).await?;
+    // The generation difference between A and F is 5, but the
+    // shortest path goes through G.
+    assert_distance(&graph, &ctx, "A", "F", Some(2)).await?;
+    assert_distance(&graph, &ctx, "F", "A", None).await?;
+    assert_distance(&graph, &ctx, "G", "E", None).await?;
+    assert_distance(&graph, &ctx, "H", "F", None).await?;
+
+    assert_is_ancestor_within(&graph, &ctx, "C", "C", 0, true).await?;
+    assert_is_ancestor_within(&graph, &ctx, "B", "E", 3, true).await?;
+    assert_is_ancestor_within(&graph, &ctx, "B", "E", 2, false).await?;
+    assert_is_ancestor_within(&graph, &ctx, "B", "E", 100, true).await?;
+    assert_is_ancestor_within(&graph, &ctx, "A", "F", 2, true).await?;
+    assert_is_ancestor_within(&graph, &ctx, "A", "F", 1, false).await?;
+    // B is an ancestor of F, but only beyond the given depth.
+    assert_is_ancestor_within(&graph, &ctx, "B", "F", 3, false).await?;
+    assert_is_ancestor_within(&graph, &ctx, "F", "A", 100, false).await?;
+    assert_is_ancestor_within(&graph, &ctx, "H", "F", 100, false).await?;
+
+    Ok(())
+}
+
+pub async fn test_path_to_ancestor(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H
+            \   /
+             E-F
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // Both parents of G reach B. The first parent is preferred.
+    assert_path_to_ancestor(&graph, &ctx, "H", "B", Some(vec!["H", "G", "D", "C", "B"])).await?;
+    assert_path_to_ancestor(
+        &graph,
+        &ctx,
+        "H",
+        "A",
+        Some(vec!["H", "G", "D", "C", "B", "A"]),
+    )
+    .await?;
+    // Only the second parent of G reaches E.
+    assert_path_to_ancestor(&graph, &ctx, "H", "E", Some(vec!["H", "G", "F", "E"])).await?;
+    assert_path_to_ancestor(&graph, &ctx, "B", "B", Some(vec!["B"])).await?;
+    assert_path_to_ancestor(&graph, &ctx, "D", "E", None).await?;
+    assert_path_to_ancestor(&graph, &ctx, "B", "H", None).await?;
+    assert_path_to_ancestor(&graph, &ctx, "D", "F", None).await?;
+
+    Ok(())
+}
+
 pub async fn test_ancestors_difference(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -400,6 +708,184 @@ pub async fn test_ancestors_difference(
     Ok(())
 }
 
+pub async fn test_ancestors_difference_missing_edges(
+    ctx: CoreContext,
+    _storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    // Construct a changeset X whose parent Y is missing from the storage.
+    let mock_storage = Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)));
+    let mock_graph = from_dag(&ctx, "A-B", mock_storage.clone()).await?;
+    mock_storage
+        .add(
+            &ctx,
+            ChangesetEdges {
+                node: name_cs_node("X", 2, 0, 0),
+                parents: vec![name_cs_node("Y", 1, 0, 0)].into(),
+                merge_ancestor: None,
+                skip_tree_parent: None,
+                skip_tree_skew_ancestor: None,
+                p1_linear_skew_ancestor: None,
+            },
+        )
+        .await?;
+
+    // Ancestors whose edges are missing are still returned.
+    assert_eq!(
+        mock_graph
+            .ancestors_difference(&ctx, vec![name_cs_id("X"), name_cs_id("B")], vec![])
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        ["X", "Y", "B", "A"]
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>(),
+    );
+
+    // The parents of Y can't be returned, so this fails instead.
+    assert!(
+        mock_graph
+            .ancestors_difference_with_parents_stream(&ctx, vec![name_cs_id("X")], vec![])
+            .await?
+            .try_collect::<Vec<_>>()
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+pub async fn test_count_ancestors_difference(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // B and A are reachable from G through both D and F,
+    // but should only be counted once.
+    assert_count_ancestors_difference(&graph, &ctx, vec!["G"], vec![], 7).await?;
+    assert_count_ancestors_difference(&graph, &ctx, vec!["K"], vec![], 11).await?;
+    assert_count_ancestors_difference(&graph, &ctx, vec!["K", "U"], vec![], 21).await?;
+    assert_count_ancestors_difference(&graph, &ctx, vec!["K"], vec!["G"], 4).await?;
+    assert_count_ancestors_difference(&graph, &ctx, vec!["K", "I"], vec!["J"], 1).await?;
+    assert_count_ancestors_difference(&graph, &ctx, vec!["G"], vec!["K"], 0).await?;
+
+    Ok(())
+}
+
+pub async fn test_generation_histogram(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_generation_histogram(
+        &graph,
+        &ctx,
+        vec!["G"],
+        1,
+        vec![(1, 1), (2, 1), (3, 2), (4, 2), (5, 1)],
+    )
+    .await?;
+    assert_generation_histogram(&graph, &ctx, vec!["K"], 4, vec![(0, 4), (4, 5), (8, 2)]).await?;
+    // B and A are reachable from K through both D and F, and the
+    // ancestors of K and U overlap in generations, but every changeset
+    // is counted exactly once.
+    assert_generation_histogram(&graph, &ctx, vec!["K", "U"], 10, vec![(0, 20), (10, 1)]).await?;
+    assert_generation_histogram(&graph, &ctx, vec!["K", "I"], 100, vec![(0, 11)]).await?;
+    assert!(graph
+        .generation_histogram(&ctx, vec![name_cs_id("K")], 0)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+pub async fn test_descendants_difference(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_descendants_difference(
+        &graph,
+        &ctx,
+        vec!["B"],
+        vec![],
+        vec!["B", "C", "D", "E", "F", "G", "H", "I", "J", "K"],
+    )
+    .await?;
+
+    assert_descendants_difference(&graph, &ctx, vec!["B"], vec!["F"], vec!["B", "C", "D", "E"])
+        .await?;
+
+    assert_descendants_difference(
+        &graph,
+        &ctx,
+        vec!["E", "R"],
+        vec!["G", "T"],
+        vec!["E", "F", "R", "S"],
+    )
+    .await?;
+
+    assert_descendants_difference(&graph, &ctx, vec!["D", "F"], vec!["C"], vec!["F"]).await?;
+    assert_descendants_difference(&graph, &ctx, vec!["G"], vec!["G"], vec![]).await?;
+    assert_descendants_difference(&graph, &ctx, vec!["H", "I"], vec!["I"], vec!["H"]).await?;
+
+    let set = ["H", "I", "J", "K"]
+        .into_iter()
+        .map(name_cs_id)
+        .collect::<HashSet<_>>();
+
+    assert_descendants_difference_with(
+        &graph,
+        &ctx,
+        vec!["B"],
+        vec![],
+        move |cs_id| {
+            cloned!(set);
+            async move { Ok(set.contains(&cs_id)) }
+        },
+        vec!["B", "C", "D", "E", "F", "G"],
+    )
+    .await?;
+
+    Ok(())
+}
+
 pub async fn test_find_by_prefix(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -462,6 +948,43 @@ pub async fn test_find_by_prefix(
         ChangesetIdsResolvedFromPrefix::Multiple(vec![name_cs_id("P"), name_cs_id("QQ")])
     );
 
+    assert_eq!(
+        graph
+            .find_by_prefixes(
+                &ctx,
+                vec![
+                    ChangesetIdPrefix::from_bytes("MA")?,
+                    ChangesetIdPrefix::from_bytes("Q")?,
+                    ChangesetIdPrefix::from_bytes("Z")?,
+                    ChangesetIdPrefix::from_bytes("MB")?,
+                    ChangesetIdPrefix::from_bytes("Q")?,
+                    ChangesetIdPrefix::from_str("5")?,
+                ],
+                3,
+            )
+            .await?,
+        vec![
+            ChangesetIdsResolvedFromPrefix::TooMany(vec![
+                name_cs_id("MA"),
+                name_cs_id("MAA"),
+                name_cs_id("MAB"),
+            ]),
+            ChangesetIdsResolvedFromPrefix::Single(name_cs_id("QQ")),
+            ChangesetIdsResolvedFromPrefix::NoMatch,
+            ChangesetIdsResolvedFromPrefix::Multiple(vec![
+                name_cs_id("MB"),
+                name_cs_id("MBB"),
+                name_cs_id("MBC"),
+            ]),
+            ChangesetIdsResolvedFromPrefix::Single(name_cs_id("QQ")),
+            ChangesetIdsResolvedFromPrefix::Multiple(vec![name_cs_id("P"), name_cs_id("QQ")]),
+        ]
+    );
+    assert_eq!(
+        graph.find_by_prefixes(&ctx, vec![], 10).await?,
+        Vec::<ChangesetIdsResolvedFromPrefix>::new()
+    );
+
     Ok(())
 }
 
@@ -621,6 +1144,91 @@ pub async fn test_add_recursive_many_changesets(
     Ok(())
 }
 
+pub async fn test_add_many(ctx: CoreContext, storage: Arc<dyn CommitGraphStorage>) -> Result<()> {
+    let graph = from_dag(&ctx, "A-B", storage.clone()).await?;
+
+    // Children are given before their parents.
+    assert_eq!(
+        graph
+            .add_many(
+                &ctx,
+                vec![
+                    (name_cs_id("F"), smallvec![name_cs_id("D"), name_cs_id("E")]),
+                    (name_cs_id("D"), smallvec![name_cs_id("C")]),
+                    (name_cs_id("E"), smallvec![name_cs_id("B")]),
+                    (name_cs_id("C"), smallvec![name_cs_id("B")]),
+                ],
+            )
+            .await?,
+        4
+    );
+    for name in ["C", "D", "E", "F"] {
+        graph.verify_edges(&ctx, name_cs_id(name)).await?;
+    }
+    assert_eq!(
+        graph
+            .changeset_generation_required(&ctx, name_cs_id("F"))
+            .await?
+            .value(),
+        5
+    );
+    assert_eq!(
+        graph
+            .changeset_parents_required(&ctx, name_cs_id("F"))
+            .await?
+            .as_slice(),
+        &[name_cs_id("D"), name_cs_id("E")]
+    );
+
+    // Changesets that already exist are not counted.
+    assert_eq!(
+        graph
+            .add_many(
+                &ctx,
+                vec![
+                    (name_cs_id("G"), smallvec![name_cs_id("F")]),
+                    (name_cs_id("C"), smallvec![name_cs_id("B")]),
+                ],
+            )
+            .await?,
+        1
+    );
+    assert_eq!(graph.add_many(&ctx, vec![]).await?, 0);
+
+    let missing_error = graph
+        .add_many(
+            &ctx,
+            vec![
+                (name_cs_id("H"), smallvec![name_cs_id("G")]),
+                (name_cs_id("I"), smallvec![name_cs_id("nonexistent")]),
+            ],
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(missing_error.contains(&name_cs_id("nonexistent").to_string()));
+    assert!(!graph.exists(&ctx, name_cs_id("H")).await?);
+
+    let cycle_error = graph
+        .add_many(
+            &ctx,
+            vec![
+                (name_cs_id("J"), smallvec![name_cs_id("G")]),
+                (name_cs_id("K"), smallvec![name_cs_id("J"), name_cs_id("L")]),
+                (name_cs_id("L"), smallvec![name_cs_id("K")]),
+            ],
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(cycle_error.contains(&name_cs_id("K").to_string()));
+    assert!(cycle_error.contains(&name_cs_id("L").to_string()));
+    assert!(!cycle_error.contains(&name_cs_id("J").to_string()));
+    assert!(!graph.exists(&ctx, name_cs_id("J")).await?);
+
+    Ok(())
+}
+
 pub async fn test_ancestors_frontier_with(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -723,6 +1331,405 @@ pub async fn test_ancestors_frontier_with(
     Ok(())
 }
 
+pub async fn test_ancestors_frontier_with_concurrency(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    let set = ["A", "B", "C", "E"]
+        .into_iter()
+        .map(name_cs_id)
+        .collect::<HashSet<_>>();
+
+    // Record the start and end of each property evaluation, yielding in
+    // between to simulate a slow property.
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let property = {
+        cloned!(set, calls);
+        move |cs_id| {
+            cloned!(set, calls);
+            async move {
+                calls.lock().unwrap().push(("start", cs_id));
+                let mut yielded = false;
+                future::poll_fn(|cx| {
+                    if yielded {
+                        Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                })
+                .await;
+                calls.lock().unwrap().push(("end", cs_id));
+                Ok(set.contains(&cs_id))
+            }
+        }
+    };
+
+    // D and F have the same generation, so their properties are evaluated
+    // concurrently.
+    let heads = vec![name_cs_id("D"), name_cs_id("F")];
+    assert_eq!(
+        graph
+            .ancestors_frontier_with_concurrency(&ctx, heads, property, 2)
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        ["C", "E"]
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>()
+    );
+    let calls = calls.lock().unwrap().clone();
+    assert_eq!(calls[0].0, "start");
+    assert_eq!(calls[1].0, "start");
+    assert_eq!(
+        [calls[0].1, calls[1].1].into_iter().collect::<HashSet<_>>(),
+        [name_cs_id("D"), name_cs_id("F")]
+            .into_iter()
+            .collect::<HashSet<_>>()
+    );
+
+    // The result is the same as for serial evaluation.
+    for heads in [vec!["K"], vec!["G"], vec!["D"], vec!["J", "I"]] {
+        let heads: Vec<_> = heads.into_iter().map(name_cs_id).collect();
+        let property = {
+            cloned!(set);
+            move |cs_id| {
+                cloned!(set);
+                async move { Ok(set.contains(&cs_id)) }
+            }
+        };
+        assert_eq!(
+            graph
+                .ancestors_frontier_with_concurrency(&ctx, heads.clone(), property.clone(), 3)
+                .await?
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            graph
+                .ancestors_frontier_with(&ctx, heads, property)
+                .await?
+                .into_iter()
+                .collect::<HashSet<_>>(),
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn test_first_ancestor_satisfying(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    let set1 = ["P", "Q", "R", "S", "T", "U"]
+        .into_iter()
+        .map(name_cs_id)
+        .collect::<HashSet<_>>();
+
+    assert_first_ancestor_satisfying(
+        &graph,
+        &ctx,
+        "U",
+        |cs_id| future::ready(Ok(set1.contains(&cs_id))),
+        Some("P"),
+    )
+    .await?;
+    assert_first_ancestor_satisfying(
+        &graph,
+        &ctx,
+        "R",
+        |cs_id| future::ready(Ok(set1.contains(&cs_id))),
+        Some("P"),
+    )
+    .await?;
+    assert_first_ancestor_satisfying(
+        &graph,
+        &ctx,
+        "O",
+        |cs_id| future::ready(Ok(set1.contains(&cs_id))),
+        None,
+    )
+    .await?;
+
+    // Descendants of E.
+    let set2 = ["E", "F", "G", "H", "I", "J", "K"]
+        .into_iter()
+        .map(name_cs_id)
+        .collect::<HashSet<_>>();
+
+    assert_first_ancestor_satisfying(
+        &graph,
+        &ctx,
+        "K",
+        |cs_id| future::ready(Ok(set2.contains(&cs_id))),
+        Some("E"),
+    )
+    .await?;
+    assert_first_ancestor_satisfying(
+        &graph,
+        &ctx,
+        "D",
+        |cs_id| future::ready(Ok(set2.contains(&cs_id))),
+        None,
+    )
+    .await?;
+
+    // Descendants of D.
+    let set3 = ["D", "G", "H", "I", "J", "K"]
+        .into_iter()
+        .map(name_cs_id)
+        .collect::<HashSet<_>>();
+
+    assert_first_ancestor_satisfying(
+        &graph,
+        &ctx,
+        "I",
+        |cs_id| future::ready(Ok(set3.contains(&cs_id))),
+        Some("D"),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn test_ancestors_in_generation_range(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_ancestors_in_generation_range(
+        &graph,
+        &ctx,
+        vec!["K"],
+        3..6,
+        vec!["C", "E", "D", "F", "G"],
+    )
+    .await?;
+    assert_ancestors_in_generation_range(
+        &graph,
+        &ctx,
+        vec!["K", "U"],
+        5..7,
+        vec!["G", "H", "P", "Q"],
+    )
+    .await?;
+    assert_ancestors_in_generation_range(&graph, &ctx, vec!["I"], 6..100, vec!["H", "I"]).await?;
+    assert_ancestors_in_generation_range(&graph, &ctx, vec!["U"], 1..2, vec!["L"]).await?;
+    assert_ancestors_in_generation_range(&graph, &ctx, vec!["K"], 5..5, vec![]).await?;
+    assert_ancestors_in_generation_range(&graph, &ctx, vec!["K"], 10..20, vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_ancestors_above_generation(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // G has generation 5, so it is excluded, and so are the heads C and
+    // G, which are at or below the watermark.
+    assert_ancestors_above_generation(
+        &graph,
+        &ctx,
+        vec!["K", "U", "C", "G"],
+        5,
+        vec!["H", "I", "J", "K", "Q", "R", "S", "T", "U"],
+    )
+    .await?;
+    assert_ancestors_above_generation(&graph, &ctx, vec!["C", "G"], 5, vec![]).await?;
+    assert_ancestors_above_generation(&graph, &ctx, vec!["G"], 4, vec!["G"]).await?;
+    assert_ancestors_above_generation(
+        &graph,
+        &ctx,
+        vec!["G"],
+        0,
+        vec!["A", "B", "C", "D", "E", "F", "G"],
+    )
+    .await?;
+    assert_ancestors_above_generation(&graph, &ctx, vec![], 0, vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_descendants_in_generation_range(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_descendants_in_generation_range(
+        &graph,
+        &ctx,
+        vec!["B"],
+        3..6,
+        vec!["C", "E", "D", "F", "G"],
+    )
+    .await?;
+    assert_descendants_in_generation_range(&graph, &ctx, vec!["C"], 4..8, vec!["D", "G", "H", "I"])
+        .await?;
+    assert_descendants_in_generation_range(
+        &graph,
+        &ctx,
+        vec!["E", "N"],
+        3..5,
+        vec!["E", "F", "N", "O"],
+    )
+    .await?;
+    assert_descendants_in_generation_range(&graph, &ctx, vec!["H"], 7..100, vec!["I", "J", "K"])
+        .await?;
+    assert_descendants_in_generation_range(&graph, &ctx, vec!["A"], 1..2, vec!["A"]).await?;
+    assert_descendants_in_generation_range(&graph, &ctx, vec!["A"], 5..5, vec![]).await?;
+    assert_descendants_in_generation_range(&graph, &ctx, vec!["K"], 1..5, vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_traversal_cancellation(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    fn is_cancelled(err: &anyhow::Error) -> bool {
+        matches!(
+            err.downcast_ref::<CommitGraphError>(),
+            Some(CommitGraphError::Cancelled)
+        )
+    }
+
+    // A token that is never cancelled does not affect the traversal.
+    let ancestors = graph
+        .ancestors_difference_stream_with_cancellation(
+            &ctx,
+            vec![name_cs_id("K")],
+            vec![name_cs_id("G")],
+            |_| future::ready(Ok(false)),
+            CancellationToken::new(),
+        )
+        .await?
+        .try_collect::<HashSet<_>>()
+        .await?;
+    assert_eq!(
+        ancestors,
+        ["H", "I", "J", "K"].into_iter().map(name_cs_id).collect()
+    );
+
+    // Cancelling stops the traversal at the next step, which fails
+    // with a cancellation error instead of fetching more changesets.
+    let cancellation = CancellationToken::new();
+    let mut stream = graph
+        .ancestors_difference_stream_with_cancellation(
+            &ctx,
+            vec![name_cs_id("U")],
+            vec![],
+            |_| future::ready(Ok(false)),
+            cancellation.clone(),
+        )
+        .await?;
+    assert_eq!(stream.try_next().await?, Some(name_cs_id("U")));
+    assert_eq!(stream.try_next().await?, Some(name_cs_id("T")));
+    cancellation.cancel();
+    assert!(is_cancelled(&stream.try_next().await.unwrap_err()));
+    assert_eq!(stream.try_next().await?, None);
+
+    let range = graph
+        .range_stream_with_cancellation(
+            &ctx,
+            name_cs_id("O"),
+            name_cs_id("T"),
+            CancellationToken::new(),
+        )
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        range,
+        ["O", "P", "Q", "R", "S", "T"]
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<Vec<_>>()
+    );
+
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let result = graph
+        .range_stream_with_cancellation(&ctx, name_cs_id("O"), name_cs_id("T"), cancellation)
+        .await;
+    assert!(is_cancelled(&result.err().unwrap()));
+
+    Ok(())
+}
+
 pub async fn test_range_stream(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
@@ -730,60 +1737,1304 @@ pub async fn test_range_stream(
     let graph = from_dag(
         &ctx,
         r##"
-         A-B-C-D-G-H---J-K
-            \   /   \ /
-             E-F     I
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_range_stream(
+        &graph,
+        &ctx,
+        "A",
+        "K",
+        vec!["A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K"],
+    )
+    .await?;
+    assert_range_stream(&graph, &ctx, "D", "K", vec!["D", "G", "H", "I", "J", "K"]).await?;
+    assert_range_stream(&graph, &ctx, "A", "U", vec![]).await?;
+    assert_range_stream(&graph, &ctx, "O", "T", vec!["O", "P", "Q", "R", "S", "T"]).await?;
+
+    assert_range_stream_limited(
+        &graph,
+        &ctx,
+        "O",
+        "T",
+        0,
+        vec!["O", "P", "Q", "R", "S", "T"],
+    )
+    .await?;
+    assert_range_stream_limited(
+        &graph,
+        &ctx,
+        "O",
+        "T",
+        3,
+        vec!["O", "P", "Q", "R", "S", "T"],
+    )
+    .await?;
+    assert_range_stream_limited(
+        &graph,
+        &ctx,
+        "O",
+        "T",
+        100,
+        vec!["O", "P", "Q", "R", "S", "T"],
+    )
+    .await?;
+    assert_range_stream_limited(&graph, &ctx, "A", "U", 5, vec![]).await?;
+    assert_range_stream_limited(
+        &graph,
+        &ctx,
+        "A",
+        "K",
+        7,
+        vec!["A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K"],
+    )
+    .await?;
+    assert_range_stream_limited(
+        &graph,
+        &ctx,
+        "D",
+        "K",
+        4,
+        vec!["D", "G", "H", "I", "J", "K"],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn test_topo_order_stream(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_topo_order_stream(
+        &graph,
+        &ctx,
+        vec!["K"],
+        vec![],
+        vec!["A", "B", "C", "E", "D", "F", "G", "H", "I", "J", "K"],
+    )
+    .await?;
+    assert_topo_order_stream(
+        &graph,
+        &ctx,
+        vec!["J", "S"],
+        vec!["C", "E", "O"],
+        vec!["D", "F", "G", "P", "H", "Q", "I", "R", "J", "S"],
+    )
+    .await?;
+    assert_topo_order_stream(&graph, &ctx, vec!["G"], vec!["G"], vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_sort_topological(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_sort_topological(
+        &graph,
+        &ctx,
+        vec!["R", "L", "U", "O", "M", "T", "Q", "N", "S", "P"],
+        vec!["L", "M", "N", "O", "P", "Q", "R", "S", "T", "U"],
+    )
+    .await?;
+    // C and E have the same generation, as do D and F, so they are
+    // ordered by changeset id. The order matches `topo_order_stream`.
+    assert_sort_topological(
+        &graph,
+        &ctx,
+        vec!["G", "E", "K", "D", "B", "F", "A", "I", "H", "C", "J"],
+        vec!["A", "B", "C", "E", "D", "F", "G", "H", "I", "J", "K"],
+    )
+    .await?;
+    // Only the relative order of the given changesets matters, they
+    // don't need to be connected.
+    assert_sort_topological(
+        &graph,
+        &ctx,
+        vec!["K", "P", "F", "U", "A"],
+        vec!["A", "F", "P", "K", "U"],
+    )
+    .await?;
+    assert_sort_topological(&graph, &ctx, vec![], vec![]).await?;
+    assert!(graph
+        .sort_topological(&ctx, vec![name_cs_id("A"), name_cs_id("nonexistent")])
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+pub async fn test_common_base(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+        A-B-C-D-E-L------N
+           \       \    /
+            F-G-H   M  /
+             \     /  /
+              I-J-K--/
+
+        O-P-Q-R-S-T-U-V-W
+        "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_common_base(&graph, &ctx, "J", "J", vec!["J"]).await?;
+    assert_common_base(&graph, &ctx, "K", "J", vec!["J"]).await?;
+    assert_common_base(&graph, &ctx, "E", "H", vec!["B"]).await?;
+    assert_common_base(&graph, &ctx, "G", "J", vec!["F"]).await?;
+    assert_common_base(&graph, &ctx, "M", "N", vec!["K", "L"]).await?;
+    assert_common_base(&graph, &ctx, "L", "K", vec!["B"]).await?;
+    assert_common_base(&graph, &ctx, "M", "H", vec!["F"]).await?;
+    assert_common_base(&graph, &ctx, "A", "B", vec!["A"]).await?;
+    assert_common_base(&graph, &ctx, "N", "W", vec![]).await?;
+    assert_common_base(&graph, &ctx, "D", "Q", vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_common_base_multi(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+        A-B-C-D-E-L------N
+           \       \    /
+            F-G-H   M  /
+             \     /  /
+              I-J-K--/
+
+        O-P-Q-R-S-T-U-V-W
+        "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_common_base_multi(&graph, &ctx, vec![], vec![]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["J"], vec!["J"]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["J", "J"], vec!["J"]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["K", "J"], vec!["J"]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["M", "N"], vec!["K", "L"]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["M", "N", "H"], vec!["F"]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["G", "J", "K"], vec!["F"]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["E", "H", "J"], vec!["B"]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["E", "H", "J", "N"], vec!["B"]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["N", "W", "A"], vec![]).await?;
+    assert_common_base_multi(&graph, &ctx, vec!["U", "W", "S"], vec!["S"]).await?;
+
+    Ok(())
+}
+
+pub async fn test_lowest_common_ancestor(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+        A-B-C-D-E-L------N
+           \       \    /
+            F-G-H   M  /
+             \     /  /
+              I-J-K--/
+
+        O-P-Q-R-S-T-U-V-W
+        "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_lowest_common_ancestor(&graph, &ctx, "J", "J", Some("J")).await?;
+    assert_lowest_common_ancestor(&graph, &ctx, "E", "H", Some("B")).await?;
+    assert_lowest_common_ancestor(&graph, &ctx, "G", "J", Some("F")).await?;
+    // M and N are a criss-cross merge with common bases K and L.
+    assert_lowest_common_ancestor(&graph, &ctx, "M", "N", Some("K")).await?;
+    assert_lowest_common_ancestor(&graph, &ctx, "N", "M", Some("K")).await?;
+    assert_lowest_common_ancestor(&graph, &ctx, "N", "W", None).await?;
+
+    Ok(())
+}
+
+pub async fn test_ancestors_difference_by_head(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+        A-B-C-D
+           \
+            E-F
+
+        Z-G
+        "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // The shared tail B-A is attributed to D, the smallest head.
+    assert_ancestors_difference_by_head(
+        &graph,
+        &ctx,
+        vec!["F", "D"],
+        vec![],
+        vec![("D", vec!["D", "C", "B", "A"]), ("F", vec!["F", "E"])],
+    )
+    .await?;
+    assert_ancestors_difference_by_head(
+        &graph,
+        &ctx,
+        vec!["D", "F"],
+        vec!["A"],
+        vec![("D", vec!["D", "C", "B"]), ("F", vec!["F", "E"])],
+    )
+    .await?;
+    // A head that is an ancestor of a larger head gets its own ancestors.
+    assert_ancestors_difference_by_head(
+        &graph,
+        &ctx,
+        vec!["F", "B"],
+        vec![],
+        vec![("B", vec!["B", "A"]), ("F", vec!["F", "E"])],
+    )
+    .await?;
+    // A head that is an ancestor of a smaller head gets nothing.
+    assert_ancestors_difference_by_head(
+        &graph,
+        &ctx,
+        vec!["Z", "G"],
+        vec![],
+        vec![("G", vec!["G", "Z"]), ("Z", vec![])],
+    )
+    .await?;
+    assert_ancestors_difference_by_head(
+        &graph,
+        &ctx,
+        vec!["D", "F"],
+        vec!["D"],
+        vec![("D", vec![]), ("F", vec!["F", "E"])],
+    )
+    .await?;
+    assert_ancestors_difference_by_head(&graph, &ctx, vec![], vec!["D"], vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_ancestors_symmetric_difference(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+        A-B-C-D-E-L------N
+           \       \    /
+            F-G-H   M  /
+             \     /  /
+              I-J-K--/
+
+        O-P-Q-R-S-T-U-V-W
+        "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // M and N are a criss-cross merge with common bases K and L, so
+    // the bases and all of their ancestors are in neither output.
+    assert_ancestors_symmetric_difference(&graph, &ctx, vec!["M"], vec!["N"], vec!["M"], vec!["N"])
+        .await?;
+    assert_ancestors_symmetric_difference(
+        &graph,
+        &ctx,
+        vec!["H"],
+        vec!["N"],
+        vec!["H", "G"],
+        vec!["N", "L", "E", "D", "C", "K", "J", "I"],
+    )
+    .await?;
+    assert_ancestors_symmetric_difference(
+        &graph,
+        &ctx,
+        vec!["K"],
+        vec!["M", "N"],
+        vec![],
+        vec!["M", "N", "L", "E", "D", "C"],
+    )
+    .await?;
+    assert_ancestors_symmetric_difference(
+        &graph,
+        &ctx,
+        vec!["H", "K"],
+        vec!["S"],
+        vec!["H", "G", "K", "J", "I", "F", "B", "A"],
+        vec!["S", "R", "Q", "P", "O"],
+    )
+    .await?;
+    assert_ancestors_symmetric_difference(&graph, &ctx, vec!["N"], vec!["N"], vec![], vec![])
+        .await?;
+    assert_ancestors_symmetric_difference(&graph, &ctx, vec![], vec!["B"], vec![], vec!["B", "A"])
+        .await?;
+
+    Ok(())
+}
+
+pub async fn test_heads_of_set(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_heads_of_set(&graph, &ctx, vec![], vec![]).await?;
+    assert_heads_of_set(&graph, &ctx, vec!["K", "K"], vec!["K"]).await?;
+    assert_heads_of_set(&graph, &ctx, vec!["G", "I", "K"], vec!["K"]).await?;
+    assert_heads_of_set(&graph, &ctx, vec!["D", "E"], vec!["D", "E"]).await?;
+    assert_heads_of_set(&graph, &ctx, vec!["A", "H", "I"], vec!["I"]).await?;
+    assert_heads_of_set(
+        &graph,
+        &ctx,
+        vec!["C", "E", "D", "F", "U", "P"],
+        vec!["D", "F", "U"],
+    )
+    .await?;
+    assert_heads_of_set(&graph, &ctx, vec!["B", "G", "L", "J"], vec!["J", "L"]).await?;
+
+    Ok(())
+}
+
+pub async fn test_roots_of_set(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_roots_of_set(&graph, &ctx, vec![], vec![]).await?;
+    assert_roots_of_set(&graph, &ctx, vec!["K", "K"], vec!["K"]).await?;
+    assert_roots_of_set(&graph, &ctx, vec!["G", "I", "K"], vec!["G"]).await?;
+    assert_roots_of_set(&graph, &ctx, vec!["A", "H", "I"], vec!["A"]).await?;
+    assert_roots_of_set(
+        &graph,
+        &ctx,
+        vec!["C", "E", "D", "F", "U", "P"],
+        vec!["C", "E", "P"],
+    )
+    .await?;
+    assert_roots_of_set(&graph, &ctx, vec!["B", "G", "L", "J"], vec!["B", "L"]).await?;
+
+    // Antichains are returned unchanged.
+    assert_roots_of_set(&graph, &ctx, vec!["D", "E"], vec!["D", "E"]).await?;
+    assert_roots_of_set(&graph, &ctx, vec!["H", "S"], vec!["H", "S"]).await?;
+    assert_roots_of_set(
+        &graph,
+        &ctx,
+        vec!["D", "F", "I", "N"],
+        vec!["D", "F", "I", "N"],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn test_extract_subgraph(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_extract_subgraph(&graph, &ctx, vec!["A"], vec!["A"], vec![]).await?;
+    assert_extract_subgraph(
+        &graph,
+        &ctx,
+        vec!["C"],
+        vec![],
+        vec![("A", vec![]), ("B", vec!["A"]), ("C", vec!["B"])],
+    )
+    .await?;
+    assert_extract_subgraph(
+        &graph,
+        &ctx,
+        vec!["G"],
+        vec!["D"],
+        vec![("E", vec![]), ("F", vec!["E"]), ("G", vec!["F"])],
+    )
+    .await?;
+    // G's parent F and C's parent B are outside of the subgraph.
+    assert_extract_subgraph(
+        &graph,
+        &ctx,
+        vec!["K"],
+        vec!["F"],
+        vec![
+            ("C", vec![]),
+            ("D", vec!["C"]),
+            ("G", vec!["D"]),
+            ("H", vec!["G"]),
+            ("I", vec!["H"]),
+            ("J", vec!["H", "I"]),
+            ("K", vec!["J"]),
+        ],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn test_highest_generation_head(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_highest_generation_head(&graph, &ctx, vec![], None).await?;
+    assert_highest_generation_head(&graph, &ctx, vec!["A"], Some("A")).await?;
+    assert_highest_generation_head(&graph, &ctx, vec!["C", "K", "E"], Some("K")).await?;
+    assert_highest_generation_head(&graph, &ctx, vec!["K", "U"], Some("U")).await?;
+
+    // D and F have the same generation number, so the larger id wins.
+    let tie_winner = if name_cs_id("D") > name_cs_id("F") {
+        "D"
+    } else {
+        "F"
+    };
+    assert_highest_generation_head(&graph, &ctx, vec!["D", "F"], Some(tie_winner)).await?;
+    assert_highest_generation_head(&graph, &ctx, vec!["F", "D", "B"], Some(tie_winner)).await?;
+
+    assert!(graph
+        .highest_generation_head(&ctx, vec![name_cs_id("A"), name_cs_id("nonexistent")])
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+pub async fn test_merge_commits_in_difference(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H-I
+            \   /
+             E-F
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // Add an octopus merge M and a merge N of it with H.
+    graph
+        .add(
+            &ctx,
+            name_cs_id("M"),
+            smallvec![name_cs_id("C"), name_cs_id("E"), name_cs_id("B")],
+        )
+        .await?;
+    graph
+        .add(
+            &ctx,
+            name_cs_id("N"),
+            smallvec![name_cs_id("H"), name_cs_id("M")],
+        )
+        .await?;
+
+    assert_merge_commits_in_difference(&graph, &ctx, vec!["N"], vec![], vec!["N", "G", "M"])
+        .await?;
+    assert_merge_commits_in_difference(&graph, &ctx, vec!["N"], vec!["G"], vec!["N", "M"]).await?;
+    assert_merge_commits_in_difference(&graph, &ctx, vec!["M"], vec!["C"], vec!["M"]).await?;
+    assert_merge_commits_in_difference(&graph, &ctx, vec!["I", "M"], vec!["G"], vec!["M"]).await?;
+    assert_merge_commits_in_difference(&graph, &ctx, vec!["I"], vec!["G"], vec![]).await?;
+    assert_merge_commits_in_difference(&graph, &ctx, vec!["F"], vec![], vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_merge_parents(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C
+          \
+           D-E
+            \
+             F
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // Add octopus merges with an explicit parent order.
+    graph
+        .add(
+            &ctx,
+            name_cs_id("M"),
+            smallvec![name_cs_id("C"), name_cs_id("F"), name_cs_id("E")],
+        )
+        .await?;
+    graph
+        .add(
+            &ctx,
+            name_cs_id("N"),
+            smallvec![
+                name_cs_id("F"),
+                name_cs_id("E"),
+                name_cs_id("C"),
+                name_cs_id("B")
+            ],
+        )
+        .await?;
+    graph
+        .add(
+            &ctx,
+            name_cs_id("Y"),
+            smallvec![name_cs_id("A"), name_cs_id("M")],
+        )
+        .await?;
+
+    assert_merge_parents(&graph, &ctx, "A", vec![]).await?;
+    assert_merge_parents(&graph, &ctx, "E", vec![]).await?;
+    assert_merge_parents(&graph, &ctx, "M", vec!["F", "E"]).await?;
+    assert_merge_parents(&graph, &ctx, "N", vec!["E", "C", "B"]).await?;
+    assert_merge_parents(&graph, &ctx, "Y", vec!["M"]).await?;
+    assert!(graph
+        .merge_parents(&ctx, name_cs_id("nonexistent"))
+        .await
+        .is_err());
+
+    assert_second_parent_ancestors(&graph, &ctx, vec!["C"], vec!["C"]).await?;
+    assert_second_parent_ancestors(&graph, &ctx, vec!["M"], vec!["M", "F", "E"]).await?;
+    assert_second_parent_ancestors(&graph, &ctx, vec!["Y"], vec!["Y", "M", "F", "E"]).await?;
+    assert_second_parent_ancestors(
+        &graph,
+        &ctx,
+        vec!["Y", "N"],
+        vec!["Y", "N", "M", "F", "E", "C", "B"],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn test_ancestors_segments(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H
+            \   /
+             E-F
+
+         L-M-N-O-P
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // Linear history compresses to a single segment.
+    assert_ancestors_segments(&graph, &ctx, vec!["P"], vec![], vec![("L", "P", 5)]).await?;
+    assert_ancestors_segments(&graph, &ctx, vec!["P"], vec!["M"], vec![("N", "P", 3)]).await?;
+    assert_ancestors_segments(&graph, &ctx, vec!["P"], vec!["P"], vec![]).await?;
+
+    // Branch points and merges start new segments.
+    assert_ancestors_segments(
+        &graph,
+        &ctx,
+        vec!["H"],
+        vec![],
+        vec![("A", "B", 2), ("C", "D", 2), ("E", "F", 2), ("G", "H", 2)],
+    )
+    .await?;
+    assert_ancestors_segments(
+        &graph,
+        &ctx,
+        vec!["H"],
+        vec!["C"],
+        vec![("D", "D", 1), ("E", "F", 2), ("G", "H", 2)],
+    )
+    .await?;
+    // Without G in the difference, B is no longer a branch point.
+    assert_ancestors_segments(&graph, &ctx, vec!["D"], vec![], vec![("A", "D", 4)]).await?;
+    assert_ancestors_segments(
+        &graph,
+        &ctx,
+        vec!["D", "F", "O"],
+        vec![],
+        vec![("A", "B", 2), ("C", "D", 2), ("E", "F", 2), ("L", "O", 4)],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn test_are_independent(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_are_independent(&graph, &ctx, "A", "A", false).await?;
+    assert_are_independent(&graph, &ctx, "K", "K", false).await?;
+    assert_are_independent(&graph, &ctx, "A", "K", false).await?;
+    assert_are_independent(&graph, &ctx, "E", "J", false).await?;
+    assert_are_independent(&graph, &ctx, "I", "J", false).await?;
+    assert_are_independent(&graph, &ctx, "C", "E", true).await?;
+    assert_are_independent(&graph, &ctx, "D", "F", true).await?;
+    assert_are_independent(&graph, &ctx, "D", "E", true).await?;
+    assert_are_independent(&graph, &ctx, "H", "M", true).await?;
+    assert_are_independent(&graph, &ctx, "K", "U", true).await?;
+
+    Ok(())
+}
+
+pub async fn test_ancestors_difference_capped(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_ancestors_difference_capped(
+        &graph,
+        &ctx,
+        vec!["K"],
+        vec!["G"],
+        10,
+        Some(vec!["K", "J", "I", "H"]),
+    )
+    .await?;
+    assert_ancestors_difference_capped(
+        &graph,
+        &ctx,
+        vec!["K"],
+        vec!["G"],
+        4,
+        Some(vec!["K", "J", "I", "H"]),
+    )
+    .await?;
+    assert_ancestors_difference_capped(&graph, &ctx, vec!["K"], vec!["G"], 3, None).await?;
+    assert_ancestors_difference_capped(&graph, &ctx, vec!["K"], vec![], 10, None).await?;
+    assert_ancestors_difference_capped(&graph, &ctx, vec!["G"], vec!["K"], 0, Some(vec![])).await?;
+    assert_ancestors_difference_capped(&graph, &ctx, vec!["A"], vec![], 0, None).await?;
+
+    Ok(())
+}
+
+pub async fn test_frontier_at_generation(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_frontier_at_generation(&graph, &ctx, vec![], 5, vec![]).await?;
+    assert_frontier_at_generation(&graph, &ctx, vec!["K"], 9, vec!["K"]).await?;
+    assert_frontier_at_generation(&graph, &ctx, vec!["K"], 100, vec!["K"]).await?;
+    assert_frontier_at_generation(&graph, &ctx, vec!["K"], 5, vec!["G"]).await?;
+    assert_frontier_at_generation(&graph, &ctx, vec!["G"], 4, vec!["D", "F"]).await?;
+    assert_frontier_at_generation(&graph, &ctx, vec!["G"], 3, vec!["C", "E"]).await?;
+    assert_frontier_at_generation(&graph, &ctx, vec!["K"], 1, vec!["A"]).await?;
+    // The edge from J to H skips over generation 7.
+    assert_frontier_at_generation(&graph, &ctx, vec!["K"], 7, vec!["H", "I"]).await?;
+    assert_frontier_at_generation(&graph, &ctx, vec!["C", "K", "U"], 5, vec!["C", "G", "P"])
+        .await?;
+
+    Ok(())
+}
+
+pub async fn test_frontier_operations(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H
+            \   /
+             E-F
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    let changesets =
+        |frontier: &Frontier| frontier.changesets().into_iter().collect::<HashSet<_>>();
+    let ids = |names: Vec<&str>| names.into_iter().map(name_cs_id).collect::<HashSet<_>>();
+
+    let df = graph
+        .frontier(&ctx, vec![name_cs_id("D"), name_cs_id("F")])
+        .await?;
+    let fh = graph
+        .frontier(&ctx, vec![name_cs_id("F"), name_cs_id("H")])
+        .await?;
+    assert_eq!(changesets(&df.union(&fh)), ids(vec!["D", "F", "H"]));
+    assert_eq!(changesets(&df.intersection(&fh)), ids(vec!["F"]));
+    assert_eq!(fh.highest_generation().map(|gen| gen.value()), Some(6));
+    assert!(df
+        .intersection(&graph.frontier(&ctx, vec![]).await?)
+        .is_empty());
+
+    let mut h = graph.frontier(&ctx, vec![name_cs_id("H")]).await?;
+    h.lower_to(&ctx, Generation::new(4)).await?;
+    assert_eq!(changesets(&h), ids(vec!["D", "F"]));
+    assert_eq!(
+        h.changesets_with_generations()
+            .into_iter()
+            .map(|(_, generation)| generation.value())
+            .collect::<Vec<_>>(),
+        vec![4, 4]
+    );
+
+    let e = graph.frontier(&ctx, vec![name_cs_id("E")]).await?;
+    let mut dfe = h.union(&e);
+    assert_eq!(changesets(&dfe), ids(vec!["D", "E", "F"]));
+    dfe.lower_to(&ctx, Generation::new(2)).await?;
+    assert_eq!(changesets(&dfe), ids(vec!["B"]));
+
+    Ok(())
+}
+
+pub async fn test_ancestors_difference_multi_common(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_ancestors_difference_multi_common(
+        &graph,
+        &ctx,
+        vec!["K"],
+        vec![],
+        vec!["K", "J", "I", "H", "G", "D", "F", "C", "E", "B", "A"],
+    )
+    .await?;
+    assert_ancestors_difference_multi_common(
+        &graph,
+        &ctx,
+        vec!["K"],
+        vec![vec![], vec!["G"]],
+        vec!["K", "J", "I", "H"],
+    )
+    .await?;
+    assert_ancestors_difference_multi_common(
+        &graph,
+        &ctx,
+        vec!["K"],
+        vec![vec!["C"], vec!["F"]],
+        vec!["K", "J", "I", "H", "G", "D"],
+    )
+    .await?;
+    assert_ancestors_difference_multi_common(
+        &graph,
+        &ctx,
+        vec!["K", "U"],
+        vec![vec!["I"], vec!["M"], vec!["E", "S"]],
+        vec!["K", "J", "U", "T"],
+    )
+    .await?;
+    assert_ancestors_difference_multi_common(
+        &graph,
+        &ctx,
+        vec!["K", "U"],
+        vec![vec!["B"], vec!["L", "U"]],
+        vec!["K", "J", "I", "H", "G", "D", "F", "C", "E"],
+    )
+    .await?;
+    assert_ancestors_difference_multi_common(
+        &graph,
+        &ctx,
+        vec!["G"],
+        vec![vec!["A"], vec!["K"]],
+        vec![],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn test_ancestors_with_depth(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C
+          \
+           D
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // E is a merge of C and D, so A is reachable from E at depth 2 through
+    // D and at depth 3 through C.
+    graph
+        .add(
+            &ctx,
+            name_cs_id("E"),
+            smallvec![name_cs_id("C"), name_cs_id("D")],
+        )
+        .await?;
+
+    assert_ancestors_with_depth(&graph, &ctx, vec!["E"], 0, vec![("E", 0)]).await?;
+    assert_ancestors_with_depth(
+        &graph,
+        &ctx,
+        vec!["E"],
+        1,
+        vec![("E", 0), ("C", 1), ("D", 1)],
+    )
+    .await?;
+    assert_ancestors_with_depth(
+        &graph,
+        &ctx,
+        vec!["E"],
+        2,
+        vec![("E", 0), ("C", 1), ("D", 1), ("B", 2), ("A", 2)],
+    )
+    .await?;
+    assert_ancestors_with_depth(
+        &graph,
+        &ctx,
+        vec!["E"],
+        100,
+        vec![("E", 0), ("C", 1), ("D", 1), ("B", 2), ("A", 2)],
+    )
+    .await?;
+    assert_ancestors_with_depth(
+        &graph,
+        &ctx,
+        vec!["C", "D"],
+        100,
+        vec![("C", 0), ("D", 0), ("B", 1), ("A", 1)],
+    )
+    .await?;
+    assert_ancestors_with_depth(
+        &graph,
+        &ctx,
+        vec!["E", "B"],
+        1,
+        vec![("E", 0), ("B", 0), ("C", 1), ("D", 1), ("A", 1)],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn test_first_parent_history(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C
+          \
+           D-E
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    // Add merges with an explicit parent order, so that the first-parent
+    // history diverges from the full ancestry.
+    graph
+        .add(
+            &ctx,
+            name_cs_id("F"),
+            smallvec![name_cs_id("C"), name_cs_id("E")],
+        )
+        .await?;
+    graph
+        .add(
+            &ctx,
+            name_cs_id("G"),
+            smallvec![name_cs_id("E"), name_cs_id("F")],
+        )
+        .await?;
+    graph
+        .add(&ctx, name_cs_id("H"), smallvec![name_cs_id("F")])
+        .await?;
+
+    assert_first_parent_history(&graph, &ctx, "A", vec!["A"]).await?;
+    assert_first_parent_history(&graph, &ctx, "E", vec!["E", "D", "A"]).await?;
+    assert_first_parent_history(&graph, &ctx, "F", vec!["F", "C", "B", "A"]).await?;
+    assert_first_parent_history(&graph, &ctx, "G", vec!["G", "E", "D", "A"]).await?;
+    assert_first_parent_history(&graph, &ctx, "H", vec!["H", "F", "C", "B", "A"]).await?;
+    assert!(graph
+        .first_parent_history_stream(&ctx, name_cs_id("nonexistent"))
+        .await?
+        .try_collect::<Vec<_>>()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+pub async fn test_first_parent_depth(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C
+          \
+           D-E
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    graph
+        .add(
+            &ctx,
+            name_cs_id("F"),
+            smallvec![name_cs_id("C"), name_cs_id("E")],
+        )
+        .await?;
+    graph
+        .add(
+            &ctx,
+            name_cs_id("G"),
+            smallvec![name_cs_id("E"), name_cs_id("F")],
+        )
+        .await?;
+
+    assert_first_parent_depth(&graph, &ctx, "A", 0).await?;
+    assert_first_parent_depth(&graph, &ctx, "C", 2).await?;
+    assert_first_parent_depth(&graph, &ctx, "E", 2).await?;
+    assert_first_parent_depth(&graph, &ctx, "F", 3).await?;
+    // G has generation 5 through its merge parent F, but its first
+    // parent history is only G-E-D-A.
+    assert_first_parent_depth(&graph, &ctx, "G", 3).await?;
+    assert_eq!(
+        graph
+            .changeset_generation_required(&ctx, name_cs_id("G"))
+            .await?,
+        Generation::new(5)
+    );
+    assert!(graph
+        .first_parent_depth(&ctx, name_cs_id("nonexistent"))
+        .await
+        .is_err());
+
+    Ok(())
+}
 
+pub async fn test_location_to_changesets(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
          L-M-N-O-P-Q-R-S-T-U
          "##,
         storage.clone(),
     )
     .await?;
 
-    assert_range_stream(
-        &graph,
+    assert_location_to_changesets(&graph, &ctx, "U", 0, 1, vec!["U"]).await?;
+    assert_location_to_changesets(&graph, &ctx, "U", 0, 3, vec!["U", "T", "S"]).await?;
+    assert_location_to_changesets(&graph, &ctx, "U", 2, 3, vec!["S", "R", "Q"]).await?;
+    assert_location_to_changesets(&graph, &ctx, "R", 1, 2, vec!["Q", "P"]).await?;
+    assert_location_to_changesets(&graph, &ctx, "U", 3, 0, vec![]).await?;
+    // Running off the root returns fewer changesets.
+    assert_location_to_changesets(&graph, &ctx, "U", 7, 5, vec!["N", "M", "L"]).await?;
+    assert_location_to_changesets(&graph, &ctx, "U", 9, 2, vec!["L"]).await?;
+    // There is nothing 10 steps back from U.
+    assert!(graph
+        .location_to_changesets(&ctx, name_cs_id("U"), 10, 1)
+        .await
+        .is_err());
+    assert!(graph
+        .location_to_changesets(&ctx, name_cs_id("nonexistent"), 0, 1)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+pub async fn test_changeset_to_location(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
         &ctx,
-        "A",
-        "K",
-        vec!["A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K"],
+        r##"
+         A-B-C
+          \
+           D-E
+         "##,
+        storage.clone(),
     )
     .await?;
-    assert_range_stream(&graph, &ctx, "D", "K", vec!["D", "G", "H", "I", "J", "K"]).await?;
-    assert_range_stream(&graph, &ctx, "A", "U", vec![]).await?;
-    assert_range_stream(&graph, &ctx, "O", "T", vec!["O", "P", "Q", "R", "S", "T"]).await?;
+
+    // Add merges with an explicit parent order. The first-parent history
+    // of G is G-E-D-A, and the one of H is H-F-C-B-A.
+    graph
+        .add(
+            &ctx,
+            name_cs_id("F"),
+            smallvec![name_cs_id("C"), name_cs_id("E")],
+        )
+        .await?;
+    graph
+        .add(
+            &ctx,
+            name_cs_id("G"),
+            smallvec![name_cs_id("E"), name_cs_id("F")],
+        )
+        .await?;
+    graph
+        .add(&ctx, name_cs_id("H"), smallvec![name_cs_id("F")])
+        .await?;
+
+    assert_changeset_to_location(&graph, &ctx, vec!["G", "H"], "G", Some(("G", 0))).await?;
+    assert_changeset_to_location(&graph, &ctx, vec!["G", "H"], "E", Some(("G", 1))).await?;
+    assert_changeset_to_location(&graph, &ctx, vec!["G", "H"], "F", Some(("H", 1))).await?;
+    assert_changeset_to_location(&graph, &ctx, vec!["G", "H"], "B", Some(("H", 3))).await?;
+    // A is on the first-parent history of both heads, G is closer.
+    assert_changeset_to_location(&graph, &ctx, vec!["H", "G"], "A", Some(("G", 3))).await?;
+    // E is an ancestor of H, but only through a merge parent.
+    assert_changeset_to_location(&graph, &ctx, vec!["H"], "E", None).await?;
+    assert_changeset_to_location(&graph, &ctx, vec!["C"], "H", None).await?;
+    assert_changeset_to_location(&graph, &ctx, vec![], "A", None).await?;
 
     Ok(())
 }
 
-pub async fn test_common_base(
+pub async fn test_is_fast_forward(
     ctx: CoreContext,
     storage: Arc<dyn CommitGraphStorage>,
 ) -> Result<()> {
     let graph = from_dag(
         &ctx,
         r##"
-        A-B-C-D-E-L------N
-           \       \    /
-            F-G-H   M  /
-             \     /  /
-              I-J-K--/
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
 
-        O-P-Q-R-S-T-U-V-W
-        "##,
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
         storage.clone(),
     )
     .await?;
 
-    assert_common_base(&graph, &ctx, "J", "J", vec!["J"]).await?;
-    assert_common_base(&graph, &ctx, "K", "J", vec!["J"]).await?;
-    assert_common_base(&graph, &ctx, "E", "H", vec!["B"]).await?;
-    assert_common_base(&graph, &ctx, "G", "J", vec!["F"]).await?;
-    assert_common_base(&graph, &ctx, "M", "N", vec!["K", "L"]).await?;
-    assert_common_base(&graph, &ctx, "L", "K", vec!["B"]).await?;
-    assert_common_base(&graph, &ctx, "M", "H", vec!["F"]).await?;
-    assert_common_base(&graph, &ctx, "A", "B", vec!["A"]).await?;
-    assert_common_base(&graph, &ctx, "N", "W", vec![]).await?;
-    assert_common_base(&graph, &ctx, "D", "Q", vec![]).await?;
+    assert_is_fast_forward(&graph, &ctx, "A", "A", true).await?;
+    assert_is_fast_forward(&graph, &ctx, "L", "U", true).await?;
+    assert_is_fast_forward(&graph, &ctx, "C", "D", true).await?;
+    // Everything between B and G descends from B, including the
+    // merged E-F branch.
+    assert_is_fast_forward(&graph, &ctx, "B", "G", true).await?;
+    assert_is_fast_forward(&graph, &ctx, "G", "K", true).await?;
+    // C is an ancestor of G, but G merges in E-F, which forked off at B
+    // and so has diverged from C.
+    assert_is_fast_forward(&graph, &ctx, "C", "G", false).await?;
+    assert_is_fast_forward(&graph, &ctx, "D", "K", false).await?;
+    // Not ancestors at all.
+    assert_is_fast_forward(&graph, &ctx, "G", "C", false).await?;
+    assert_is_fast_forward(&graph, &ctx, "E", "D", false).await?;
+    assert_is_fast_forward(&graph, &ctx, "A", "U", false).await?;
+
+    Ok(())
+}
+
+pub async fn test_nearest_ancestor_in_set(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-D-G-H---J-K
+            \   /   \ /
+             E-F     I
+
+         L-M-N-O-P-Q-R-S-T-U
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert_nearest_ancestor_in_set(&graph, &ctx, "K", vec![], None).await?;
+    assert_nearest_ancestor_in_set(&graph, &ctx, "K", vec!["K", "A"], Some("K")).await?;
+    assert_nearest_ancestor_in_set(&graph, &ctx, "K", vec!["A", "H", "C"], Some("H")).await?;
+    assert_nearest_ancestor_in_set(&graph, &ctx, "H", vec!["I", "J", "E"], Some("E")).await?;
+    assert_nearest_ancestor_in_set(&graph, &ctx, "F", vec!["C", "D", "G"], None).await?;
+    assert_nearest_ancestor_in_set(&graph, &ctx, "U", vec!["K", "Q", "M"], Some("Q")).await?;
+    assert_nearest_ancestor_in_set(&graph, &ctx, "G", vec!["nonexistent", "B"], Some("B")).await?;
+
+    // D and F have the same generation, so the smallest id wins.
+    let tie_winner = if name_cs_id("D") < name_cs_id("F") {
+        "D"
+    } else {
+        "F"
+    };
+    assert_nearest_ancestor_in_set(&graph, &ctx, "K", vec!["D", "F", "C"], Some(tie_winner))
+        .await?;
+
+    Ok(())
+}
+
+pub async fn test_roots(ctx: CoreContext, storage: Arc<dyn CommitGraphStorage>) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+         A-B-C-G-H-I
+              /
+         D-E-F
+
+         X-Y
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert!(graph.is_root(&ctx, name_cs_id("A")).await?);
+    assert!(graph.is_root(&ctx, name_cs_id("D")).await?);
+    assert!(graph.is_root(&ctx, name_cs_id("X")).await?);
+    assert!(!graph.is_root(&ctx, name_cs_id("B")).await?);
+    assert!(!graph.is_root(&ctx, name_cs_id("G")).await?);
+    assert!(graph
+        .is_root(&ctx, name_cs_id("nonexistent"))
+        .await
+        .is_err());
+
+    assert_roots_reachable_from(&graph, &ctx, vec![], vec![]).await?;
+    assert_roots_reachable_from(&graph, &ctx, vec!["A"], vec!["A"]).await?;
+    assert_roots_reachable_from(&graph, &ctx, vec!["C"], vec!["A"]).await?;
+    assert_roots_reachable_from(&graph, &ctx, vec!["C", "F"], vec!["A", "D"]).await?;
+    assert_roots_reachable_from(&graph, &ctx, vec!["I"], vec!["A", "D"]).await?;
+    assert_roots_reachable_from(&graph, &ctx, vec!["I", "Y"], vec!["A", "D", "X"]).await?;
+    assert_roots_reachable_from(&graph, &ctx, vec!["G", "E"], vec!["A", "D"]).await?;
 
     Ok(())
 }
@@ -889,5 +3140,304 @@ pub async fn test_children(ctx: CoreContext, storage: Arc<dyn CommitGraphStorage
     assert_children(&graph, &ctx, "M", vec![]).await?;
     assert_children(&graph, &ctx, "N", vec![]).await?;
 
+    assert_children_many(
+        &graph,
+        &ctx,
+        vec![
+            ("B", vec!["C", "F"]),
+            ("F", vec!["G", "I"]),
+            ("K", vec!["M", "N"]),
+            ("H", vec![]),
+        ],
+    )
+    .await?;
+    assert_children_many(&graph, &ctx, vec![]).await?;
+
+    Ok(())
+}
+
+pub async fn test_verify_edges(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+             A-B-C-D-G-H-I
+              \     /
+               E---F
+         "##,
+        storage,
+    )
+    .await?;
+
+    for name in ["A", "B", "C", "D", "E", "F", "G", "H", "I"] {
+        graph.verify_edges(&ctx, name_cs_id(name)).await?;
+    }
+
+    // Construct inconsistent edges directly in a mock storage.
+    let mock_storage = Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)));
+    let mock_graph = from_dag(
+        &ctx,
+        r##"
+             A-B-C-D
+              \
+               E-F
+         "##,
+        mock_storage.clone(),
+    )
+    .await?;
+    let edges = |node: ChangesetNode, parents: Vec<ChangesetNode>| ChangesetEdges {
+        node,
+        parents: parents.into(),
+        merge_ancestor: None,
+        skip_tree_parent: None,
+        skip_tree_skew_ancestor: None,
+        p1_linear_skew_ancestor: None,
+    };
+    let d = mock_storage
+        .fetch_edges_required(&ctx, name_cs_id("D"))
+        .await?
+        .node;
+    let f = mock_storage
+        .fetch_edges_required(&ctx, name_cs_id("F"))
+        .await?
+        .node;
+
+    mock_storage
+        .add(
+            &ctx,
+            edges(
+                name_cs_node("X", 5, 0, 0),
+                vec![d, name_cs_node("missing", 1, 0, 0)],
+            ),
+        )
+        .await?;
+    assert!(matches!(
+        mock_graph
+            .verify_edges(&ctx, name_cs_id("X"))
+            .await
+            .unwrap_err()
+            .downcast_ref::<CommitGraphError>(),
+        Some(CommitGraphError::MissingParent { cs_id, parent })
+            if *cs_id == name_cs_id("X") && *parent == name_cs_id("missing")
+    ));
+
+    mock_storage
+        .add(&ctx, edges(name_cs_node("Y", 7, 0, 0), vec![d]))
+        .await?;
+    assert!(matches!(
+        mock_graph
+            .verify_edges(&ctx, name_cs_id("Y"))
+            .await
+            .unwrap_err()
+            .downcast_ref::<CommitGraphError>(),
+        Some(CommitGraphError::InconsistentGeneration {
+            cs_id,
+            generation: 7,
+            expected: 5,
+        }) if *cs_id == name_cs_id("Y")
+    ));
+
+    mock_storage
+        .add(
+            &ctx,
+            ChangesetEdges {
+                skip_tree_skew_ancestor: Some(name_cs_node("D", 5, 0, 0)),
+                ..edges(name_cs_node("Z", 5, 0, 0), vec![d])
+            },
+        )
+        .await?;
+    assert!(matches!(
+        mock_graph
+            .verify_edges(&ctx, name_cs_id("Z"))
+            .await
+            .unwrap_err()
+            .downcast_ref::<CommitGraphError>(),
+        Some(CommitGraphError::SkewAncestorGenerationNotLower { cs_id, skew_ancestor })
+            if *cs_id == name_cs_id("Z") && *skew_ancestor == name_cs_id("D")
+    ));
+
+    mock_storage
+        .add(
+            &ctx,
+            ChangesetEdges {
+                skip_tree_skew_ancestor: Some(f),
+                ..edges(name_cs_node("W", 5, 0, 0), vec![d])
+            },
+        )
+        .await?;
+    assert!(matches!(
+        mock_graph
+            .verify_edges(&ctx, name_cs_id("W"))
+            .await
+            .unwrap_err()
+            .downcast_ref::<CommitGraphError>(),
+        Some(CommitGraphError::SkewAncestorNotAncestor { cs_id, skew_ancestor })
+            if *cs_id == name_cs_id("W") && *skew_ancestor == name_cs_id("F")
+    ));
+
+    Ok(())
+}
+
+pub async fn test_find_cycle(
+    ctx: CoreContext,
+    storage: Arc<dyn CommitGraphStorage>,
+) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+             A-B-C-D-G-H-I
+              \     /
+               E---F
+         "##,
+        storage,
+    )
+    .await?;
+
+    // Changesets reached through both sides of a merge are not cycles.
+    assert_eq!(graph.find_cycle(&ctx, name_cs_id("I"), 100).await?, None);
+    assert_eq!(graph.find_cycle(&ctx, name_cs_id("A"), 100).await?, None);
+    assert_eq!(graph.find_cycle(&ctx, name_cs_id("I"), 0).await?, None);
+
+    // Construct edges that form a cycle Y -> X -> Z -> Y directly in a
+    // mock storage.
+    let mock_storage = Arc::new(InMemoryCommitGraphStorage::new(RepositoryId::new(1)));
+    let mock_graph = from_dag(&ctx, "A-B-C", mock_storage.clone()).await?;
+    let edges = |node: ChangesetNode, parents: Vec<ChangesetNode>| ChangesetEdges {
+        node,
+        parents: parents.into(),
+        merge_ancestor: None,
+        skip_tree_parent: None,
+        skip_tree_skew_ancestor: None,
+        p1_linear_skew_ancestor: None,
+    };
+    let c = mock_storage
+        .fetch_edges_required(&ctx, name_cs_id("C"))
+        .await?
+        .node;
+    let x = name_cs_node("X", 6, 0, 0);
+    let y = name_cs_node("Y", 5, 0, 0);
+    let z = name_cs_node("Z", 4, 0, 0);
+    mock_storage.add(&ctx, edges(x, vec![c, z])).await?;
+    mock_storage.add(&ctx, edges(y, vec![x])).await?;
+    mock_storage.add(&ctx, edges(z, vec![y])).await?;
+    mock_storage
+        .add(&ctx, edges(name_cs_node("H", 7, 0, 0), vec![y]))
+        .await?;
+
+    // The walk goes H, Y, X, C, B, A and then Z, which leads back to Y.
+    assert_eq!(
+        mock_graph.find_cycle(&ctx, name_cs_id("H"), 7).await?,
+        Some(vec![
+            name_cs_id("Y"),
+            name_cs_id("X"),
+            name_cs_id("Z"),
+            name_cs_id("Y")
+        ])
+    );
+    assert_eq!(mock_graph.find_cycle(&ctx, name_cs_id("H"), 6).await?, None);
+    assert_eq!(
+        mock_graph.find_cycle(&ctx, name_cs_id("Z"), 100).await?,
+        Some(vec![
+            name_cs_id("Z"),
+            name_cs_id("Y"),
+            name_cs_id("X"),
+            name_cs_id("Z")
+        ])
+    );
+    assert_eq!(
+        mock_graph.find_cycle(&ctx, name_cs_id("C"), 100).await?,
+        None
+    );
+
+    Ok(())
+}
+
+pub async fn test_builder(ctx: CoreContext, storage: Arc<dyn CommitGraphStorage>) -> Result<()> {
+    let graph = from_dag(
+        &ctx,
+        r##"
+             A-B-C-D-G-H-I
+              \     /
+               E---F
+         "##,
+        storage.clone(),
+    )
+    .await?;
+
+    assert!(matches!(
+        CommitGraphBuilder::new()
+            .build()
+            .err()
+            .and_then(|err| err.downcast::<CommitGraphError>().ok()),
+        Some(CommitGraphError::MissingStorage)
+    ));
+
+    // Graphs built over the same storage see the same commits, whatever
+    // their settings.
+    for builder in [
+        CommitGraphBuilder::new().with_storage(storage.clone()),
+        CommitGraphBuilder::default()
+            .with_storage(storage.clone())
+            .with_default_prefetch(Prefetch::None),
+    ] {
+        let built_graph = builder.build()?;
+        assert_eq!(
+            built_graph
+                .ancestors_difference(&ctx, vec![name_cs_id("I")], vec![name_cs_id("B")])
+                .await?
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            graph
+                .ancestors_difference(&ctx, vec![name_cs_id("I")], vec![name_cs_id("B")])
+                .await?
+                .into_iter()
+                .collect::<HashSet<_>>()
+        );
+        assert_range_stream(
+            &built_graph,
+            &ctx,
+            "A",
+            "I",
+            vec!["A", "B", "C", "D", "E", "F", "G", "H", "I"],
+        )
+        .await?;
+    }
+
+    // Only the first graph built with a cache size can create the cache
+    // pool with that size.
+    #[cfg(fbcode_build)]
+    {
+        static INIT_CACHE: std::sync::Once = std::sync::Once::new();
+        INIT_CACHE.call_once(|| {
+            let config = cachelib::LruCacheConfig::new(64 * 1024 * 1024);
+            cachelib::init_cache(ctx.fb, config).unwrap();
+        });
+
+        let cached_graph = CommitGraphBuilder::new()
+            .with_storage(storage.clone())
+            .with_cache_size(8 * 1024 * 1024)
+            .build()?;
+        assert_range_stream(
+            &cached_graph,
+            &ctx,
+            "A",
+            "I",
+            vec!["A", "B", "C", "D", "E", "F", "G", "H", "I"],
+        )
+        .await?;
+
+        assert!(matches!(
+            CommitGraphBuilder::new()
+                .with_storage(storage.clone())
+                .with_cache_size(16 * 1024 * 1024)
+                .build()
+                .err()
+                .and_then(|err| err.downcast::<CommitGraphError>().ok()),
+            Some(CommitGraphError::CachePoolExists { .. })
+        ));
+    }
+
     Ok(())
 }
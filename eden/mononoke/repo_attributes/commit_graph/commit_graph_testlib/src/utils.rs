@@ -6,15 +6,21 @@
  */
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::ops::Range;
 use std::sync::Arc;
 
 use anyhow::Result;
 use commit_graph::CommitGraph;
+use commit_graph::CommitGraphError;
 use commit_graph_types::edges::ChangesetNode;
 use commit_graph_types::storage::CommitGraphStorage;
+use commit_graph_types::storage::Prefetch;
 use context::CoreContext;
 use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
 use futures::Future;
 use mononoke_types::ChangesetId;
 use mononoke_types::Generation;
@@ -118,62 +124,924 @@ pub async fn assert_skip_tree_skew_ancestor(
     Ok(())
 }
 
+pub async fn assert_commit_graph_skip_tree_skew_ancestor(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    u: &str,
+    u_skip_tree_skew_ancestor: Option<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .skip_tree_skew_ancestor(ctx, name_cs_id(u))
+            .await?
+            .map(|node| node.cs_id),
+        u_skip_tree_skew_ancestor.map(name_cs_id)
+    );
+    Ok(())
+}
+
 pub async fn assert_skip_tree_level_ancestor(
     graph: &CommitGraph,
     ctx: &CoreContext,
-    u: &str,
-    target_depth: u64,
-    u_level_ancestor: Option<&str>,
-) -> Result<()> {
+    u: &str,
+    target_depth: u64,
+    u_level_ancestor: Option<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .skip_tree_level_ancestor(ctx, name_cs_id(u), target_depth,)
+            .await?
+            .map(|node| node.cs_id),
+        u_level_ancestor.map(name_cs_id)
+    );
+    Ok(())
+}
+
+pub async fn assert_skip_tree_lowest_common_ancestor(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    u: &str,
+    v: &str,
+    lca: Option<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .skip_tree_lowest_common_ancestor(ctx, name_cs_id(u), name_cs_id(v),)
+            .await?
+            .map(|node| node.cs_id),
+        lca.map(name_cs_id)
+    );
+    Ok(())
+}
+
+pub async fn assert_distance(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    ancestor: &str,
+    descendant: &str,
+    distance: Option<u64>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .distance(ctx, name_cs_id(ancestor), name_cs_id(descendant))
+            .await?,
+        distance
+    );
+    Ok(())
+}
+
+pub async fn assert_is_ancestor_within(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    ancestor: &str,
+    descendant: &str,
+    max_depth: u64,
+    is_ancestor_within: bool,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .is_ancestor_within(ctx, name_cs_id(ancestor), name_cs_id(descendant), max_depth)
+            .await?,
+        is_ancestor_within
+    );
+    Ok(())
+}
+
+pub async fn assert_is_ancestor_batch(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    ancestor: &str,
+    descendants: Vec<&str>,
+    expected: Vec<bool>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .is_ancestor_batch(
+                ctx,
+                name_cs_id(ancestor),
+                descendants.into_iter().map(name_cs_id).collect(),
+            )
+            .await?,
+        expected
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_difference_with<Property, Out>(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    property_fn: Property,
+    ancestors_difference: Vec<&str>,
+) -> Result<()>
+where
+    Property: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+    Out: Future<Output = Result<bool>> + Send,
+{
+    let heads = heads.into_iter().map(name_cs_id).collect();
+    let common = common.into_iter().map(name_cs_id).collect();
+
+    assert_eq!(
+        graph
+            .ancestors_difference_with(ctx, heads, common, property_fn)
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        ancestors_difference
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_difference(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    ancestors_difference: Vec<&str>,
+) -> Result<()> {
+    let heads: Vec<_> = heads.into_iter().map(name_cs_id).collect();
+    let common: Vec<_> = common.into_iter().map(name_cs_id).collect();
+    let ancestors_difference = ancestors_difference
+        .into_iter()
+        .map(name_cs_id)
+        .collect::<HashSet<_>>();
+
+    assert_eq!(
+        graph
+            .ancestors_difference(ctx, heads.clone(), common.clone())
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        ancestors_difference
+    );
+
+    for prefetch in [Prefetch::None, Prefetch::for_p1_linear_traversal()] {
+        assert_eq!(
+            graph
+                .ancestors_difference_stream_with_prefetch(
+                    ctx,
+                    heads.clone(),
+                    common.clone(),
+                    prefetch
+                )
+                .await?
+                .try_collect::<HashSet<_>>()
+                .await?,
+            ancestors_difference
+        );
+    }
+
+    let with_parents = graph
+        .ancestors_difference_with_parents_stream(ctx, heads, common)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(
+        with_parents
+            .iter()
+            .map(|(cs_id, _)| *cs_id)
+            .collect::<HashSet<_>>(),
+        ancestors_difference
+    );
+    for (cs_id, parents) in with_parents {
+        assert_eq!(parents, graph.changeset_parents_required(ctx, cs_id).await?);
+    }
+    Ok(())
+}
+
+pub async fn assert_ancestors_difference_multi_common(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    commons: Vec<Vec<&str>>,
+    ancestors_difference: Vec<&str>,
+) -> Result<()> {
+    let heads: Vec<_> = heads.into_iter().map(name_cs_id).collect();
+    let commons: Vec<Vec<_>> = commons
+        .into_iter()
+        .map(|common| common.into_iter().map(name_cs_id).collect())
+        .collect();
+    let ancestors_difference = ancestors_difference
+        .into_iter()
+        .map(name_cs_id)
+        .collect::<HashSet<_>>();
+
+    assert_eq!(
+        graph
+            .ancestors_difference_multi_common(ctx, heads.clone(), commons.clone())
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        ancestors_difference
+    );
+    assert_eq!(
+        graph
+            .ancestors_difference(ctx, heads, commons.concat())
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        ancestors_difference
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_difference_by_head(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    by_head: Vec<(&str, Vec<&str>)>,
+) -> Result<()> {
+    let heads: Vec<_> = heads.into_iter().map(name_cs_id).collect();
+    let common: Vec<_> = common.into_iter().map(name_cs_id).collect();
+
+    let actual = graph
+        .ancestors_difference_by_head(ctx, heads.clone(), common.clone())
+        .await?;
+    assert_eq!(
+        actual,
+        by_head
+            .into_iter()
+            .map(|(head, ancestors)| (
+                name_cs_id(head),
+                ancestors.into_iter().map(name_cs_id).collect::<Vec<_>>()
+            ))
+            .collect::<HashMap<_, _>>()
+    );
+
+    // Together the heads cover the whole difference, each changeset once.
+    let all = actual.into_values().flatten().collect::<Vec<_>>();
+    let all_set = all.iter().copied().collect::<HashSet<_>>();
+    assert_eq!(all.len(), all_set.len());
+    assert_eq!(
+        all_set,
+        graph
+            .ancestors_difference(ctx, heads, common)
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_difference_capped(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    cap: usize,
+    ancestors_difference: Option<Vec<&str>>,
+) -> Result<()> {
+    let heads = heads.into_iter().map(name_cs_id).collect();
+    let common = common.into_iter().map(name_cs_id).collect();
+
+    let result = graph
+        .ancestors_difference_capped(ctx, heads, common, cap)
+        .await;
+
+    match ancestors_difference {
+        Some(ancestors_difference) => assert_eq!(
+            result?.into_iter().collect::<HashSet<_>>(),
+            ancestors_difference
+                .into_iter()
+                .map(name_cs_id)
+                .collect::<HashSet<_>>()
+        ),
+        None => assert!(matches!(
+            result.unwrap_err().downcast_ref::<CommitGraphError>(),
+            Some(CommitGraphError::AncestorsDifferenceCapExceeded { cap: exceeded_cap })
+                if *exceeded_cap == cap
+        )),
+    }
+    Ok(())
+}
+
+pub async fn assert_count_ancestors_difference(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    count: u64,
+) -> Result<()> {
+    let heads = heads.into_iter().map(name_cs_id).collect();
+    let common = common.into_iter().map(name_cs_id).collect();
+
+    assert_eq!(
+        graph.count_ancestors_difference(ctx, heads, common).await?,
+        count
+    );
+    Ok(())
+}
+
+pub async fn assert_generation_histogram(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    bucket_size: u64,
+    histogram: Vec<(u64, u64)>,
+) -> Result<()> {
+    let heads: Vec<_> = heads.into_iter().map(name_cs_id).collect();
+
+    let actual = graph
+        .generation_histogram(ctx, heads.clone(), bucket_size)
+        .await?;
+    assert_eq!(actual, histogram.into_iter().collect::<BTreeMap<_, _>>());
+    assert_eq!(
+        actual.values().sum::<u64>(),
+        graph.count_ancestors_difference(ctx, heads, vec![]).await?
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_symmetric_difference(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    a: Vec<&str>,
+    b: Vec<&str>,
+    only_a: Vec<&str>,
+    only_b: Vec<&str>,
+) -> Result<()> {
+    let a: Vec<_> = a.into_iter().map(name_cs_id).collect();
+    let b: Vec<_> = b.into_iter().map(name_cs_id).collect();
+
+    let (actual_only_a, actual_only_b) = graph
+        .ancestors_symmetric_difference(ctx, a.clone(), b.clone())
+        .await?;
+
+    for (actual, expected) in [(&actual_only_a, only_a), (&actual_only_b, only_b)] {
+        assert_eq!(
+            actual.iter().copied().collect::<HashSet<_>>(),
+            expected.into_iter().map(name_cs_id).collect::<HashSet<_>>()
+        );
+        // Each changeset is returned once, in decreasing generation order.
+        let generations = graph
+            .changeset_generation_many_required(ctx, actual.clone())
+            .await?;
+        let actual_generations = actual
+            .iter()
+            .map(|cs_id| generations[cs_id])
+            .collect::<Vec<_>>();
+        assert!(actual_generations.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(actual.len(), generations.len());
+    }
+
+    assert_eq!(
+        actual_only_a.into_iter().collect::<HashSet<_>>(),
+        graph
+            .ancestors_difference(ctx, a.clone(), b.clone())
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>()
+    );
+    assert_eq!(
+        actual_only_b.into_iter().collect::<HashSet<_>>(),
+        graph
+            .ancestors_difference(ctx, b, a)
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
+async fn assert_topological_order(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_ids: &Vec<ChangesetId>,
+) -> Result<()> {
+    let all_cs_ids: HashSet<ChangesetId> = cs_ids.iter().copied().collect();
+    let mut previous_cs_ids: HashSet<ChangesetId> = Default::default();
+
+    for cs_id in cs_ids {
+        let parents = graph.changeset_parents_required(ctx, *cs_id).await?;
+        // Check that each parent of cs_id either isn't contained in cs_ids
+        // or is found before cs_id.
+        assert!(
+            parents
+                .into_iter()
+                .all(|parent| !all_cs_ids.contains(&parent) || previous_cs_ids.contains(&parent))
+        );
+        previous_cs_ids.insert(*cs_id);
+    }
+
+    Ok(())
+}
+
+pub async fn assert_extract_subgraph(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    subgraph: Vec<(&str, Vec<&str>)>,
+) -> Result<()> {
+    let heads = heads.into_iter().map(name_cs_id).collect();
+    let common = common.into_iter().map(name_cs_id).collect();
+
+    let extracted = graph.extract_subgraph(ctx, heads, common).await?;
+
+    assert_topological_order(
+        graph,
+        ctx,
+        &extracted.iter().map(|(cs_id, _)| *cs_id).collect(),
+    )
+    .await?;
+
+    assert_eq!(
+        extracted
+            .into_iter()
+            .map(|(cs_id, parents)| (cs_id, parents.into_iter().collect()))
+            .collect::<HashMap<_, HashSet<_>>>(),
+        subgraph
+            .into_iter()
+            .map(|(name, parents)| (
+                name_cs_id(name),
+                parents.into_iter().map(name_cs_id).collect()
+            ))
+            .collect::<HashMap<_, HashSet<_>>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_highest_generation_head(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    highest_generation_head: Option<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .highest_generation_head(ctx, heads.into_iter().map(name_cs_id).collect())
+            .await?,
+        highest_generation_head.map(name_cs_id)
+    );
+    Ok(())
+}
+
+pub async fn assert_merge_parents(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_id: &str,
+    merge_parents: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph.merge_parents(ctx, name_cs_id(cs_id)).await?,
+        merge_parents
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_second_parent_ancestors(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    ancestors: Vec<&str>,
+) -> Result<()> {
+    let second_parent_ancestors = graph
+        .second_parent_ancestors_stream(ctx, heads.into_iter().map(name_cs_id).collect())
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    // Check that changesets are yielded in decreasing generation order.
+    let mut generations = vec![];
+    for cs_id in second_parent_ancestors.iter() {
+        generations.push(graph.changeset_generation_required(ctx, *cs_id).await?);
+    }
+    assert!(generations.windows(2).all(|w| w[0] >= w[1]));
+
+    assert_eq!(
+        second_parent_ancestors.into_iter().collect::<HashSet<_>>(),
+        ancestors
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_segments(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    segments: Vec<(&str, &str, u64)>,
+) -> Result<()> {
+    let heads = heads.into_iter().map(name_cs_id).collect();
+    let common = common.into_iter().map(name_cs_id).collect();
+
+    assert_eq!(
+        graph
+            .ancestors_segments(ctx, heads, common)
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        segments
+            .into_iter()
+            .map(|(low, high, length)| (name_cs_id(low), name_cs_id(high), length))
+            .collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_are_independent(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    u: &str,
+    v: &str,
+    are_independent: bool,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .are_independent(ctx, name_cs_id(u), name_cs_id(v))
+            .await?,
+        are_independent
+    );
+    assert_eq!(
+        graph
+            .are_independent(ctx, name_cs_id(v), name_cs_id(u))
+            .await?,
+        are_independent
+    );
+    Ok(())
+}
+
+pub async fn assert_frontier_at_generation(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    target: u64,
+    frontier: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .frontier_at_generation(
+                ctx,
+                heads.into_iter().map(name_cs_id).collect(),
+                Generation::new(target)
+            )
+            .await?,
+        frontier
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_with_depth(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    max_depth: u64,
+    ancestors: Vec<(&str, u64)>,
+) -> Result<()> {
+    let ancestors_with_depth = graph
+        .ancestors_with_depth_stream(ctx, heads.into_iter().map(name_cs_id).collect(), max_depth)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    // Check that changesets are yielded once, in increasing order of depth.
+    assert!(ancestors_with_depth.windows(2).all(|w| w[0].1 <= w[1].1));
+    assert_eq!(ancestors_with_depth.len(), ancestors.len());
+
+    assert_eq!(
+        ancestors_with_depth.into_iter().collect::<HashMap<_, _>>(),
+        ancestors
+            .into_iter()
+            .map(|(name, depth)| (name_cs_id(name), depth))
+            .collect::<HashMap<_, _>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_first_parent_history(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    head: &str,
+    history: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .first_parent_history_stream(ctx, name_cs_id(head))
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?,
+        history.into_iter().map(name_cs_id).collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_first_parent_depth(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_id: &str,
+    depth: u64,
+) -> Result<()> {
+    assert_eq!(
+        graph.first_parent_depth(ctx, name_cs_id(cs_id)).await?,
+        depth
+    );
+    assert_eq!(
+        graph
+            .first_parent_history_stream(ctx, name_cs_id(cs_id))
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?
+            .len() as u64,
+        depth + 1
+    );
+    Ok(())
+}
+
+pub async fn assert_location_to_changesets(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    known: &str,
+    distance: u64,
+    count: u64,
+    changesets: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .location_to_changesets(ctx, name_cs_id(known), distance, count)
+            .await?,
+        changesets.into_iter().map(name_cs_id).collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_changeset_to_location(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    master_heads: Vec<&str>,
+    cs_id: &str,
+    location: Option<(&str, u64)>,
+) -> Result<()> {
+    let master_heads = master_heads.into_iter().map(name_cs_id).collect();
+    let actual = graph
+        .changeset_to_location(ctx, master_heads, name_cs_id(cs_id))
+        .await?;
+    assert_eq!(
+        actual,
+        location.map(|(head, distance)| (name_cs_id(head), distance))
+    );
+
+    // The location resolves back to the changeset.
+    if let Some((head, distance)) = actual {
+        assert_eq!(
+            graph.location_to_changesets(ctx, head, distance, 1).await?,
+            vec![name_cs_id(cs_id)]
+        );
+    }
+    Ok(())
+}
+
+pub async fn assert_is_fast_forward(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    from: &str,
+    to: &str,
+    is_fast_forward: bool,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .is_fast_forward(ctx, name_cs_id(from), name_cs_id(to))
+            .await?,
+        is_fast_forward
+    );
+    Ok(())
+}
+
+pub async fn assert_nearest_ancestor_in_set(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    head: &str,
+    candidates: Vec<&str>,
+    nearest_ancestor: Option<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .nearest_ancestor_in_set(
+                ctx,
+                name_cs_id(head),
+                candidates.into_iter().map(name_cs_id).collect()
+            )
+            .await?,
+        nearest_ancestor.map(name_cs_id)
+    );
+    Ok(())
+}
+
+pub async fn assert_roots_reachable_from(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    roots: Vec<&str>,
+) -> Result<()> {
+    let roots_reachable_from = graph
+        .roots_reachable_from(ctx, heads.into_iter().map(name_cs_id).collect())
+        .await?;
+
+    for root in roots_reachable_from.iter() {
+        assert!(graph.is_root(ctx, *root).await?);
+    }
+
+    assert_eq!(
+        roots_reachable_from,
+        roots
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_range_stream(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    start: &str,
+    end: &str,
+    range: Vec<&str>,
+) -> Result<()> {
+    let start_id = name_cs_id(start);
+    let end_id = name_cs_id(end);
+
+    let range_stream_cs_ids = graph
+        .range_stream(ctx, start_id, end_id)
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_topological_order(graph, ctx, &range_stream_cs_ids).await?;
+
+    assert_eq!(
+        range_stream_cs_ids.into_iter().collect::<HashSet<_>>(),
+        range.into_iter().map(name_cs_id).collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_range_stream_limited(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    start: &str,
+    end: &str,
+    limit: usize,
+    range: Vec<&str>,
+) -> Result<()> {
+    let start_id = name_cs_id(start);
+    let end_id = name_cs_id(end);
+
+    let range_stream_cs_ids = graph
+        .range_stream_limited(ctx, start_id, end_id, limit)
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_topological_order(graph, ctx, &range_stream_cs_ids).await?;
+
+    let range = range.into_iter().map(name_cs_id).collect::<HashSet<_>>();
+    assert_eq!(range_stream_cs_ids.len(), limit.min(range.len()));
+    assert!(range_stream_cs_ids
+        .iter()
+        .all(|cs_id| range.contains(cs_id)));
+
+    // The output should be a prefix of the whole range in topological order,
+    // so it must be closed under taking parents within the range.
+    let output = range_stream_cs_ids.iter().copied().collect::<HashSet<_>>();
+    for cs_id in range_stream_cs_ids {
+        for parent in graph.changeset_parents_required(ctx, cs_id).await? {
+            assert!(!range.contains(&parent) || output.contains(&parent));
+        }
+    }
+    Ok(())
+}
+
+pub async fn assert_descendants_difference_with<Property, Out>(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    property_fn: Property,
+    descendants_difference: Vec<&str>,
+) -> Result<()>
+where
+    Property: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+    Out: Future<Output = Result<bool>> + Send,
+{
+    let heads = heads.into_iter().map(name_cs_id).collect();
+    let common = common.into_iter().map(name_cs_id).collect();
+
+    let descendants_difference_cs_ids = graph
+        .descendants_difference_stream_with(ctx, heads, common, property_fn)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    assert_topological_order(graph, ctx, &descendants_difference_cs_ids).await?;
+
     assert_eq!(
-        graph
-            .skip_tree_level_ancestor(ctx, name_cs_id(u), target_depth,)
-            .await?
-            .map(|node| node.cs_id),
-        u_level_ancestor.map(name_cs_id)
+        descendants_difference_cs_ids
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        descendants_difference
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>()
     );
     Ok(())
 }
 
-pub async fn assert_skip_tree_lowest_common_ancestor(
+pub async fn assert_descendants_difference(
     graph: &CommitGraph,
     ctx: &CoreContext,
-    u: &str,
-    v: &str,
-    lca: Option<&str>,
+    heads: Vec<&str>,
+    common: Vec<&str>,
+    descendants_difference: Vec<&str>,
+) -> Result<()> {
+    assert_descendants_difference_with(
+        graph,
+        ctx,
+        heads,
+        common,
+        |_| async { Ok(false) },
+        descendants_difference,
+    )
+    .await
+}
+
+pub async fn assert_sort_topological(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_ids: Vec<&str>,
+    sorted: Vec<&str>,
 ) -> Result<()> {
     assert_eq!(
         graph
-            .skip_tree_lowest_common_ancestor(ctx, name_cs_id(u), name_cs_id(v),)
-            .await?
-            .map(|node| node.cs_id),
-        lca.map(name_cs_id)
+            .sort_topological(ctx, cs_ids.into_iter().map(name_cs_id).collect())
+            .await?,
+        sorted.into_iter().map(name_cs_id).collect::<Vec<_>>()
     );
     Ok(())
 }
 
-pub async fn assert_ancestors_difference_with<Property, Out>(
+pub async fn assert_topo_order_stream(
     graph: &CommitGraph,
     ctx: &CoreContext,
     heads: Vec<&str>,
     common: Vec<&str>,
+    topo_order: Vec<&str>,
+) -> Result<()> {
+    let heads = heads.into_iter().map(name_cs_id).collect();
+    let common = common.into_iter().map(name_cs_id).collect();
+
+    let topo_order_cs_ids = graph
+        .topo_order_stream(ctx, heads, common)
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_topological_order(graph, ctx, &topo_order_cs_ids).await?;
+
+    assert_eq!(
+        topo_order_cs_ids,
+        topo_order.into_iter().map(name_cs_id).collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_frontier_with<Property, Out>(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
     property_fn: Property,
-    ancestors_difference: Vec<&str>,
+    ancestors_frontier: Vec<&str>,
 ) -> Result<()>
 where
     Property: Fn(ChangesetId) -> Out + Send + Sync + 'static,
-    Out: Future<Output = Result<bool>> + Send,
+    Out: Future<Output = Result<bool>>,
 {
     let heads = heads.into_iter().map(name_cs_id).collect();
-    let common = common.into_iter().map(name_cs_id).collect();
 
     assert_eq!(
         graph
-            .ancestors_difference_with(ctx, heads, common, property_fn)
+            .ancestors_frontier_with(ctx, heads, property_fn)
             .await?
             .into_iter()
             .collect::<HashSet<_>>(),
-        ancestors_difference
+        ancestors_frontier
             .into_iter()
             .map(name_cs_id)
             .collect::<HashSet<_>>()
@@ -181,23 +1049,41 @@ where
     Ok(())
 }
 
-pub async fn assert_ancestors_difference(
+pub async fn assert_merge_commits_in_difference(
     graph: &CommitGraph,
     ctx: &CoreContext,
     heads: Vec<&str>,
     common: Vec<&str>,
-    ancestors_difference: Vec<&str>,
+    merges: Vec<&str>,
 ) -> Result<()> {
     let heads = heads.into_iter().map(name_cs_id).collect();
     let common = common.into_iter().map(name_cs_id).collect();
 
     assert_eq!(
         graph
-            .ancestors_difference(ctx, heads, common)
+            .merge_commits_in_difference(ctx, heads, common)
+            .await?,
+        merges.into_iter().map(name_cs_id).collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_ancestors_above_generation(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    heads: Vec<&str>,
+    min_gen: u64,
+    ancestors: Vec<&str>,
+) -> Result<()> {
+    let heads = heads.into_iter().map(name_cs_id).collect();
+
+    assert_eq!(
+        graph
+            .ancestors_above_generation(ctx, heads, Generation::new(min_gen))
             .await?
             .into_iter()
             .collect::<HashSet<_>>(),
-        ancestors_difference
+        ancestors
             .into_iter()
             .map(name_cs_id)
             .collect::<HashSet<_>>()
@@ -205,77 +1091,92 @@ pub async fn assert_ancestors_difference(
     Ok(())
 }
 
-async fn assert_topological_order(
+pub async fn assert_ancestors_in_generation_range(
     graph: &CommitGraph,
     ctx: &CoreContext,
-    cs_ids: &Vec<ChangesetId>,
+    heads: Vec<&str>,
+    range: Range<u64>,
+    ancestors: Vec<&str>,
 ) -> Result<()> {
-    let all_cs_ids: HashSet<ChangesetId> = cs_ids.iter().copied().collect();
-    let mut previous_cs_ids: HashSet<ChangesetId> = Default::default();
-
-    for cs_id in cs_ids {
-        let parents = graph.changeset_parents_required(ctx, *cs_id).await?;
-        // Check that each parent of cs_id either isn't contained in cs_ids
-        // or is found before cs_id.
-        assert!(
-            parents
-                .into_iter()
-                .all(|parent| !all_cs_ids.contains(&parent) || previous_cs_ids.contains(&parent))
-        );
-        previous_cs_ids.insert(*cs_id);
-    }
+    let heads = heads.into_iter().map(name_cs_id).collect();
 
+    assert_eq!(
+        graph
+            .ancestors_in_generation_range(
+                ctx,
+                heads,
+                Generation::new(range.start)..Generation::new(range.end)
+            )
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        ancestors
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>()
+    );
     Ok(())
 }
 
-pub async fn assert_range_stream(
+pub async fn assert_descendants_in_generation_range(
     graph: &CommitGraph,
     ctx: &CoreContext,
-    start: &str,
-    end: &str,
-    range: Vec<&str>,
+    roots: Vec<&str>,
+    range: Range<u64>,
+    descendants: Vec<&str>,
 ) -> Result<()> {
-    let start_id = name_cs_id(start);
-    let end_id = name_cs_id(end);
-
-    let range_stream_cs_ids = graph
-        .range_stream(ctx, start_id, end_id)
-        .await?
-        .collect::<Vec<_>>()
-        .await;
+    let roots = roots.into_iter().map(name_cs_id).collect();
 
-    assert_topological_order(graph, ctx, &range_stream_cs_ids).await?;
+    assert_eq!(
+        graph
+            .descendants_in_generation_range(
+                ctx,
+                roots,
+                Generation::new(range.start)..Generation::new(range.end)
+            )
+            .await?
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        descendants
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<HashSet<_>>()
+    );
+    Ok(())
+}
 
+pub async fn assert_path_to_ancestor(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    descendant: &str,
+    ancestor: &str,
+    path: Option<Vec<&str>>,
+) -> Result<()> {
     assert_eq!(
-        range_stream_cs_ids.into_iter().collect::<HashSet<_>>(),
-        range.into_iter().map(name_cs_id).collect::<HashSet<_>>()
+        graph
+            .path_to_ancestor(ctx, name_cs_id(descendant), name_cs_id(ancestor))
+            .await?,
+        path.map(|path| path.into_iter().map(name_cs_id).collect::<Vec<_>>())
     );
     Ok(())
 }
 
-pub async fn assert_ancestors_frontier_with<Property, Out>(
+pub async fn assert_first_ancestor_satisfying<Property, Out>(
     graph: &CommitGraph,
     ctx: &CoreContext,
-    heads: Vec<&str>,
+    head: &str,
     property_fn: Property,
-    ancestors_frontier: Vec<&str>,
+    first_ancestor: Option<&str>,
 ) -> Result<()>
 where
-    Property: Fn(ChangesetId) -> Out + Send + Sync + 'static,
+    Property: Fn(ChangesetId) -> Out + Send + Sync,
     Out: Future<Output = Result<bool>>,
 {
-    let heads = heads.into_iter().map(name_cs_id).collect();
-
     assert_eq!(
         graph
-            .ancestors_frontier_with(ctx, heads, property_fn)
-            .await?
-            .into_iter()
-            .collect::<HashSet<_>>(),
-        ancestors_frontier
-            .into_iter()
-            .map(name_cs_id)
-            .collect::<HashSet<_>>()
+            .first_ancestor_satisfying(ctx, name_cs_id(head), property_fn)
+            .await?,
+        first_ancestor.map(name_cs_id)
     );
     Ok(())
 }
@@ -353,6 +1254,82 @@ pub async fn assert_common_base(
     Ok(())
 }
 
+pub async fn assert_lowest_common_ancestor(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    u: &str,
+    v: &str,
+    lca: Option<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .lowest_common_ancestor(ctx, name_cs_id(u), name_cs_id(v))
+            .await?,
+        lca.map(name_cs_id)
+    );
+    Ok(())
+}
+
+pub async fn assert_common_base_multi(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_ids: Vec<&str>,
+    common_base: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .common_base_multi(ctx, cs_ids.into_iter().map(name_cs_id).collect())
+            .await?,
+        common_base
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_heads_of_set(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_ids: Vec<&str>,
+    heads: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .heads_of_set(ctx, cs_ids.into_iter().map(name_cs_id).collect())
+            .await?,
+        heads
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+pub async fn assert_roots_of_set(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    cs_ids: Vec<&str>,
+    roots: Vec<&str>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .roots_of_set(ctx, cs_ids.into_iter().map(name_cs_id).collect())
+            .await?,
+        roots
+            .into_iter()
+            .map(name_cs_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
 pub async fn assert_slice_ancestors<NeedsProcessing, Out>(
     graph: &CommitGraph,
     ctx: &CoreContext,
@@ -362,24 +1339,68 @@ pub async fn assert_slice_ancestors<NeedsProcessing, Out>(
     slices: Vec<(u64, Vec<&str>)>,
 ) -> Result<()>
 where
-    NeedsProcessing: Fn(Vec<ChangesetId>) -> Out,
-    Out: Future<Output = Result<HashSet<ChangesetId>>>,
+    NeedsProcessing: Fn(Vec<ChangesetId>) -> Out + Clone + Send + Sync,
+    Out: Future<Output = Result<HashSet<ChangesetId>>> + Send,
 {
-    let heads = heads.into_iter().map(name_cs_id).collect();
+    let heads: Vec<_> = heads.into_iter().map(name_cs_id).collect();
+    let slices = slices
+        .into_iter()
+        .map(|(gen_group, cs_ids)| {
+            (
+                gen_group,
+                cs_ids.into_iter().map(name_cs_id).collect::<HashSet<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+
     assert_eq!(
         graph
-            .slice_ancestors(ctx, heads, needs_processing, slice_size)
+            .slice_ancestors(ctx, heads.clone(), needs_processing.clone(), slice_size)
             .await?
             .into_iter()
             .map(|(gen_group, cs_ids)| (gen_group, cs_ids.into_iter().collect::<HashSet<_>>()))
             .collect::<Vec<_>>(),
-        slices
+        slices,
+    );
+
+    // The stream yields the same slices in decreasing order of generation.
+    assert_eq!(
+        graph
+            .slice_ancestors_stream(ctx, heads, needs_processing, slice_size)
+            .await?
+            .map_ok(|(gen_group, cs_ids)| (gen_group, cs_ids.into_iter().collect::<HashSet<_>>()))
+            .try_collect::<Vec<_>>()
+            .await?,
+        slices.into_iter().rev().collect::<Vec<_>>(),
+    );
+    Ok(())
+}
+
+pub async fn assert_children_many(
+    graph: &CommitGraph,
+    ctx: &CoreContext,
+    children: Vec<(&str, Vec<&str>)>,
+) -> Result<()> {
+    assert_eq!(
+        graph
+            .changeset_children_many(
+                ctx,
+                children
+                    .iter()
+                    .map(|(cs_id, _)| name_cs_id(cs_id))
+                    .collect()
+            )
+            .await?
             .into_iter()
-            .map(|(gen_group, cs_ids)| (
-                gen_group,
-                cs_ids.into_iter().map(name_cs_id).collect::<HashSet<_>>()
+            .map(|(cs_id, children)| (cs_id, children.into_iter().collect::<HashSet<_>>()))
+            .collect::<HashMap<_, _>>(),
+        children
+            .into_iter()
+            .map(|(cs_id, children)| (
+                name_cs_id(cs_id),
+                children.into_iter().map(name_cs_id).collect::<HashSet<_>>()
             ))
-            .collect::<Vec<_>>(),
+            .collect::<HashMap<_, _>>(),
     );
     Ok(())
 }
@@ -390,13 +1411,28 @@ pub async fn assert_children(
     cs_id: &str,
     children: Vec<&str>,
 ) -> Result<()> {
+    let children = children.into_iter().map(name_cs_id).collect::<HashSet<_>>();
     assert_eq!(
         graph
             .changeset_children(ctx, name_cs_id(cs_id))
             .await?
             .into_iter()
             .collect::<HashSet<_>>(),
-        children.into_iter().map(name_cs_id).collect::<HashSet<_>>(),
+        children,
+    );
+    assert_eq!(
+        graph
+            .changeset_children_stream(ctx, name_cs_id(cs_id))
+            .await?
+            .try_collect::<HashSet<_>>()
+            .await?,
+        children,
+    );
+    assert_eq!(
+        graph
+            .changeset_children_count(ctx, name_cs_id(cs_id))
+            .await?,
+        children.len(),
     );
     Ok(())
 }
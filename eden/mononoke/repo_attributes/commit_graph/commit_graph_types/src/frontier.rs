@@ -41,6 +41,15 @@ impl ChangesetFrontier {
         }
     }
 
+    pub fn lowest_generation_contains(&self, cs_id: ChangesetId, generation: Generation) -> bool {
+        match self.first_key_value() {
+            None => false,
+            Some((lowest_frontier_generation, cs_ids)) => {
+                *lowest_frontier_generation == generation && cs_ids.contains(&cs_id)
+            }
+        }
+    }
+
     pub fn highest_generation_intersection(
         &self,
         other_frontier: &ChangesetFrontier,
@@ -67,6 +76,23 @@ impl ChangesetFrontier {
         true
     }
 
+    /// Returns true if no changeset in this frontier is also contained in
+    /// all of the other frontiers.
+    pub fn is_disjoint_from_all(&self, other_frontiers: &[ChangesetFrontier]) -> bool {
+        for (gen, cs_ids) in self.iter().rev() {
+            for cs_id in cs_ids {
+                if other_frontiers.iter().all(|other_frontier| {
+                    other_frontier
+                        .get(gen)
+                        .is_some_and(|other_cs_ids| other_cs_ids.contains(cs_id))
+                }) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
     /// Return an iterator over tuples of each changeset in the frontier
     /// together with its generation number.
     pub fn into_flat_iter(self) -> impl Iterator<Item = (ChangesetId, Generation)> {
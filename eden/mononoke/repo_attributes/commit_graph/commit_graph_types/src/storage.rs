@@ -14,6 +14,9 @@ use std::collections::HashMap;
 use anyhow::Result;
 use async_trait::async_trait;
 use context::CoreContext;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use mononoke_types::ChangesetId;
 use mononoke_types::ChangesetIdPrefix;
 use mononoke_types::ChangesetIdsResolvedFromPrefix;
@@ -191,4 +194,47 @@ pub trait CommitGraphStorage: Send + Sync {
         _ctx: &CoreContext,
         _cs_id: ChangesetId,
     ) -> Result<Vec<ChangesetId>>;
+
+    /// Fetch all children of a changeset as a stream.
+    ///
+    /// The default implementation fetches all of the children with
+    /// `fetch_children` and streams them from memory. None of the existing
+    /// storage back-ends fetch children incrementally, so they all use the
+    /// default implementation.
+    async fn fetch_children_stream(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<BoxStream<'static, Result<ChangesetId>>> {
+        let children = self.fetch_children(ctx, cs_id).await?;
+        Ok(stream::iter(children).map(Ok).boxed())
+    }
+
+    /// Count the children of a changeset.
+    ///
+    /// The default implementation fetches all of the children with
+    /// `fetch_children` and counts them. Implementors that can count
+    /// children without fetching them should override this. The in-memory
+    /// and SQL back-ends do, and the caching and preloaded back-ends forward
+    /// to the storage they wrap.
+    async fn count_children(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<usize> {
+        Ok(self.fetch_children(ctx, cs_id).await?.len())
+    }
+
+    /// Fetch all children of multiple changesets.
+    ///
+    /// The default implementation fetches the children of each changeset
+    /// one at a time. Implementors that can fetch children in bulk should
+    /// override this.
+    async fn fetch_many_children(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        let mut children = HashMap::new();
+        for cs_id in cs_ids {
+            children.insert(*cs_id, self.fetch_children(ctx, *cs_id).await?);
+        }
+        Ok(children)
+    }
 }
@@ -200,6 +200,34 @@ impl CommitGraphStorage for InMemoryCommitGraphStorage {
             .into_iter()
             .collect())
     }
+
+    async fn count_children(&self, _ctx: &CoreContext, cs_id: ChangesetId) -> Result<usize> {
+        Ok(self
+            .children
+            .read()
+            .get(&cs_id)
+            .map_or(0, |children| children.len()))
+    }
+
+    async fn fetch_many_children(
+        &self,
+        _ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        let children = self.children.read();
+        Ok(cs_ids
+            .iter()
+            .map(|cs_id| {
+                (
+                    *cs_id,
+                    children
+                        .get(cs_id)
+                        .map(|children| children.iter().copied().collect())
+                        .unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
 }
 
 #[cfg(test)]
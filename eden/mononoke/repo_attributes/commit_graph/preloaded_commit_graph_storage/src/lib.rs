@@ -421,4 +421,18 @@ impl CommitGraphStorage for PreloadedCommitGraphStorage {
     async fn fetch_children(&self, ctx: &CoreContext, cs: ChangesetId) -> Result<Vec<ChangesetId>> {
         self.persistent_storage.fetch_children(ctx, cs).await
     }
+
+    async fn count_children(&self, ctx: &CoreContext, cs: ChangesetId) -> Result<usize> {
+        self.persistent_storage.count_children(ctx, cs).await
+    }
+
+    async fn fetch_many_children(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        self.persistent_storage
+            .fetch_many_children(ctx, cs_ids)
+            .await
+    }
 }
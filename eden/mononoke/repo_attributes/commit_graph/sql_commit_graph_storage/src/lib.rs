@@ -812,6 +812,63 @@ mononoke_queries! {
                 = {cs_id};
         "
     }
+
+    read CountChildren(repo_id: RepositoryId, cs_id: ChangesetId) -> (u64) {
+        "
+        SELECT COUNT(*)
+        FROM (
+            SELECT
+                cs.cs_id
+            FROM commit_graph_edges cs
+            INNER JOIN commit_graph_edges cs_p1_parent
+                ON cs_p1_parent.id = cs.p1_parent
+            WHERE
+                cs_p1_parent.repo_id = {repo_id}
+                AND cs_p1_parent.cs_id = {cs_id}
+
+            UNION
+
+            SELECT
+                cs.cs_id
+            FROM commit_graph_edges cs
+            INNER JOIN commit_graph_merge_parents cgmp
+                ON cgmp.id = cs.id
+            INNER JOIN commit_graph_edges cs_merge_parent
+                ON cgmp.parent = cs_merge_parent.id
+            WHERE
+                cs_merge_parent.repo_id = {repo_id}
+                AND cs_merge_parent.cs_id = {cs_id}
+        ) children
+        "
+    }
+
+    read SelectManyChildren(repo_id: RepositoryId, >list cs_ids: ChangesetId) -> (ChangesetId, ChangesetId) {
+        "
+        SELECT
+            cs_p1_parent.cs_id,
+            cs.cs_id
+        FROM commit_graph_edges cs
+        INNER JOIN commit_graph_edges cs_p1_parent
+            ON cs_p1_parent.id = cs.p1_parent
+        WHERE
+            cs_p1_parent.repo_id = {repo_id}
+            AND cs_p1_parent.cs_id IN {cs_ids}
+
+        UNION
+
+        SELECT
+            cs_merge_parent.cs_id,
+            cs.cs_id
+        FROM commit_graph_edges cs
+        INNER JOIN commit_graph_merge_parents cgmp
+            ON cgmp.id = cs.id
+        INNER JOIN commit_graph_edges cs_merge_parent
+            ON cgmp.parent = cs_merge_parent.id
+        WHERE
+            cs_merge_parent.repo_id = {repo_id}
+            AND cs_merge_parent.cs_id IN {cs_ids}
+        "
+    }
 }
 
 impl SqlCommitGraphStorage {
@@ -1417,4 +1474,35 @@ impl CommitGraphStorage for SqlCommitGraphStorage {
                 .collect(),
         )
     }
+
+    async fn count_children(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<usize> {
+        let count = CountChildren::query(&self.read_master_connection.conn, &self.repo_id, &cs_id)
+            .await?
+            .into_iter()
+            .next()
+            .map_or(0, |(count,)| count);
+        Ok(count.try_into()?)
+    }
+
+    async fn fetch_many_children(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, Vec<ChangesetId>>> {
+        let mut children: HashMap<ChangesetId, Vec<ChangesetId>> =
+            cs_ids.iter().map(|cs_id| (*cs_id, vec![])).collect();
+
+        if cs_ids.is_empty() {
+            return Ok(children);
+        }
+
+        for (parent, child) in
+            SelectManyChildren::query(&self.read_master_connection.conn, &self.repo_id, cs_ids)
+                .await?
+        {
+            children.entry(parent).or_default().push(child);
+        }
+
+        Ok(children)
+    }
 }
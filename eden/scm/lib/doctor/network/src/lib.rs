@@ -154,7 +154,8 @@ fn diagnose_http_error(config: &dyn Config, err: &HttpError) -> String {
         ),
         HttpError::InvalidCert(err, _) => maybe_append_help(format!("{}", err), "tlsauthhelp"),
         HttpError::MissingCerts(err) => maybe_append_help(format!("{}", err), "tlsauthhelp"),
-        HttpError::RequestFailure(HttpClientError::Curl(err)) => diagnose_curl_error(err),
+        HttpError::RequestFailure(HttpClientError::Curl(err))
+        | HttpError::RequestFailure(HttpClientError::Timeout(err)) => diagnose_curl_error(err),
 
         HttpError::Config(err) => err.to_string(),
         HttpError::RequestFailure(_) => format!("{}", err),
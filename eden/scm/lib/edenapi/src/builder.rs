@@ -19,6 +19,8 @@ use configmodel::ConfigExt;
 use http_client::Encoding;
 use http_client::HttpVersion;
 use http_client::MinTransferSpeed;
+use http_client::Request;
+use http_client::SslVersion;
 use lazy_static::lazy_static;
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
@@ -28,6 +30,7 @@ use rand::Rng;
 use url::Url;
 
 use crate::client::Client;
+use crate::client::EdenApiMetrics;
 use crate::errors::ConfigError;
 use crate::errors::EdenApiError;
 use crate::EdenApi;
@@ -40,6 +43,16 @@ lazy_static! {
         .collect();
 }
 
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Default for `edenapi.maxrequests`, used to avoid opening a connection for
+/// every batch of a large fetch at once.
+const DEFAULT_MAX_REQUESTS: usize = 16;
+
+/// Environment variable that, when set to a directory, enables dumping
+/// response bodies there. See `HttpClientBuilder::debug_dump_dir`.
+const DEBUG_DUMP_ENV: &str = "EDENAPI_DEBUG_DUMP";
+
 /// External function that constructs other kinds of `EdenApi` from config.
 static CUSTOM_BUILD_FUNCS: Lazy<
     RwLock<
@@ -139,14 +152,21 @@ pub struct HttpClientBuilder {
     max_commit_mutations: Option<usize>,
     max_commit_translate_id: Option<usize>,
     timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
     debug: bool,
     correlator: Option<String>,
     http_version: Option<HttpVersion>,
     log_dir: Option<PathBuf>,
+    debug_dump_dir: Option<PathBuf>,
     encoding: Option<Encoding>,
     min_transfer_speed: Option<MinTransferSpeed>,
+    max_recv_speed: Option<u64>,
+    min_tls_version: Option<SslVersion>,
     max_retry_per_request: usize,
+    retry_backoff: Option<Duration>,
+    allow_http1_fallback: bool,
     http_config: http_client::Config,
+    metrics: Option<Arc<dyn EdenApiMetrics>>,
 }
 
 impl HttpClientBuilder {
@@ -181,12 +201,12 @@ impl HttpClientBuilder {
             .transpose()
             .map_err(|e| ConfigError::Invalid("edenapi.headers".into(), e.into()))?
             .unwrap_or_default();
-        headers.insert(
-            "User-Agent".to_string(),
-            format!("EdenSCM/{}", version::VERSION),
-        );
+        let user_agent = get_config::<String>(config, "edenapi", "user-agent")?
+            .unwrap_or_else(|| format!("EdenSCM/{}", version::VERSION));
+        headers.insert("User-Agent".to_string(), user_agent);
 
-        let max_requests = get_config(config, "edenapi", "maxrequests")?;
+        let max_requests =
+            get_config(config, "edenapi", "maxrequests")?.or(Some(DEFAULT_MAX_REQUESTS));
         let max_files = get_config(config, "edenapi", "maxfiles")?;
         let max_trees = get_config(config, "edenapi", "maxtrees")?;
         let max_history = get_config(config, "edenapi", "maxhistory")?;
@@ -194,6 +214,8 @@ impl HttpClientBuilder {
         let max_commit_mutations = get_config(config, "edenapi", "maxcommitmutations")?;
         let max_commit_translate_id = get_config(config, "edenapi", "maxcommittranslateid")?;
         let timeout = get_config(config, "edenapi", "timeout")?.map(Duration::from_secs);
+        let connect_timeout =
+            get_config(config, "edenapi", "connect-timeout")?.map(Duration::from_secs);
         let debug = get_config(config, "edenapi", "debug")?.unwrap_or_default();
         let http_version =
             get_config(config, "edenapi", "http-version")?.unwrap_or_else(|| "2".to_string());
@@ -220,8 +242,18 @@ impl HttpClientBuilder {
                     grace_period: Duration::from_secs(low_speed_grace_period),
                 },
             );
+        let max_recv_speed =
+            get_config::<u64>(config, "edenapi", "max-recv-bytes-per-second")?.filter(|n| *n > 0);
+        let min_tls_version = get_config::<String>(config, "edenapi", "min-tls-version")?
+            .map(|v| parse_tls_version(&v))
+            .transpose()
+            .map_err(|e| ConfigError::Invalid("edenapi.min-tls-version".into(), e))?;
         let max_retry_per_request =
             get_config::<usize>(config, "edenapi", "max-retry-per-request")?.unwrap_or(3);
+        let retry_backoff =
+            get_config::<u64>(config, "edenapi", "retry-backoff-ms")?.map(Duration::from_millis);
+        let allow_http1_fallback =
+            get_config(config, "edenapi", "allow-http1-fallback")?.unwrap_or_default();
 
         let mut http_config = hg_http::http_config(config, &server_url)?;
         http_config.verbose_stats |= debug;
@@ -238,14 +270,21 @@ impl HttpClientBuilder {
             max_commit_mutations,
             max_commit_translate_id,
             timeout,
+            connect_timeout,
             debug,
             correlator: None,
             http_version,
             log_dir,
+            debug_dump_dir: None,
             encoding,
             min_transfer_speed,
+            max_recv_speed,
+            min_tls_version,
             max_retry_per_request,
+            retry_backoff,
+            allow_http1_fallback,
             http_config,
+            metrics: None,
         };
 
         tracing::debug!(?builder);
@@ -284,7 +323,21 @@ impl HttpClientBuilder {
         self
     }
 
-    /// Maximum number of concurrent HTTP requests allowed.
+    /// Set the User-Agent header sent with every request. When populated
+    /// from config, this is `edenapi.user-agent`, defaulting to
+    /// `EdenSCM/<version>`.
+    pub fn user_agent(self, user_agent: impl ToString) -> Self {
+        self.header("User-Agent", user_agent)
+    }
+
+    /// Maximum number of concurrent HTTP requests allowed. `None` or 0 means
+    /// no limit. When populated from config, defaults to 16.
+    ///
+    /// Large fetches are split into one request per batch of `max_files`,
+    /// `max_trees`, etc. keys, and all of them are handed to libcurl at
+    /// once. libcurl only runs this many at a time and starts the queued
+    /// ones as earlier ones complete, so smaller batches mean more, shorter
+    /// requests in the queue rather than more concurrent connections.
     pub fn max_requests(mut self, size: Option<usize>) -> Self {
         self.http_config.max_concurrent_requests = size;
         self
@@ -318,18 +371,45 @@ impl HttpClientBuilder {
         self
     }
 
+    /// Limit the download speed of each request, in bytes per second.
+    /// Concurrent requests are limited separately.
+    pub fn max_recv_speed(mut self, bytes_per_second: Option<u64>) -> Self {
+        self.max_recv_speed = bytes_per_second;
+        self
+    }
+
+    /// Minimum TLS version to negotiate with the server.
+    pub fn min_tls_version(mut self, version: SslVersion) -> Self {
+        self.min_tls_version = Some(version);
+        self
+    }
+
     /// Maximum number of retries per request.
     pub fn max_retry_per_request(mut self, max: usize) -> Self {
         self.max_retry_per_request = max;
         self
     }
 
+    /// Delay before the first retry of a failed request. The delay doubles
+    /// after each subsequent failure.
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = Some(backoff);
+        self
+    }
+
     /// Timeout for HTTP requests sent by the client.
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout = Some(timeout);
         self
     }
 
+    /// Timeout for establishing connections for HTTP requests sent by the
+    /// client.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
     /// Unique identifier that will be logged by both the client and server for
     /// every request, allowing log entries on both sides to be correlated. Also
     /// allows correlating multiple requests that were made by the same instance
@@ -345,6 +425,15 @@ impl HttpClientBuilder {
         self
     }
 
+    /// If enabled, a request that fails with an HTTP/2 protocol error is
+    /// retried once with HTTP/1.1, and the client uses HTTP/1.1 for all
+    /// subsequent requests. Disabled by default so that such errors are
+    /// surfaced immediately.
+    pub fn allow_http1_fallback(mut self, enable: bool) -> Self {
+        self.allow_http1_fallback = enable;
+        self
+    }
+
     /// If specified, the client will write a JSON version of every request
     /// it sends to the specified directory. This is primarily useful for
     /// debugging.
@@ -353,6 +442,19 @@ impl HttpClientBuilder {
         self
     }
 
+    /// If specified, the client will write the body of every successful
+    /// response it receives to the specified directory, both as received
+    /// and transcoded from CBOR to pretty JSON. Files are named after the
+    /// index of the response, counting from 0 for each client. The body is
+    /// written before it is decoded, so bodies that fail to decode are
+    /// captured too. Defaults to the `EDENAPI_DEBUG_DUMP` environment
+    /// variable, if set. This is only meant for debugging, as it buffers
+    /// each response in full before decoding it.
+    pub fn debug_dump_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.debug_dump_dir = Some(dir.as_ref().into());
+        self
+    }
+
     /// If enabled, convert the user's client certificate from PEM to PKCS#12
     /// prior to use. This is required on platforms that do not natively support
     /// PEM certificates, such as Windows.
@@ -360,6 +462,13 @@ impl HttpClientBuilder {
         self.http_config.convert_cert = enable;
         self
     }
+
+    /// Report the status, size and duration of every request to the given
+    /// metrics sink.
+    pub fn metrics(mut self, metrics: Arc<dyn EdenApiMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
 }
 
 fn get_config<T: FromConfigValue>(
@@ -397,15 +506,22 @@ pub(crate) struct Config {
     pub(crate) max_commit_mutations: Option<usize>,
     pub(crate) max_commit_translate_id: Option<usize>,
     pub(crate) timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
     #[allow(dead_code)]
     pub(crate) debug: bool,
     pub(crate) correlator: Option<String>,
     pub(crate) http_version: Option<HttpVersion>,
     pub(crate) log_dir: Option<PathBuf>,
+    pub(crate) debug_dump_dir: Option<PathBuf>,
     pub(crate) encoding: Option<Encoding>,
     pub(crate) min_transfer_speed: Option<MinTransferSpeed>,
+    pub(crate) max_recv_speed: Option<u64>,
+    pub(crate) min_tls_version: Option<SslVersion>,
     pub(crate) max_retry_per_request: usize,
+    pub(crate) retry_backoff: Duration,
+    pub(crate) allow_http1_fallback: bool,
     pub(crate) http_config: http_client::Config,
+    pub(crate) metrics: Option<Arc<dyn EdenApiMetrics>>,
 }
 
 impl TryFrom<HttpClientBuilder> for Config {
@@ -423,14 +539,21 @@ impl TryFrom<HttpClientBuilder> for Config {
             max_commit_mutations,
             max_commit_translate_id,
             timeout,
+            connect_timeout,
             debug,
             correlator,
             http_version,
             log_dir,
+            debug_dump_dir,
             encoding,
             min_transfer_speed,
+            max_recv_speed,
+            min_tls_version,
             max_retry_per_request,
+            retry_backoff,
+            allow_http1_fallback,
             http_config,
+            metrics,
         } = builder;
 
         // Check for missing required fields.
@@ -449,6 +572,41 @@ impl TryFrom<HttpClientBuilder> for Config {
         let max_trees = max_trees.filter(|n| *n > 0);
         let max_history = max_history.filter(|n| *n > 0);
 
+        let retry_backoff = retry_backoff.unwrap_or(DEFAULT_RETRY_BACKOFF);
+
+        let debug_dump_dir = debug_dump_dir.or_else(|| {
+            std::env::var_os(DEBUG_DUMP_ENV)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+        });
+
+        // A zero timeout would disable the timeout in libcurl, which is
+        // unlikely to be intended.
+        if timeout == Some(Duration::ZERO) {
+            return Err(ConfigError::Invalid(
+                "edenapi.timeout".into(),
+                anyhow!("timeout must be non-zero"),
+            )
+            .into());
+        }
+        if connect_timeout == Some(Duration::ZERO) {
+            return Err(ConfigError::Invalid(
+                "edenapi.connect-timeout".into(),
+                anyhow!("timeout must be non-zero"),
+            )
+            .into());
+        }
+
+        // Report unsupported TLS versions now rather than on every request.
+        if let Some(version) = min_tls_version {
+            Request::check_min_tls_version(version).map_err(|e| {
+                ConfigError::Invalid(
+                    "edenapi.min-tls-version".into(),
+                    anyhow!("{:?} is not supported: {}", version, e),
+                )
+            })?;
+        }
+
         Ok(Config {
             repo_name,
             server_url,
@@ -460,18 +618,41 @@ impl TryFrom<HttpClientBuilder> for Config {
             max_commit_mutations,
             max_commit_translate_id,
             timeout,
+            connect_timeout,
             debug,
             correlator,
             http_version,
             log_dir,
+            debug_dump_dir,
             encoding,
             min_transfer_speed,
+            max_recv_speed,
+            min_tls_version,
             max_retry_per_request,
+            retry_backoff,
+            allow_http1_fallback,
             http_config,
+            metrics,
         })
     }
 }
 
+/// Parse a TLS version such as "tls1.2".
+fn parse_tls_version(version: &str) -> Result<SslVersion, Error> {
+    Ok(match version {
+        "tls1.0" => SslVersion::Tlsv10,
+        "tls1.1" => SslVersion::Tlsv11,
+        "tls1.2" => SslVersion::Tlsv12,
+        "tls1.3" => SslVersion::Tlsv13,
+        _ => {
+            return Err(anyhow!(
+                "invalid TLS version {:?} (expected tls1.0, tls1.1, tls1.2 or tls1.3)",
+                version
+            ));
+        }
+    })
+}
+
 /// Parse headers from a JSON object.
 fn parse_headers(headers: impl AsRef<str>) -> Result<HashMap<String, String>, Error> {
     Ok(serde_json::from_str(headers.as_ref())
@@ -11,6 +11,9 @@ use std::fmt::Debug;
 use std::fs::create_dir_all;
 use std::future::ready;
 use std::num::NonZeroU64;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -86,11 +89,19 @@ use edenapi_types::UploadTreeRequest;
 use edenapi_types::UploadTreeResponse;
 use futures::future::BoxFuture;
 use futures::prelude::*;
+use futures::stream::BoxStream;
 use hg_http::http_client;
+use http::StatusCode;
 use http_client::AsyncResponse;
+use http_client::CborStream;
+use http_client::ClientCreds;
 use http_client::Encoding;
 use http_client::HttpClient;
+use http_client::HttpClientError;
+use http_client::HttpVersion;
 use http_client::Request;
+use http_client::RequestInfo;
+use http_client::RequestStats;
 use itertools::Itertools;
 use metrics::Counter;
 use metrics::EntranceGuard;
@@ -104,12 +115,15 @@ use serde::Serialize;
 use types::HgId;
 use types::Key;
 use url::Url;
+use uuid::Uuid;
 
 use crate::api::EdenApi;
 use crate::builder::Config;
+use crate::debug_dump::dump_response;
 use crate::errors::EdenApiError;
 use crate::response::Response;
 use crate::response::ResponseMeta;
+use crate::retryable::exponential_backoff;
 use crate::retryable::RetryableFileAttrs;
 use crate::retryable::RetryableFiles;
 use crate::retryable::RetryableStreamRequest;
@@ -160,6 +174,32 @@ mod paths {
     pub const BLAME: &str = "blame";
 }
 
+/// Sink for per-request metrics, configured via
+/// [`HttpClientBuilder::metrics`](crate::HttpClientBuilder::metrics).
+///
+/// `record_request` is called once for every HTTP request the client sends,
+/// including retries, as soon as the request completes. `status` is `None`
+/// if no response was received.
+pub trait EdenApiMetrics: Send + Sync {
+    fn record_request(
+        &self,
+        path: &str,
+        status: Option<StatusCode>,
+        bytes: usize,
+        duration: Duration,
+    );
+}
+
+impl Debug for dyn EdenApiMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EdenApiMetrics")
+    }
+}
+
+fn record_request_stats(metrics: &dyn EdenApiMetrics, req: &RequestInfo, stats: &RequestStats) {
+    metrics.record_request(req.url().path(), stats.status, stats.downloaded, stats.time);
+}
+
 #[derive(Clone)]
 pub struct Client {
     inner: Arc<ClientInner>,
@@ -170,6 +210,12 @@ pub struct ClientInner {
     client: HttpClient,
     tree_progress: Arc<AggregatingProgressBar>,
     file_progress: Arc<AggregatingProgressBar>,
+    /// Set once a request has failed with an HTTP/2 error and
+    /// `allow_http1_fallback` is enabled. All subsequent requests use HTTP/1.1.
+    http1_fallback: AtomicBool,
+    /// Index of the next response whose body is dumped, if a debug dump
+    /// directory is configured.
+    debug_dump_index: AtomicUsize,
 }
 
 static LOG_SERVER_INFO_ONCE: Once = Once::new();
@@ -177,12 +223,21 @@ static LOG_SERVER_INFO_ONCE: Once = Once::new();
 impl Client {
     /// Create an EdenAPI client with the given configuration.
     pub(crate) fn with_config(config: Config) -> Self {
-        let client = http_client("edenapi", config.http_config.clone());
+        let mut client = http_client("edenapi", config.http_config.clone());
+        if let Some(metrics) = config.metrics.clone() {
+            client
+                .event_listeners()
+                .on_request_stats(move |req, stats| {
+                    record_request_stats(metrics.as_ref(), req, stats)
+                });
+        }
         let inner = Arc::new(ClientInner {
             config,
             client,
             tree_progress: AggregatingProgressBar::new("fetching", "trees"),
             file_progress: AggregatingProgressBar::new("fetching", "files"),
+            http1_fallback: AtomicBool::new(false),
+            debug_dump_index: AtomicUsize::new(0),
         });
         Self { inner }
     }
@@ -191,6 +246,17 @@ impl Client {
         &self.inner.config
     }
 
+    /// Replace the TLS client certificate and key, for example after they
+    /// have been rotated on disk, without rebuilding the client.
+    ///
+    /// This can be called concurrently with requests being sent, from any
+    /// thread. Requests created after this call use the new credentials,
+    /// while requests that were already created, including those in
+    /// flight, keep using the credentials they were created with.
+    pub fn reload_creds(&self, creds: ClientCreds) {
+        self.inner.client.reload_creds(creds);
+    }
+
     fn repo_name(&self) -> &str {
         &self.config().repo_name
     }
@@ -233,7 +299,13 @@ impl Client {
             req.set_timeout(timeout);
         }
 
-        if let Some(http_version) = config.http_version {
+        if let Some(connect_timeout) = config.connect_timeout {
+            req.set_connect_timeout(connect_timeout);
+        }
+
+        if self.inner.http1_fallback.load(Ordering::Relaxed) {
+            req.set_http_version(HttpVersion::V11);
+        } else if let Some(http_version) = config.http_version {
             req.set_http_version(http_version);
         }
 
@@ -245,6 +317,20 @@ impl Client {
             req.set_min_transfer_speed(*mts);
         }
 
+        if let Some(max_recv_speed) = config.max_recv_speed {
+            req.set_max_recv_speed(max_recv_speed);
+        }
+
+        if let Some(min_tls_version) = config.min_tls_version {
+            req.set_min_tls_version(min_tls_version);
+        }
+
+        // Unique per request, so that a failure can be matched with the
+        // server's logs for that exact request.
+        let request_id = Uuid::new_v4().to_string();
+        tracing::debug!(%request_id, url = %req.ctx().url(), "Prepared request");
+        req.set_header("X-Request-Id", request_id);
+
         Ok(req)
     }
 
@@ -285,17 +371,30 @@ impl Client {
     /// from different HTTP responses may be arbitrarily interleaved.
     fn fetch_raw<T: DeserializeOwned + Send + 'static>(
         &self,
-        requests: Vec<Request>,
+        mut requests: Vec<Request>,
     ) -> Result<Response<T>, EdenApiError> {
+        let request_ids = requests
+            .iter_mut()
+            .map(|req| req.get_header_mut("X-Request-Id").cloned())
+            .collect::<Vec<_>>();
         let (responses, stats) = self.inner.client.send_async(requests)?;
 
         // Transform each response `Future` (which resolves when all of the HTTP
         // headers for that response have been received) into a `Stream` that
         // waits until all headers have been received and then starts yielding
         // entries. This allows multiplexing the streams using `select_all`.
-        let streams = responses.into_iter().map(|fut| {
+        let responses = responses.into_iter().zip(request_ids);
+        let streams = responses.map(|(fut, request_id)| {
+            // Indices are assigned in the order the requests were given, not
+            // the order the responses arrive, so that dumps are named
+            // deterministically.
+            let debug_dump = self.config().debug_dump_dir.clone().map(|dir| {
+                let index = self.inner.debug_dump_index.fetch_add(1, Ordering::Relaxed);
+                (dir, index)
+            });
+
             stream::once(async move {
-                let res = raise_for_status(fut.await?).await?;
+                let res = raise_for_status(fut.await?, request_id).await?;
                 tracing::debug!("{:?}", ResponseMeta::from(&res));
 
                 LOG_SERVER_INFO_ONCE.call_once(|| {
@@ -303,8 +402,27 @@ impl Client {
                     tracing::debug!(target: "mononoke_info", mononoke_host=res_meta.mononoke_host.unwrap_or_default());
                 });
 
-                Ok::<_, EdenApiError>(res.into_body().cbor::<T>().err_into())
+                let body = res.into_body();
+                let entries: BoxStream<'static, Result<T, EdenApiError>> = match debug_dump {
+                    None => body.cbor::<T>().err_into().boxed(),
+                    Some((dir, index)) => {
+                        // Dump the whole body before decoding it, so that
+                        // it is captured even if it can't be decoded.
+                        let bytes: Vec<u8> = body.decoded().try_concat().await?;
+                        let bytes = async_runtime::spawn_blocking(move || {
+                            if let Err(e) = dump_response(&dir, index, &bytes) {
+                                tracing::warn!("Failed to dump response {}: {:?}", index, &e);
+                            }
+                            bytes
+                        })
+                        .await
+                        .map_err(|e| EdenApiError::Other(e.into()))?;
+                        let bytes = stream::once(future::ready(Ok::<_, HttpClientError>(bytes)));
+                        CborStream::new(bytes).err_into().boxed()
+                    }
+                };
 
+                Ok::<_, EdenApiError>(entries)
             })
             .try_flatten()
             .boxed()
@@ -587,7 +705,35 @@ impl Client {
         func: impl Fn(&'t Self) -> BoxFuture<'t, Result<T, EdenApiError>>,
     ) -> Result<T, EdenApiError> {
         let retry_count = self.inner.config.max_retry_per_request;
-        with_retry(retry_count, || func(self)).await
+        let backoff = self.inner.config.retry_backoff;
+        let func = &func;
+        with_retry(retry_count, backoff, || {
+            async move {
+                match func(self).await {
+                    Err(e) if self.fall_back_to_http1(&e) => func(self).await,
+                    result => result,
+                }
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    /// Switch the client to HTTP/1.1 if `error` is an HTTP/2 error and
+    /// `allow_http1_fallback` is enabled. Returns true if the caller should
+    /// retry the failed request, which only happens the first time.
+    pub(crate) fn fall_back_to_http1(&self, error: &EdenApiError) -> bool {
+        if !self.config().allow_http1_fallback
+            || matches!(self.config().http_version, Some(HttpVersion::V11))
+            || !is_http2_error(error)
+        {
+            return false;
+        }
+        if self.inner.http1_fallback.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+        tracing::debug!("Falling back to HTTP/1.1 after HTTP/2 error: {}", error);
+        true
     }
 }
 
@@ -602,8 +748,9 @@ impl EdenApi for Client {
 
         tracing::info!("Sending health check request: {}", &url);
 
-        let req = self.configure_request(self.inner.client.get(url))?;
-        let res = raise_for_status(req.send_async().await?).await?;
+        let mut req = self.configure_request(self.inner.client.get(url))?;
+        let request_id = req.get_header_mut("X-Request-Id").cloned();
+        let res = raise_for_status(req.send_async().await?, request_id).await?;
 
         Ok(ResponseMeta::from(&res))
     }
@@ -611,8 +758,9 @@ impl EdenApi for Client {
     async fn capabilities(&self) -> Result<Vec<String>, EdenApiError> {
         tracing::info!("Requesting capabilities for repo {}", &self.repo_name());
         let url = self.build_url("capabilities")?;
-        let req = self.configure_request(self.inner.client.get(url))?;
-        let res = raise_for_status(req.send_async().await?).await?;
+        let mut req = self.configure_request(self.inner.client.get(url))?;
+        let request_id = req.get_header_mut("X-Request-Id").cloned();
+        let res = raise_for_status(req.send_async().await?, request_id).await?;
         let body: Vec<u8> = res.into_body().decoded().try_concat().await?;
         let caps = serde_json::from_slice(&body)
             .map_err(|e| EdenApiError::ParseResponse(e.to_string()))?;
@@ -1392,7 +1540,13 @@ fn split_into_batches<T>(
     }
 }
 
-async fn raise_for_status(res: AsyncResponse) -> Result<AsyncResponse, EdenApiError> {
+/// Turn an error status into an `EdenApiError::HttpError`. The id sent in
+/// the request's X-Request-Id header, if any, is added to the message so
+/// that the failure can be found in the server's logs.
+async fn raise_for_status(
+    res: AsyncResponse,
+    request_id: Option<String>,
+) -> Result<AsyncResponse, EdenApiError> {
     let status = res.status();
     if status.as_u16() < 400 {
         return Ok(res);
@@ -1409,6 +1563,9 @@ async fn raise_for_status(res: AsyncResponse) -> Result<AsyncResponse, EdenApiEr
         message.truncate(MAX_ERROR_MSG_LEN);
         message.push_str("... (truncated)")
     }
+    if let Some(request_id) = request_id {
+        message.push_str(&format!(" (X-Request-Id: {})", request_id));
+    }
 
     let headers = head.headers().clone();
     Err(EdenApiError::HttpError {
@@ -1419,8 +1576,16 @@ async fn raise_for_status(res: AsyncResponse) -> Result<AsyncResponse, EdenApiEr
     })
 }
 
+fn is_http2_error(error: &EdenApiError) -> bool {
+    match error {
+        EdenApiError::Http(e) => e.is_http2_error(),
+        _ => false,
+    }
+}
+
 async fn with_retry<'t, T>(
     max_retry_count: usize,
+    backoff: Duration,
     func: impl Fn() -> BoxFuture<'t, Result<T, EdenApiError>>,
 ) -> Result<T, EdenApiError> {
     let mut attempt = 0usize;
@@ -1436,7 +1601,7 @@ async fn with_retry<'t, T>(
                     return result;
                 }
                 tracing::warn!("Retrying http error {:?}", error);
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                tokio::time::sleep(exponential_backoff(backoff, attempt)).await;
             }
         }
         attempt += 1;
@@ -1445,9 +1610,275 @@ async fn with_retry<'t, T>(
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
     use anyhow::Result;
+    use edenapi_types::FileEntry;
+    use edenapi_types::Parents;
+    use mockito::Matcher;
+    use types::RepoPathBuf;
 
+    use super::*;
     use crate::builder::HttpClientBuilder;
+    use crate::Capabilities;
+
+    fn http_error(status: StatusCode) -> EdenApiError {
+        EdenApiError::HttpError {
+            status,
+            message: "fake error".to_string(),
+            headers: Default::default(),
+            url: "https://example.com/repo/files2".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_zero_timeout_is_rejected() -> Result<()> {
+        let builder = || {
+            HttpClientBuilder::new()
+                .repo_name("repo")
+                .server_url("https://example.com".parse().unwrap())
+        };
+
+        assert!(builder().timeout(Duration::ZERO).build().is_err());
+        assert!(builder().connect_timeout(Duration::ZERO).build().is_err());
+        builder()
+            .timeout(Duration::from_secs(10))
+            .connect_timeout(Duration::from_secs(1))
+            .build()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_tls_version() -> Result<()> {
+        HttpClientBuilder::new()
+            .repo_name("repo")
+            .server_url("https://example.com".parse()?)
+            .min_tls_version(http_client::SslVersion::Tlsv12)
+            .build()?;
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_with_retry() -> Result<()> {
+        let backoff = Duration::from_secs(1);
+
+        // Fail twice, then succeed.
+        let attempts = AtomicUsize::new(0);
+        let start = tokio::time::Instant::now();
+        let result = with_retry(3, backoff, || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt < 2 {
+                    Err(http_error(StatusCode::SERVICE_UNAVAILABLE))
+                } else {
+                    Ok(attempt)
+                }
+            }
+            .boxed()
+        })
+        .await?;
+        assert_eq!(result, 2);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(start.elapsed(), Duration::from_secs(1 + 2));
+
+        // The last error is returned once the retry budget is exhausted.
+        let attempts = AtomicUsize::new(0);
+        let result: Result<(), _> = with_retry(2, backoff, || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            future::err(http_error(StatusCode::BAD_GATEWAY)).boxed()
+        })
+        .await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("502"));
+        assert!(message.contains("https://example.com/repo/files2"));
+
+        // Non-retryable errors are not retried.
+        let attempts = AtomicUsize::new(0);
+        let result: Result<(), _> = with_retry(3, backoff, || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            future::err(http_error(StatusCode::NOT_FOUND)).boxed()
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_files_retry() -> Result<()> {
+        let keys = ["first", "second"]
+            .into_iter()
+            .map(|path| {
+                let path = RepoPathBuf::from_string(path.to_string())?;
+                anyhow::Ok(Key::new(path, *HgId::null_id()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let entry = |key: &Key| {
+            let response = FileResponse {
+                key: key.clone(),
+                result: Ok(FileEntry::new(key.clone(), Parents::None)),
+            };
+            serde_cbor::to_vec(&response.to_wire())
+        };
+        let first = entry(&keys[0])?;
+        let second = entry(&keys[1])?;
+
+        // Retries must only ask for the key that wasn't received yet.
+        let retry_body = serde_cbor::to_vec(
+            &FileRequest {
+                keys: vec![keys[1].clone()],
+                reqs: vec![],
+            }
+            .to_wire(),
+        )?;
+        let retry_body = Matcher::Exact(String::from_utf8_lossy(&retry_body).into_owned());
+        let unavailable = mockito::mock("POST", "/repo/files2")
+            .match_body(retry_body.clone())
+            .with_status(503)
+            .expect(2)
+            .create();
+        let retried = mockito::mock("POST", "/repo/files2")
+            .match_body(retry_body)
+            .with_status(200)
+            .with_body(&second)
+            .expect(1)
+            .create();
+        // The first response is cut off in the middle of the second entry.
+        // Any other request for both keys would hit this mock again.
+        let truncated = mockito::mock("POST", "/repo/files2")
+            .with_status(200)
+            .with_body([&first[..], &second[..second.len() / 2]].concat())
+            .expect(1)
+            .create();
+
+        let client = HttpClientBuilder::new()
+            .repo_name("repo")
+            .server_url(mockito::server_url().parse()?)
+            .http_version(HttpVersion::V11)
+            .max_retry_per_request(3)
+            .retry_backoff(Duration::from_millis(1))
+            .build()?;
+
+        let Response { entries, .. } = client.files(keys.clone()).await?;
+        let received = entries
+            .map_ok(|response| response.key)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(received, keys);
+
+        truncated.assert();
+        unavailable.assert();
+        retried.assert();
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_headers() -> Result<()> {
+        let client = HttpClientBuilder::new()
+            .repo_name("repo")
+            .server_url("https://example.com".parse()?)
+            .user_agent("test-client/1.0")
+            .build()?;
+        let url: Url = "https://example.com/repo/files2".parse()?;
+
+        let mut req1 = client.configure_request(client.inner.client.post(url.clone()))?;
+        let mut req2 = client.configure_request(client.inner.client.post(url))?;
+
+        assert_eq!(
+            req1.get_header_mut("User-Agent").cloned(),
+            Some("test-client/1.0".to_string())
+        );
+
+        // Every request gets its own id.
+        let id1 = req1.get_header_mut("X-Request-Id").cloned().unwrap();
+        let id2 = req2.get_header_mut("X-Request-Id").cloned().unwrap();
+        assert!(Uuid::parse_str(&id1).is_ok());
+        assert_ne!(id1, id2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_error_request_id() -> Result<()> {
+        let mock = mockito::mock("GET", "/health_check")
+            .match_header("X-Request-Id", Matcher::Any)
+            .with_status(503)
+            .create();
+
+        let client = HttpClientBuilder::new()
+            .repo_name("repo")
+            .server_url(mockito::server_url().parse()?)
+            .http_version(HttpVersion::V11)
+            .build()?;
+
+        // The id sent with the failed request is part of the error.
+        let message = match client.health().await {
+            Err(EdenApiError::HttpError { message, .. }) => message,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("health check should have failed"),
+        };
+        let request_id = message
+            .rsplit_once("(X-Request-Id: ")
+            .and_then(|(_, rest)| rest.strip_suffix(')'))
+            .unwrap();
+        assert!(Uuid::parse_str(request_id).is_ok());
+
+        mock.assert();
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload_creds() -> Result<()> {
+        let client = HttpClientBuilder::new()
+            .repo_name("repo")
+            .server_url("https://example.com".parse()?)
+            .build()?;
+
+        let creds = ClientCreds {
+            cert_path: Some("rotated.pem".into()),
+            key_path: Some("rotated.key".into()),
+        };
+        client.clone().reload_creds(creds.clone());
+        assert_eq!(client.inner.client.creds(), creds);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_http1_fallback() -> Result<()> {
+        let h2_error = || EdenApiError::Http(curl::Error::new(16 /* CURLE_HTTP2 */).into());
+        let builder = || {
+            HttpClientBuilder::new()
+                .repo_name("repo")
+                .server_url("https://example.com".parse().unwrap())
+        };
+
+        // Disabled by default.
+        let client = builder().build()?;
+        assert!(!client.fall_back_to_http1(&h2_error()));
+
+        // Only HTTP/2 errors trigger the fallback, and only once.
+        let client = builder().allow_http1_fallback(true).build()?;
+        assert!(!client.fall_back_to_http1(&http_error(StatusCode::BAD_GATEWAY)));
+        assert!(client.fall_back_to_http1(&h2_error()));
+        assert!(!client.fall_back_to_http1(&h2_error()));
+
+        // Clients already using HTTP/1.1 have nothing to fall back to.
+        let client = builder()
+            .allow_http1_fallback(true)
+            .http_version(HttpVersion::V11)
+            .build()?;
+        assert!(!client.fall_back_to_http1(&h2_error()));
+
+        Ok(())
+    }
 
     #[test]
     fn test_url_escaping() -> Result<()> {
@@ -1467,4 +1898,104 @@ mod tests {
 
         Ok(())
     }
+
+    struct FakeCapabilities(Result<Vec<&'static str>, StatusCode>);
+
+    #[async_trait]
+    impl EdenApi for FakeCapabilities {
+        async fn capabilities(&self) -> Result<Vec<String>, EdenApiError> {
+            match &self.0 {
+                Ok(caps) => Ok(caps.iter().map(|c| c.to_string()).collect()),
+                Err(status) => Err(http_error(*status)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_capabilities() -> Result<()> {
+        let caps = FakeCapabilities(Ok(vec!["segmented-changelog", "future-feature"]))
+            .server_capabilities()
+            .await?;
+        assert!(caps.segmented_changelog());
+        assert!(caps.has("future-feature"));
+        assert!(!caps.has("unknown"));
+
+        let caps = FakeCapabilities(Ok(vec![])).server_capabilities().await?;
+        assert!(!caps.segmented_changelog());
+
+        // Servers without the endpoint get the minimal capability set.
+        let caps = FakeCapabilities(Err(StatusCode::NOT_FOUND))
+            .server_capabilities()
+            .await?;
+        assert_eq!(caps, Capabilities::minimal());
+        assert!(!caps.segmented_changelog());
+
+        // Other errors are still reported.
+        let result = FakeCapabilities(Err(StatusCode::INTERNAL_SERVER_ERROR))
+            .server_capabilities()
+            .await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        records: parking_lot::Mutex<Vec<(String, Option<StatusCode>, usize, Duration)>>,
+    }
+
+    impl EdenApiMetrics for RecordingMetrics {
+        fn record_request(
+            &self,
+            path: &str,
+            status: Option<StatusCode>,
+            bytes: usize,
+            duration: Duration,
+        ) {
+            self.records
+                .lock()
+                .push((path.to_string(), status, bytes, duration));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_metrics() -> Result<()> {
+        let mock = mockito::mock("POST", "/repo/history")
+            .with_status(200)
+            .expect(3)
+            .create();
+
+        let metrics = Arc::new(RecordingMetrics::default());
+        let client = HttpClientBuilder::new()
+            .repo_name("repo")
+            .server_url(mockito::server_url().parse()?)
+            .http_version(HttpVersion::V11)
+            .max_history(Some(1))
+            .metrics(metrics.clone())
+            .build()?;
+
+        // With one key per request, this sends three requests.
+        let keys = ["a", "b", "c"]
+            .into_iter()
+            .map(|path| {
+                let path = RepoPathBuf::from_string(path.to_string())?;
+                anyhow::Ok(Key::new(path, *HgId::null_id()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let Response { entries, stats } = client.history(keys, None).await?;
+        assert!(entries.try_collect::<Vec<_>>().await?.is_empty());
+        stats.await?;
+
+        let records = metrics.records.lock();
+        assert_eq!(records.len(), 3);
+        for (path, status, bytes, _duration) in records.iter() {
+            assert_eq!(path, "/repo/history");
+            assert_eq!(*status, Some(StatusCode::OK));
+            assert_eq!(*bytes, 0);
+        }
+
+        mock.assert();
+
+        Ok(())
+    }
 }
@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Dumping of response bodies to disk, to help debug protocol issues.
+//!
+//! Each response body is written as received (after Content-Encoding
+//! decoding) to `response_<index>.cbor`, and transcoded into a pretty JSON
+//! array of its CBOR values in `response_<index>.json`.
+
+use std::fs::create_dir_all;
+use std::io;
+use std::path::Path;
+
+use serde_cbor::Value as CborValue;
+use serde_json::Map;
+use serde_json::Number;
+use serde_json::Value as JsonValue;
+
+/// Write the given response body to `dir`. The raw body is always written,
+/// so that bodies that are not valid CBOR are captured too. If the body is
+/// not valid CBOR, the JSON file contains the values before the invalid
+/// data and a warning is logged.
+pub(crate) fn dump_response(dir: &Path, index: usize, body: &[u8]) -> io::Result<()> {
+    create_dir_all(dir)?;
+    std::fs::write(dir.join(format!("response_{}.cbor", index)), body)?;
+
+    let mut values = Vec::new();
+    for value in serde_cbor::Deserializer::from_slice(body).into_iter::<CborValue>() {
+        match value {
+            Ok(value) => values.push(cbor_to_json(value)),
+            Err(e) => {
+                tracing::warn!(
+                    "Response {} is not valid CBOR, only {} value(s) transcoded: {}",
+                    index,
+                    values.len(),
+                    e
+                );
+                break;
+            }
+        }
+    }
+    let json = serde_json::to_string_pretty(&values)?;
+    std::fs::write(dir.join(format!("response_{}.json", index)), json)
+}
+
+/// Convert a CBOR value into JSON. Byte strings, which are used for hashes,
+/// are written as hex strings, and map keys that are not strings are written
+/// as their JSON representation.
+fn cbor_to_json(value: CborValue) -> JsonValue {
+    match value {
+        CborValue::Null => JsonValue::Null,
+        CborValue::Bool(b) => JsonValue::Bool(b),
+        CborValue::Integer(i) => match (i64::try_from(i), u64::try_from(i)) {
+            (Ok(i), _) => JsonValue::Number(i.into()),
+            (_, Ok(u)) => JsonValue::Number(u.into()),
+            _ => JsonValue::String(i.to_string()),
+        },
+        CborValue::Float(f) => Number::from_f64(f).map_or(JsonValue::Null, JsonValue::Number),
+        CborValue::Bytes(bytes) => JsonValue::String(to_hex(&bytes)),
+        CborValue::Text(text) => JsonValue::String(text),
+        CborValue::Array(values) => {
+            JsonValue::Array(values.into_iter().map(cbor_to_json).collect())
+        }
+        CborValue::Map(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| {
+                    let key = match cbor_to_json(k) {
+                        JsonValue::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    (key, cbor_to_json(v))
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        CborValue::Tag(_, value) => cbor_to_json(*value),
+        _ => JsonValue::Null,
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn test_dump_response() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let mut body = serde_cbor::to_vec(&BTreeMap::from([(0, "a"), (1, "b")]))?;
+        body.extend(serde_cbor::to_vec(&CborValue::Bytes(vec![0xab, 0x01]))?);
+        dump_response(dir.path(), 3, &body)?;
+
+        assert_eq!(std::fs::read(dir.path().join("response_3.cbor"))?, body);
+        let json: JsonValue =
+            serde_json::from_slice(&std::fs::read(dir.path().join("response_3.json"))?)?;
+        assert_eq!(json, serde_json::json!([{"0": "a", "1": "b"}, "ab01"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_invalid_response() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let mut body = serde_cbor::to_vec(&"valid")?;
+        body.extend([0xff, 0xff]);
+        dump_response(dir.path(), 0, &body)?;
+
+        // The raw body is kept even though it can't be fully decoded.
+        assert_eq!(std::fs::read(dir.path().join("response_0.cbor"))?, body);
+        let json: JsonValue =
+            serde_json::from_slice(&std::fs::read(dir.path().join("response_0.json"))?)?;
+        assert_eq!(json, serde_json::json!(["valid"]));
+
+        Ok(())
+    }
+}
@@ -7,17 +7,21 @@
 
 mod builder;
 mod client;
+mod debug_dump;
 mod response;
 mod retryable;
 
 // Re-export for convenience.
 pub use configmodel;
 pub use edenapi_trait::api;
+pub use edenapi_trait::capabilities;
 pub use edenapi_trait::errors;
+pub use edenapi_trait::Capabilities;
 pub use edenapi_trait::Entries;
 pub use edenapi_trait::Response;
 pub use edenapi_trait::ResponseMeta;
 pub use edenapi_types as types;
+pub use http_client::ClientCreds;
 pub use http_client::Stats;
 
 pub use crate::api::EdenApi;
@@ -25,6 +29,7 @@ pub use crate::builder::Builder;
 pub use crate::builder::HttpClientBuilder;
 pub use crate::builder::DEFAULT_CORRELATOR;
 pub use crate::client::Client;
+pub use crate::client::EdenApiMetrics;
 pub use crate::errors::ConfigError;
 pub use crate::errors::EdenApiError;
 pub use crate::response::BlockingResponse;
@@ -37,9 +37,10 @@ pub(crate) trait RetryableStreamRequest: Sized + Sync + Send + 'static {
         error: &EdenApiError,
         attempt: usize,
         max: usize,
+        backoff: Duration,
     ) -> Option<Duration> {
         if error.is_retryable() && attempt < max {
-            Some(Duration::from_secs(attempt as u64 + 1))
+            Some(exponential_backoff(backoff, attempt))
         } else {
             None
         }
@@ -68,6 +69,7 @@ pub(crate) trait RetryableStreamRequest: Sized + Sync + Send + 'static {
 
             async move {
                 let max_attempts = client.config().max_retry_per_request;
+                let backoff = client.config().retry_backoff;
                 loop {
                     // Ideally we'd return None when we hit the final error, but we need to use
                     // that time to return the error, so instead we return None on this next
@@ -106,17 +108,23 @@ pub(crate) trait RetryableStreamRequest: Sized + Sync + Send + 'static {
                         Err(e) => e,
                     };
 
-                    let retry_after =
-                        match state
-                            .request
-                            .retry_after(&error, state.attempt, max_attempts)
-                        {
-                            Some(d) => d,
-                            None => {
-                                state.attempt = max_attempts + 1;
-                                return Some((Err(error), state));
-                            }
-                        };
+                    if client.fall_back_to_http1(&error) {
+                        state.entries = None;
+                        continue;
+                    }
+
+                    let retry_after = match state.request.retry_after(
+                        &error,
+                        state.attempt,
+                        max_attempts,
+                        backoff,
+                    ) {
+                        Some(d) => d,
+                        None => {
+                            state.attempt = max_attempts + 1;
+                            return Some((Err(error), state));
+                        }
+                    };
                     state.attempt += 1;
                     state.entries = None;
 
@@ -145,6 +153,12 @@ pub(crate) trait RetryableStreamRequest: Sized + Sync + Send + 'static {
     }
 }
 
+/// Delay before retrying after `attempt` previous retries. The delay starts
+/// at `backoff` and doubles on each attempt.
+pub(crate) fn exponential_backoff(backoff: Duration, attempt: usize) -> Duration {
+    backoff.saturating_mul(1 << attempt.min(31))
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
@@ -249,4 +263,14 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let backoff = Duration::from_millis(100);
+        assert_eq!(exponential_backoff(backoff, 0), Duration::from_millis(100));
+        assert_eq!(exponential_backoff(backoff, 1), Duration::from_millis(200));
+        assert_eq!(exponential_backoff(backoff, 3), Duration::from_millis(800));
+        assert_eq!(exponential_backoff(backoff, 100), backoff * (1 << 31));
+        assert_eq!(exponential_backoff(Duration::ZERO, 5), Duration::ZERO);
+    }
 }
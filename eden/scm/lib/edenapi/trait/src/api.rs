@@ -47,10 +47,12 @@ use edenapi_types::UploadToken;
 use edenapi_types::UploadTokensResponse;
 use edenapi_types::UploadTreeEntry;
 use edenapi_types::UploadTreeResponse;
+use http::StatusCode;
 use minibytes::Bytes;
 use types::HgId;
 use types::Key;
 
+use crate::capabilities::Capabilities;
 use crate::errors::EdenApiError;
 use crate::response::Response;
 use crate::response::ResponseMeta;
@@ -71,6 +73,20 @@ pub trait EdenApi: Send + Sync + 'static {
         Err(EdenApiError::NotSupported)
     }
 
+    /// Like `capabilities`, but parsed into [`Capabilities`]. Servers that do
+    /// not have the `capabilities` endpoint (i.e. respond 404) are treated as
+    /// supporting the minimal capability set rather than as an error.
+    async fn server_capabilities(&self) -> Result<Capabilities, EdenApiError> {
+        match self.capabilities().await {
+            Ok(names) => Ok(names.into_iter().collect()),
+            Err(EdenApiError::HttpError {
+                status: StatusCode::NOT_FOUND,
+                ..
+            }) => Ok(Capabilities::minimal()),
+            Err(e) => Err(e),
+        }
+    }
+
     async fn files(&self, keys: Vec<Key>) -> Result<Response<FileResponse>, EdenApiError> {
         let _ = keys;
         Err(EdenApiError::NotSupported)
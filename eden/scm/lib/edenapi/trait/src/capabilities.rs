@@ -0,0 +1,53 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeSet;
+
+/// Capability advertising that the server provides segmented changelog
+/// endpoints (`commit/location_to_hash`, `commit/hash_to_location`, `clone`,
+/// etc.) for the repo.
+pub const CAP_SEGMENTED_CHANGELOG: &str = "segmented-changelog";
+
+/// Optional features supported by the server for a repo, as reported by the
+/// `capabilities` endpoint.
+///
+/// Servers that predate the endpoint are represented by
+/// [`Capabilities::minimal`], which has no optional features.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    names: BTreeSet<String>,
+}
+
+impl Capabilities {
+    /// The capabilities of a server that does not report any.
+    pub fn minimal() -> Self {
+        Self::default()
+    }
+
+    /// Whether the server reported the named capability.
+    pub fn has(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    /// Whether the server supports segmented changelog endpoints.
+    pub fn segmented_changelog(&self) -> bool {
+        self.has(CAP_SEGMENTED_CHANGELOG)
+    }
+
+    /// The names of all capabilities reported by the server.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for Capabilities {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        Self {
+            names: iter.into_iter().map(Into::into).collect(),
+        }
+    }
+}
@@ -6,6 +6,7 @@
  */
 
 pub mod api;
+pub mod capabilities;
 pub mod errors;
 pub mod response;
 
@@ -14,6 +15,7 @@ pub use configmodel;
 pub use edenapi_types as types;
 
 pub use crate::api::EdenApi;
+pub use crate::capabilities::Capabilities;
 pub use crate::errors::ConfigError;
 pub use crate::errors::EdenApiError;
 pub use crate::response::Entries;
@@ -20,6 +20,7 @@ use clientinfo::ClientInfo;
 use configmodel::ConfigExt;
 use hg_metrics::increment_counter;
 use http_client::HttpClient;
+use http_client::ProxyAuth;
 use http_client::Request;
 use http_client::Stats;
 use once_cell::sync::Lazy;
@@ -86,6 +87,28 @@ pub fn http_config(
                 .into_iter(),
         ),
         verbose: config.get_or_default("http", "verbose").unwrap_or(false),
+        proxy: config
+            .get_nonempty_opt("http_proxy", "host")
+            .unwrap_or_default(),
+        proxy_auth: config
+            .get_nonempty_opt::<String>("http_proxy", "user")
+            .unwrap_or_default()
+            .map(|username| ProxyAuth {
+                username,
+                password: config
+                    .get_nonempty_opt("http_proxy", "passwd")
+                    .unwrap_or_default(),
+            }),
+        no_proxy: if config
+            .get_or_default("http_proxy", "always")
+            .unwrap_or(false)
+        {
+            Vec::new()
+        } else {
+            config
+                .get_or("http_proxy", "no", Vec::new)
+                .unwrap_or_else(|_| vec![])
+        },
         ..Default::default()
     };
 
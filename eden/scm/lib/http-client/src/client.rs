@@ -8,9 +8,11 @@
 use std::collections::HashSet;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::Arc;
 
 use curl::easy::Easy2;
 use futures::prelude::*;
+use parking_lot::RwLock;
 use url::Url;
 
 use crate::driver::MultiDriver;
@@ -25,10 +27,12 @@ use crate::progress::Progress;
 use crate::receiver::ChannelReceiver;
 use crate::receiver::Receiver;
 use crate::request::Method;
+use crate::request::ProxyAuth;
 use crate::request::Request;
 use crate::request::StreamRequest;
 use crate::response::AsyncResponse;
 use crate::response::Response;
+use crate::stats::RequestStats;
 use crate::stats::Stats;
 
 pub type ResponseFuture =
@@ -50,6 +54,15 @@ pub struct HttpClient {
     pool: Pool,
     event_listeners: HttpClientEventListeners,
     config: Config,
+    creds: Arc<RwLock<ClientCreds>>,
+}
+
+/// Paths to the client certificate and private key used for TLS mutual
+/// authentication. See [`HttpClient::reload_creds`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientCreds {
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
 }
 
 #[derive(Clone, Debug)]
@@ -66,6 +79,15 @@ pub struct Config {
     pub unix_socket_path: Option<String>,
     pub verbose: bool,
     pub verbose_stats: bool,
+
+    /// HTTP proxy to use, for example "myproxy:8000". If unset, libcurl
+    /// uses the `http_proxy`, `https_proxy` and `no_proxy` environment
+    /// variables. An explicitly configured proxy takes precedence over the
+    /// environment.
+    pub proxy: Option<String>,
+    pub proxy_auth: Option<ProxyAuth>,
+    /// Host names that should bypass the proxy.
+    pub no_proxy: Vec<String>,
 }
 
 impl Default for Config {
@@ -83,6 +105,10 @@ impl Default for Config {
             unix_socket_path: None,
             verbose: false,
             verbose_stats: false,
+
+            proxy: None,
+            proxy_auth: None,
+            no_proxy: Vec::new(),
         }
     }
 }
@@ -93,13 +119,36 @@ impl HttpClient {
     }
 
     pub fn from_config(config: Config) -> Self {
+        let creds = ClientCreds {
+            cert_path: config.cert_path.clone(),
+            key_path: config.key_path.clone(),
+        };
         Self {
             config,
             pool: Pool::new(),
             event_listeners: Default::default(),
+            creds: Arc::new(RwLock::new(creds)),
         }
     }
 
+    /// The client credentials that are applied to new requests.
+    pub fn creds(&self) -> ClientCreds {
+        self.creds.read().clone()
+    }
+
+    /// Replace the client credentials, for example after the certificate
+    /// has been rotated on disk, without rebuilding the client.
+    ///
+    /// This is safe to call concurrently with creating and sending requests,
+    /// and the certificate and key are always replaced together. The new
+    /// credentials are shared by all clones of this client and apply to
+    /// requests created after this call. Requests that were already created,
+    /// including those in flight, keep the credentials they were created
+    /// with.
+    pub fn reload_creds(&self, creds: ClientCreds) {
+        *self.creds.write() = creds;
+    }
+
     pub fn verbose_stats(mut self, verbose: bool) -> Self {
         self.config.verbose_stats = verbose;
         self
@@ -163,6 +212,7 @@ impl HttpClient {
             }
             let res = res
                 .map_err(|(mut easy, e)| {
+                    self.report_request_stats(&mut easy);
                     let ctx = easy.get_mut().request_context_mut();
                     let info = ctx.info().clone();
                     ctx.event_listeners().trigger_failure(&info);
@@ -170,6 +220,7 @@ impl HttpClient {
                     e.into()
                 })
                 .and_then(|mut easy| {
+                    self.report_request_stats(&mut easy);
                     let ctx = easy.get_mut().request_context_mut();
                     let info = ctx.info().clone();
                     ctx.event_listeners().trigger_success(&info);
@@ -329,6 +380,17 @@ impl HttpClient {
         self
     }
 
+    /// Report the statistics of a completed request to the
+    /// `request_stats` event listeners.
+    fn report_request_stats<H: HandlerExt>(&self, easy: &mut Easy2<H>) {
+        if !self.event_listeners.should_trigger_request_stats() {
+            return;
+        }
+        let stats = RequestStats::from_handle(easy);
+        self.event_listeners
+            .trigger_request_stats(easy.get_ref().request_context().info(), &stats);
+    }
+
     /// Callback for `MultiDriver::perform` when working with
     /// a `Streaming` handler. Reports the result of the
     /// completed request to the handler's `Receiver`.
@@ -342,6 +404,7 @@ impl HttpClient {
         // therein.
         let (mut easy, res) = match res {
             Ok(mut easy) => {
+                self.report_request_stats(&mut easy);
                 let ctx = easy.get_mut().request_context_mut();
                 let info = ctx.info().clone();
                 ctx.event_listeners().trigger_success(&info);
@@ -349,6 +412,7 @@ impl HttpClient {
                 (easy, Ok(()))
             }
             Err((mut easy, e)) => {
+                self.report_request_stats(&mut easy);
                 let ctx = easy.get_mut().request_context_mut();
                 let info = ctx.info().clone();
                 ctx.event_listeners().trigger_failure(&info);
@@ -404,11 +468,12 @@ impl HttpClient {
             }
         }
 
-        if let Some(cert_path) = &self.config.cert_path {
+        let creds = self.creds.read();
+        if let Some(cert_path) = &creds.cert_path {
             req.set_cert(cert_path);
         }
 
-        if let Some(key_path) = &self.config.key_path {
+        if let Some(key_path) = &creds.key_path {
             req.set_key(key_path);
         }
 
@@ -419,6 +484,14 @@ impl HttpClient {
         req.set_verify_tls_cert(!self.config.disable_tls_verification);
         req.set_verify_tls_host(!self.config.disable_tls_verification);
 
+        if let Some(proxy) = &self.config.proxy {
+            req.set_proxy(proxy);
+            if let Some(proxy_auth) = &self.config.proxy_auth {
+                req.set_proxy_auth(proxy_auth.clone());
+            }
+            req.set_no_proxy(self.config.no_proxy.iter());
+        }
+
         req
     }
 }
@@ -730,4 +803,68 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_reload_creds() {
+        let client = HttpClient::from_config(Config {
+            cert_path: Some("old.pem".into()),
+            key_path: Some("old.key".into()),
+            ..Default::default()
+        });
+        assert_eq!(
+            client.creds(),
+            ClientCreds {
+                cert_path: Some("old.pem".into()),
+                key_path: Some("old.key".into()),
+            }
+        );
+
+        let clone = client.clone();
+        let new_creds = ClientCreds {
+            cert_path: Some("new.pem".into()),
+            key_path: None,
+        };
+        client.reload_creds(new_creds.clone());
+
+        // Clones share the reloaded credentials.
+        assert_eq!(client.creds(), new_creds);
+        assert_eq!(clone.creds(), new_creds);
+    }
+
+    #[test]
+    fn test_request_stats_listener() -> Result<()> {
+        const BODY: &[u8] = b"body";
+        let mock = mock("GET", "/stats")
+            .with_status(201)
+            .with_body(BODY)
+            .expect(4)
+            .create();
+
+        let url = Url::parse(&mockito::server_url())?.join("stats")?;
+        let request = Request::get(url.clone());
+
+        let (tx, rx) = crossbeam::channel::unbounded();
+        let client = HttpClient::new().with_event_listeners(|l| {
+            l.on_request_stats(move |req, stats| {
+                tx.send((req.url().clone(), stats.clone())).unwrap();
+            });
+        });
+
+        client.send(vec![request.clone(), request.clone()], |_| Ok(()))?;
+        let my_stream_req = || request.clone().into_streaming(TestReceiver::new());
+        client.stream(vec![my_stream_req(), my_stream_req()])?;
+        drop(client);
+
+        let records = rx.iter().collect::<Vec<_>>();
+        assert_eq!(records.len(), 4);
+        for (req_url, stats) in records {
+            assert_eq!(req_url, url);
+            assert_eq!(stats.status, Some(StatusCode::CREATED));
+            assert_eq!(stats.downloaded, BODY.len());
+        }
+
+        mock.assert();
+
+        Ok(())
+    }
 }
@@ -12,6 +12,8 @@ use thiserror::Error;
 pub enum HttpClientError {
     #[error(transparent)]
     Curl(curl::Error),
+    #[error("Request timed out: {0}")]
+    Timeout(curl::Error),
     #[error(transparent)]
     Tls(#[from] TlsError),
     #[error(transparent)]
@@ -34,8 +36,23 @@ pub enum HttpClientError {
     Other(#[from] anyhow::Error),
 }
 
+impl HttpClientError {
+    /// Whether the request failed due to an HTTP/2 framing or stream error.
+    /// Such errors are often caused by proxies or servers that do not fully
+    /// support HTTP/2, and may be avoided by using HTTP/1.1 instead.
+    pub fn is_http2_error(&self) -> bool {
+        match self {
+            HttpClientError::Curl(e) => e.is_http2_error() || e.is_http2_stream_error(),
+            _ => false,
+        }
+    }
+}
+
 impl From<curl::Error> for HttpClientError {
     fn from(e: curl::Error) -> Self {
+        if e.is_operation_timedout() {
+            return HttpClientError::Timeout(e);
+        }
         TlsError::try_from(e).map_or_else(HttpClientError::Curl, HttpClientError::Tls)
     }
 }
@@ -141,3 +158,31 @@ fn ssl_categorize_recv_error(error: &curl::Error) -> TlsErrorKind {
 
     TlsErrorKind::RecvError
 }
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn test_timeout_error() {
+        let err = HttpClientError::from(curl::Error::new(curl_sys::CURLE_OPERATION_TIMEDOUT));
+        assert_matches!(err, HttpClientError::Timeout(_));
+
+        let err = HttpClientError::from(curl::Error::new(curl_sys::CURLE_COULDNT_CONNECT));
+        assert_matches!(err, HttpClientError::Curl(_));
+    }
+
+    #[test]
+    fn test_http2_error() {
+        let err = HttpClientError::from(curl::Error::new(curl_sys::CURLE_HTTP2));
+        assert!(err.is_http2_error());
+
+        let err = HttpClientError::from(curl::Error::new(curl_sys::CURLE_HTTP2_STREAM));
+        assert!(err.is_http2_error());
+
+        let err = HttpClientError::from(curl::Error::new(curl_sys::CURLE_COULDNT_CONNECT));
+        assert!(!err.is_http2_error());
+    }
+}
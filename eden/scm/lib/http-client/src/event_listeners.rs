@@ -11,6 +11,7 @@ use crate::progress::Progress;
 use crate::request::Request;
 use crate::request::RequestContext;
 use crate::request::RequestInfo;
+use crate::stats::RequestStats;
 use crate::stats::Stats;
 
 /// Generate a struct for holding event listeners (callbacks).
@@ -81,6 +82,9 @@ gen_event_listeners! {
         /// A request is failed.
         failed_request(req: &RequestContext),
 
+        /// A request has completed, successfully or not, with its statistics.
+        request_stats(req: &RequestInfo, stats: &RequestStats),
+
         /// One or more requests have completed with statistics.
         stats(stats: &Stats),
     }
@@ -23,11 +23,13 @@ mod response;
 mod stats;
 mod stream;
 
+pub use client::ClientCreds;
 pub use client::Config;
 pub use client::HttpClient;
 pub use client::ResponseFuture;
 pub use client::StatsFuture;
 pub use curl::easy::HttpVersion;
+pub use curl::easy::SslVersion;
 pub use errors::Abort;
 pub use errors::HttpClientError;
 pub use errors::TlsError;
@@ -38,6 +40,7 @@ pub use receiver::Receiver;
 pub use request::Encoding;
 pub use request::Method;
 pub use request::MinTransferSpeed;
+pub use request::ProxyAuth;
 pub use request::Request;
 pub use request::RequestContext;
 pub use request::RequestInfo;
@@ -45,6 +48,7 @@ pub use request::StreamRequest;
 pub use response::AsyncBody;
 pub use response::AsyncResponse;
 pub use response::Response;
+pub use stats::RequestStats;
 pub use stats::Stats;
 pub use stream::BufferedStream;
 pub use stream::CborStream;
@@ -20,6 +20,7 @@ use std::time::SystemTime;
 use curl::easy::Easy2;
 use curl::easy::HttpVersion;
 use curl::easy::List;
+use curl::easy::SslVersion;
 use http::header;
 use lru_cache::LruCache;
 use maplit::hashmap;
@@ -57,6 +58,23 @@ pub struct MinTransferSpeed {
     pub grace_period: Duration,
 }
 
+/// Credentials to authenticate with an HTTP proxy.
+#[derive(Clone, PartialEq)]
+pub struct ProxyAuth {
+    pub username: String,
+    pub password: Option<String>,
+}
+
+// Avoid leaking the password into logs.
+impl fmt::Debug for ProxyAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyAuth")
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
 impl fmt::Display for Method {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -173,15 +191,21 @@ pub struct Request {
     cert: Option<PathBuf>,
     key: Option<PathBuf>,
     cainfo: Option<PathBuf>,
+    min_tls_version: Option<SslVersion>,
     timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
     http_version: HttpVersion,
     accept_encoding: Vec<Encoding>,
     min_transfer_speed: Option<MinTransferSpeed>,
+    max_recv_speed: Option<u64>,
     verify_tls_host: bool,
     verify_tls_cert: bool,
     verbose: bool,
     convert_cert: bool,
     auth_proxy_socket_path: Option<String>,
+    proxy: Option<String>,
+    proxy_auth: Option<ProxyAuth>,
+    no_proxy: Vec<String>,
 }
 
 static REQUEST_CREATION_LISTENERS: Lazy<RwLock<RequestCreationEventListeners>> =
@@ -263,15 +287,21 @@ impl Request {
             cert: None,
             key: None,
             cainfo: None,
+            min_tls_version: None,
             timeout: None,
+            connect_timeout: None,
             http_version: DEFAULT_HTTP_VERSION.clone(),
             accept_encoding: Vec::new(),
             min_transfer_speed: None,
+            max_recv_speed: None,
             verify_tls_host: true,
             verify_tls_cert: true,
             verbose: false,
             convert_cert: false,
             auth_proxy_socket_path: None,
+            proxy: None,
+            proxy_auth: None,
+            no_proxy: Vec::new(),
         }
     }
 
@@ -369,6 +399,28 @@ impl Request {
         self
     }
 
+    /// Limit the download speed of this request, in bytes per second.
+    ///
+    /// The limit applies to each request separately. Requests sent
+    /// concurrently (for example, batches sent via `HttpClient::send_async`)
+    /// may together exceed it, so divide the desired total by the number of
+    /// concurrent requests to approximate a global limit.
+    pub fn max_recv_speed(mut self, bytes_per_second: u64) -> Self {
+        self.set_max_recv_speed(bytes_per_second);
+        self
+    }
+
+    /// Limit the download speed of this request, in bytes per second.
+    ///
+    /// The limit applies to each request separately. Requests sent
+    /// concurrently (for example, batches sent via `HttpClient::send_async`)
+    /// may together exceed it, so divide the desired total by the number of
+    /// concurrent requests to approximate a global limit.
+    pub fn set_max_recv_speed(&mut self, bytes_per_second: u64) -> &mut Self {
+        self.max_recv_speed = Some(bytes_per_second);
+        self
+    }
+
     /// Serialize the given value as JSON and use it as the request body.
     pub fn json<S: Serialize>(mut self, value: &S) -> Result<Self, serde_json::Error> {
         self.set_json_body(value)?;
@@ -478,6 +530,28 @@ impl Request {
         self
     }
 
+    /// Set the minimum TLS version the client will negotiate with the
+    /// server.
+    pub fn min_tls_version(mut self, version: SslVersion) -> Self {
+        self.set_min_tls_version(version);
+        self
+    }
+
+    /// Set the minimum TLS version the client will negotiate with the
+    /// server.
+    pub fn set_min_tls_version(&mut self, version: SslVersion) -> &mut Self {
+        self.min_tls_version = Some(version);
+        self
+    }
+
+    /// Check that libcurl accepts `version` as the minimum TLS version,
+    /// so that configuration errors can be reported before sending
+    /// requests.
+    pub fn check_min_tls_version(version: SslVersion) -> Result<(), HttpClientError> {
+        curl::easy::Easy::new().ssl_min_max_version(version, SslVersion::Default)?;
+        Ok(())
+    }
+
     /// Set the maximum time this request is allowed to take.
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.set_timeout(timeout);
@@ -490,6 +564,20 @@ impl Request {
         self
     }
 
+    /// Set the maximum time the connection phase (including the TLS
+    /// handshake) of this request is allowed to take.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.set_connect_timeout(timeout);
+        self
+    }
+
+    /// Set the maximum time the connection phase (including the TLS
+    /// handshake) of this request is allowed to take.
+    pub fn set_connect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
     /// Configure whether the client should verify that the server's hostname
     /// matches either the common name (CN) or a subject alternate name (SAN)
     /// present in the server's TLS certificate. Disabling this option will make
@@ -561,6 +649,36 @@ impl Request {
         self
     }
 
+    /// Send the request through the given HTTP proxy, for example
+    /// "myproxy:8000". This takes precedence over the `http_proxy` and
+    /// `https_proxy` environment variables, which libcurl respects if no
+    /// proxy is set explicitly.
+    pub fn proxy(mut self, proxy: impl ToString) -> Self {
+        self.set_proxy(proxy);
+        self
+    }
+
+    /// Send the request through the given HTTP proxy, for example
+    /// "myproxy:8000". This takes precedence over the `http_proxy` and
+    /// `https_proxy` environment variables, which libcurl respects if no
+    /// proxy is set explicitly.
+    pub fn set_proxy(&mut self, proxy: impl ToString) -> &mut Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// Credentials to authenticate with the proxy server.
+    pub fn set_proxy_auth(&mut self, proxy_auth: ProxyAuth) -> &mut Self {
+        self.proxy_auth = Some(proxy_auth);
+        self
+    }
+
+    /// Host names that should not be accessed via the proxy.
+    pub fn set_no_proxy(&mut self, hosts: impl IntoIterator<Item = impl ToString>) -> &mut Self {
+        self.no_proxy = hosts.into_iter().map(|h| h.to_string()).collect();
+        self
+    }
+
     /// Convert the client's X.509 certificate from a PEM file into an in-memory
     /// PKCS#12 archive before passing it to libcurl. This is necessary on some
     /// platforms (most notably Windows) where the system crypto APIs (SChannel
@@ -668,6 +786,23 @@ impl Request {
 
         easy.url(url.as_str())?;
         easy.verbose(self.verbose)?;
+
+        // The auth proxy is reached via a unix socket, so the HTTP proxy
+        // is not used in that case.
+        if self.auth_proxy_socket_path.is_none() {
+            if let Some(proxy) = &self.proxy {
+                easy.proxy(proxy)?;
+                if let Some(proxy_auth) = &self.proxy_auth {
+                    easy.proxy_username(&proxy_auth.username)?;
+                    if let Some(password) = &proxy_auth.password {
+                        easy.proxy_password(password)?;
+                    }
+                }
+            }
+            if !self.no_proxy.is_empty() {
+                easy.noproxy(&self.no_proxy.join(","))?;
+            }
+        }
         easy.unix_socket_path(self.auth_proxy_socket_path)?;
 
         // Configure the handle for the desired HTTP method.
@@ -764,10 +899,18 @@ impl Request {
             easy.cainfo(cainfo)?;
         }
 
+        if let Some(min_tls_version) = self.min_tls_version {
+            easy.ssl_min_max_version(min_tls_version, SslVersion::Default)?;
+        }
+
         if let Some(timeout) = self.timeout {
             easy.timeout(timeout)?;
         }
 
+        if let Some(connect_timeout) = self.connect_timeout {
+            easy.connect_timeout(connect_timeout)?;
+        }
+
         easy.http_version(self.http_version)?;
 
         if let Some(mts) = self.min_transfer_speed {
@@ -775,6 +918,10 @@ impl Request {
             easy.low_speed_time(mts.grace_period)?;
         }
 
+        if let Some(max_recv_speed) = self.max_recv_speed {
+            easy.max_recv_speed(max_recv_speed)?;
+        }
+
         // Tell libcurl to report progress to the handler.
         easy.progress(true)?;
 
@@ -1202,4 +1349,66 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_max_recv_speed() -> Result<()> {
+        let mock = mock("GET", "/test")
+            .with_status(200)
+            .with_body("Hello, world!")
+            .create();
+
+        let url = Url::parse(&mockito::server_url())?.join("test")?;
+        let req = Request::get(url).max_recv_speed(1024 * 1024);
+        assert_eq!(req.max_recv_speed, Some(1024 * 1024));
+        let res = req.send()?;
+
+        mock.assert();
+        assert_eq!(res.head.status, StatusCode::OK);
+        assert_eq!(&*res.body, &b"Hello, world!"[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_tls_version() -> Result<()> {
+        Request::check_min_tls_version(SslVersion::Tlsv12)?;
+
+        let url: Url = "https://example.com".parse()?;
+        let req = Request::get(url).min_tls_version(SslVersion::Tlsv12);
+        assert!(matches!(req.min_tls_version, Some(SslVersion::Tlsv12)));
+        let _easy: Easy2<Buffered> = req.try_into()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_proxy_config() -> Result<()> {
+        let url: Url = "https://example.com".parse()?;
+
+        let req = HttpClient::new().get(url.clone());
+        assert_eq!(req.proxy, None);
+        assert_eq!(req.proxy_auth, None);
+
+        let client = HttpClient::from_config(Config {
+            proxy: Some("myproxy:8000".to_string()),
+            proxy_auth: Some(ProxyAuth {
+                username: "user".to_string(),
+                password: Some("pass".to_string()),
+            }),
+            no_proxy: vec!["localhost".to_string(), "internal.example.com".to_string()],
+            ..Default::default()
+        });
+        let req = client.get(url);
+        assert_eq!(req.proxy.as_deref(), Some("myproxy:8000"));
+        let proxy_auth = req.proxy_auth.as_ref().unwrap();
+        assert_eq!(proxy_auth.username, "user");
+        assert_eq!(proxy_auth.password.as_deref(), Some("pass"));
+        assert!(!format!("{:?}", &req).contains("\"pass\""));
+        assert_eq!(req.no_proxy, ["localhost", "internal.example.com"]);
+
+        // The proxy options are accepted by libcurl.
+        let _easy: Easy2<Buffered> = req.try_into()?;
+
+        Ok(())
+    }
 }
@@ -9,6 +9,9 @@ use std::fmt;
 use std::mem;
 use std::time::Duration;
 
+use curl::easy::Easy2;
+use http::StatusCode;
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Stats {
     pub downloaded: usize,
@@ -28,6 +31,30 @@ impl Stats {
     }
 }
 
+/// Statistics for a single completed request.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RequestStats {
+    /// The HTTP status code, or None if no response was received.
+    pub status: Option<StatusCode>,
+    pub downloaded: usize,
+    pub uploaded: usize,
+    pub time: Duration,
+}
+
+impl RequestStats {
+    pub(crate) fn from_handle<H>(easy: &mut Easy2<H>) -> Self {
+        Self {
+            status: easy
+                .response_code()
+                .ok()
+                .and_then(|code| StatusCode::from_u16(code as u16).ok()),
+            downloaded: easy.download_size().unwrap_or_default() as usize,
+            uploaded: easy.upload_size().unwrap_or_default() as usize,
+            time: easy.total_time().unwrap_or_default(),
+        }
+    }
+}
+
 impl fmt::Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
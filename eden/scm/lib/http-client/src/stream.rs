@@ -50,7 +50,9 @@ pub struct CborStream<T, S, B, E> {
 }
 
 impl<T, S, B, E> CborStream<T, S, B, E> {
-    pub(crate) fn new(body: S) -> Self {
+    /// Deserialize CBOR values from the given stream of bytes. Most callers
+    /// will want to use `AsyncBody::cbor` instead.
+    pub fn new(body: S) -> Self {
         Self::with_buffer_size(body, SMALL_BUFFER_SIZE)
     }
 
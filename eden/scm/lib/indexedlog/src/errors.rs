@@ -9,9 +9,12 @@
 //!
 //! See [`Error`] for the main type.
 
+use std::backtrace::Backtrace;
+use std::backtrace::BacktraceStatus;
 use std::fmt;
 use std::io;
 use std::path::Path;
+use std::path::PathBuf;
 
 // Error design goals:
 // - Callsites can test whether an error is caused by data corruption or other
@@ -32,12 +35,56 @@ pub struct Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Coarse classification of an [`Error`], returned by [`Error::kind`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Data corruption. See [`Error::is_corruption`].
+    Corruption,
+
+    /// An I/O error that is not a data corruption. For example, permission
+    /// denied, or exceeding the file descriptor limit.
+    Io,
+
+    /// A programming error or API misuse. For example, passing an invalid
+    /// parameter to an API.
+    Programming,
+
+    /// Other errors.
+    Other,
+}
+
+/// Structured information about a data corruption, returned by
+/// [`Error::corruption_context`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorruptionInfo {
+    path: Option<PathBuf>,
+    messages: Vec<String>,
+}
+
+impl CorruptionInfo {
+    /// The path of the corrupted file or directory, if known.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Messages attached to the error, outermost context first.
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+}
+
+const PROGRAMMING_ERROR_PREFIX: &str = "ProgrammingError: ";
+
 #[derive(Default)]
 struct Inner {
     sources: Vec<Box<dyn std::error::Error + Send + Sync + 'static>>,
     messages: Vec<String>,
+    path: Option<PathBuf>,
     is_corruption: bool,
+    recoverable: bool,
+    is_resource_exhaustion: bool,
     io_error_kind: Option<io::ErrorKind>,
+    backtrace: Option<Backtrace>,
 }
 
 impl Error {
@@ -75,17 +122,182 @@ impl Error {
     /// attempting to write, deleting or changing files in some ways. Issues
     /// like "disk is full", "permission errors", "process killed at random
     /// time" are expected to not cause data corruption (but only data loss).
+    ///
+    /// An error caused by resource exhaustion is never a data corruption,
+    /// see [`Error::is_resource_exhaustion`].
     pub fn is_corruption(&self) -> bool {
         self.inner.is_corruption
     }
 
-    pub fn io_error_kind(&self) -> io::ErrorKind {
-        self.inner.io_error_kind.unwrap_or(io::ErrorKind::Other)
+    /// Return `true` if the error is a data corruption that is known to be
+    /// recoverable. For example, a derived file that can be rebuilt from
+    /// other data.
+    ///
+    /// A corruption is recoverable only if every corruption it carries,
+    /// including those inherited from its sources, is recoverable.
+    pub fn is_recoverable_corruption(&self) -> bool {
+        self.inner.is_corruption && self.inner.recoverable
+    }
+
+    /// Return `true` if the error is caused by the system running out of a
+    /// resource, for example memory, disk space, or file descriptors.
+    ///
+    /// Those errors say nothing about the data, which could not be read or
+    /// written confidently, so they are never considered as data corruption,
+    /// even if the error also carries a corruption. The operation might
+    /// succeed later once the resource is available again, while repairing
+    /// would throw away data that is likely fine.
+    ///
+    /// I/O errors are considered as resource exhaustion if they are
+    /// [`io::ErrorKind::OutOfMemory`], or if their OS error code means the
+    /// disk or quota is full, or too many files are open.
+    pub fn is_resource_exhaustion(&self) -> bool {
+        self.inner.is_resource_exhaustion
+    }
+
+    /// Return details about the data corruption, or `None` if the error is
+    /// not a data corruption.
+    ///
+    /// The path is taken from this error, or from the first corrupted error
+    /// source that has a path.
+    pub fn corruption_context(&self) -> Option<CorruptionInfo> {
+        if !self.is_corruption() {
+            return None;
+        }
+        Some(CorruptionInfo {
+            path: self.corruption_path().map(Path::to_path_buf),
+            messages: self.inner.messages.clone(),
+        })
+    }
+
+    fn corruption_path(&self) -> Option<&Path> {
+        self.inner.path.as_deref().or_else(|| {
+            self.inner.sources.iter().find_map(|source| {
+                source
+                    .downcast_ref::<Error>()
+                    .filter(|err| err.is_corruption())
+                    .and_then(|err| err.corruption_path())
+            })
+        })
+    }
+
+    /// Iterate through messages attached to the error, in the same order as
+    /// they are shown by `Display`.
+    pub fn messages(&self) -> impl Iterator<Item = &str> {
+        self.inner.messages.iter().map(|message| message.as_str())
+    }
+
+    /// Iterate through errors attached as direct sources, in the same order
+    /// as they are shown by `Display`.
+    pub fn sources(&self) -> impl Iterator<Item = &(dyn std::error::Error + 'static)> {
+        self.inner
+            .sources
+            .iter()
+            .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
+    }
+
+    /// Return the backtrace captured when the error was created.
+    ///
+    /// A backtrace is only captured if the `backtrace` feature is enabled, or
+    /// the `RUST_LIB_BACKTRACE` or `RUST_BACKTRACE` environment variable
+    /// enables it. See [`Backtrace::capture`] for details.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.backtrace.as_ref()
+    }
+
+    /// Classify the error.
+    ///
+    /// Data corruption takes precedence over other kinds, followed by
+    /// programming errors, then I/O errors. Kinds of errors attached as
+    /// sources are taken into account.
+    pub fn kind(&self) -> ErrorKind {
+        if self.is_corruption() {
+            return ErrorKind::Corruption;
+        }
+
+        if self.is_programming() {
+            ErrorKind::Programming
+        } else if self.io_error_kind().is_some() {
+            ErrorKind::Io
+        } else {
+            ErrorKind::Other
+        }
+    }
+
+    /// Return the [`io::ErrorKind`] of the I/O error that caused this error,
+    /// or `None` if the error was not caused by an I/O error.
+    ///
+    /// The error sources are searched in order, including the sources of
+    /// nested indexedlog errors.
+    pub fn io_error_kind(&self) -> Option<io::ErrorKind> {
+        if let Some(kind) = self.inner.io_error_kind {
+            return Some(kind);
+        }
+        self.inner.sources.iter().find_map(|source| {
+            if let Some(err) = source.downcast_ref::<Error>() {
+                err.io_error_kind()
+            } else {
+                source.downcast_ref::<io::Error>().map(|err| err.kind())
+            }
+        })
+    }
+
+    /// Return `true` if the error is likely transient, and retrying the
+    /// operation might succeed.
+    ///
+    /// An error is considered retryable if it was caused by an I/O error
+    /// with one of the following kinds:
+    /// - [`io::ErrorKind::WouldBlock`], for example, failing to take a lock
+    ///   that is held by another process.
+    /// - [`io::ErrorKind::Interrupted`]
+    /// - [`io::ErrorKind::TimedOut`]
+    ///
+    /// Resource exhaustion errors (see [`Error::is_resource_exhaustion`]) are
+    /// also retryable, since memory, disk space or file descriptors may be
+    /// released by the time the operation is retried.
+    ///
+    /// Data corruption and programming errors are never retryable, even if
+    /// they were caused by one of the above.
+    pub fn is_retryable(&self) -> bool {
+        match self.kind() {
+            ErrorKind::Io => {
+                self.is_resource_exhaustion()
+                    || matches!(
+                        self.io_error_kind(),
+                        Some(
+                            io::ErrorKind::WouldBlock
+                                | io::ErrorKind::Interrupted
+                                | io::ErrorKind::TimedOut
+                        )
+                    )
+            }
+            ErrorKind::Other => self.is_resource_exhaustion(),
+            ErrorKind::Corruption | ErrorKind::Programming => false,
+        }
+    }
+
+    fn is_programming(&self) -> bool {
+        self.inner
+            .messages
+            .iter()
+            .any(|message| message.starts_with(PROGRAMMING_ERROR_PREFIX))
+            || self.inner.sources.iter().any(|source| {
+                source
+                    .downcast_ref::<Error>()
+                    .is_some_and(|err| err.is_programming())
+            })
     }
 
     // Following methods are used by this crate only.
     // External code should not construct or modify `Error`.
 
+    /// Convert to [`anyhow::Error`], preserving the error so it can be
+    /// downcast back to [`Error`].
+    #[cfg(feature = "anyhow")]
+    pub fn into_anyhow(self) -> anyhow::Error {
+        anyhow::Error::new(self)
+    }
+
     pub(crate) fn message(mut self, message: impl ToString) -> Self {
         self.inner.messages.push(message.to_string());
         self
@@ -96,33 +308,74 @@ impl Error {
     }
 
     fn source_dyn(mut self, source: Box<dyn std::error::Error + Send + Sync + 'static>) -> Self {
-        // Inherit the data corruption flag.
+        // Inherit the data corruption and resource exhaustion flags.
         if let Some(err) = source.downcast_ref::<Error>() {
-            if err.is_corruption() {
+            if err.is_resource_exhaustion() {
+                self = self.mark_resource_exhaustion();
+            } else if err.is_recoverable_corruption() {
+                self = self.mark_recoverable_corruption();
+            } else if err.is_corruption() {
                 self = self.mark_corruption();
             }
+        } else if let Some(err) = source.downcast_ref::<io::Error>() {
+            if is_resource_exhaustion_io_error(err) {
+                self = self.mark_resource_exhaustion();
+            }
         }
 
         self.inner.sources.push(source);
         self
     }
 
+    fn with_path(mut self, path: &Path) -> Self {
+        self.inner.path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Mark as a data corruption, unless the error is caused by resource
+    /// exhaustion.
     pub(crate) fn mark_corruption(mut self) -> Self {
-        self.inner.is_corruption = true;
+        if !self.inner.is_resource_exhaustion {
+            self.inner.is_corruption = true;
+            self.inner.recoverable = false;
+        }
         self
     }
 
-    pub(crate) fn blank() -> Self {
-        Error {
-            inner: Default::default(),
+    /// Mark as a recoverable data corruption, unless the error is already a
+    /// non-recoverable data corruption, or is caused by resource exhaustion.
+    pub(crate) fn mark_recoverable_corruption(mut self) -> Self {
+        if !self.inner.is_corruption && !self.inner.is_resource_exhaustion {
+            self.inner.is_corruption = true;
+            self.inner.recoverable = true;
         }
+        self
+    }
+
+    /// Mark as caused by resource exhaustion. This clears the data
+    /// corruption flag.
+    pub(crate) fn mark_resource_exhaustion(mut self) -> Self {
+        self.inner.is_resource_exhaustion = true;
+        self.inner.is_corruption = false;
+        self.inner.recoverable = false;
+        self
+    }
+
+    pub(crate) fn blank() -> Self {
+        let mut inner: Box<Inner> = Default::default();
+        inner.backtrace = capture_backtrace();
+        Error { inner }
     }
 
     /// A ProgrammingError that breaks some internal assumptions.
     /// For example, passing an invalid parameter to an API.
     #[inline(never)]
     pub(crate) fn programming(message: impl ToString) -> Self {
-        Self::blank().message(format!("ProgrammingError: {}", message.to_string()))
+        Self::blank().message(format!(
+            "{}{}",
+            PROGRAMMING_ERROR_PREFIX,
+            message.to_string()
+        ))
     }
 
     /// A data corruption error with path.
@@ -131,7 +384,30 @@ impl Error {
     #[inline(never)]
     pub(crate) fn corruption(path: &Path, message: impl ToString) -> Self {
         let message = format!("{:?}: {}", path, message.to_string());
-        Self::blank().mark_corruption().message(message)
+        Self::blank()
+            .mark_corruption()
+            .with_path(path)
+            .message(message)
+    }
+
+    /// A recoverable data corruption error with path. See
+    /// [`Error::is_recoverable_corruption`].
+    #[inline(never)]
+    pub(crate) fn corruption_recoverable(path: &Path, message: impl ToString) -> Self {
+        let message = format!("{:?}: {}", path, message.to_string());
+        Self::blank()
+            .mark_recoverable_corruption()
+            .with_path(path)
+            .message(message)
+    }
+
+    /// An error caused by the system running out of a resource. See
+    /// [`Error::is_resource_exhaustion`].
+    ///
+    /// If there is an [`IOError`], use [`IoResultExt::context`] instead.
+    #[inline(never)]
+    pub(crate) fn resource(message: impl ToString) -> Self {
+        Self::blank().mark_resource_exhaustion().message(message)
     }
 
     /// An error with a path that is not a data corruption.
@@ -140,7 +416,7 @@ impl Error {
     #[inline(never)]
     pub(crate) fn path(path: &Path, message: impl ToString) -> Self {
         let message = format!("{:?}: {}", path, message.to_string());
-        Self::blank().message(message)
+        Self::blank().with_path(path).message(message)
     }
 
     /// Wrap a dynamic stdlib error.
@@ -187,6 +463,12 @@ impl fmt::Display for Error {
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.debug_string(true))
+    }
+}
+
+impl Error {
+    fn debug_string(&self, include_backtrace: bool) -> String {
         let mut lines = Vec::new();
         for message in &self.inner.messages {
             lines.push(message.to_string());
@@ -194,13 +476,59 @@ impl fmt::Debug for Error {
         if self.is_corruption() {
             lines.push("(This error is considered as a data corruption)".to_string())
         }
+        if self.is_resource_exhaustion() {
+            lines.push("(This error is caused by resource exhaustion)".to_string())
+        }
         if !self.inner.sources.is_empty() {
             lines.push(format!("Caused by {} errors:", self.inner.sources.len()));
             for source in &self.inner.sources {
-                lines.push(indent(format!("{:?}", source), 2, '-'));
+                // Backtraces of inner errors are mostly duplicated with the
+                // outer one. Skip them to keep the output readable.
+                let source = match source.downcast_ref::<Error>() {
+                    Some(err) => err.debug_string(false),
+                    None => format!("{:?}", source),
+                };
+                lines.push(indent(source, 2, '-'));
             }
         }
-        write!(f, "{}", lines.join("\n"))
+        if include_backtrace {
+            if let Some(backtrace) = self.backtrace() {
+                lines.push(format!("Backtrace:\n{}", backtrace));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// Capture a backtrace if enabled. This is cheap if backtrace is disabled.
+#[inline]
+fn capture_backtrace() -> Option<Backtrace> {
+    let backtrace = if cfg!(feature = "backtrace") {
+        Backtrace::force_capture()
+    } else {
+        Backtrace::capture()
+    };
+    match backtrace.status() {
+        BacktraceStatus::Captured => Some(backtrace),
+        _ => None,
+    }
+}
+
+/// Serialize as `{ messages, is_corruption, sources }`. Sources are
+/// serialized as their `Display` strings.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let sources: Vec<String> = self.sources().map(|source| source.to_string()).collect();
+        let mut state = serializer.serialize_struct("Error", 3)?;
+        state.serialize_field("messages", &self.inner.messages)?;
+        state.serialize_field("is_corruption", &self.is_corruption())?;
+        state.serialize_field("sources", &sources)?;
+        state.end()
     }
 }
 
@@ -244,12 +572,21 @@ impl<T> ResultExt<T> for Result<T> {
     }
 }
 
+// Note: `From<Error> for Box<dyn std::error::Error + Send + Sync>` is
+// provided by the std blanket impl. It moves the `Error` into the box
+// without formatting, so the error can be downcast back to `Error`.
 impl std::error::Error for Error {
     // This 'Error' type is designed to be opaque (internal states are
     // private, including inner errors), and takes responsibility
-    // of displaying a -chain- tree of errors. So it might be desirable
-    // not implementing `source` here, and expose public APIs for all
-    // use-needs.
+    // of displaying a -chain- tree of errors. `source` only exposes the
+    // first inner error so std error chain walkers (ex. anyhow) can
+    // traverse it. Other inner errors are only visible via Display.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner
+            .sources
+            .first()
+            .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
+    }
 }
 
 pub(crate) trait IoResultExt<T> {
@@ -257,6 +594,8 @@ pub(crate) trait IoResultExt<T> {
     /// path and the operation name.
     ///
     /// Mark InvalidData and UnexpectedEof as data corruption automatically.
+    /// Mark errors caused by running out of memory, disk space, or file
+    /// descriptors as resource exhaustion automatically.
     ///
     /// Consider using [`ResultExt::corruption`] to mark the error as data
     /// corruption if appropriate.
@@ -279,8 +618,9 @@ impl<T> IoResultExt<T> for std::io::Result<T> {
                 _ => false,
             };
             let is_eperm = kind == ErrorKind::PermissionDenied;
+            let resource = is_resource_exhaustion_io_error(&err);
 
-            let mut err = Error::blank().source(err).message(format!(
+            let mut err = Error::blank().source(err).with_path(path).message(format!(
                 "{:?}: {}",
                 path,
                 message.to_string_costly()
@@ -288,6 +628,9 @@ impl<T> IoResultExt<T> for std::io::Result<T> {
             if corruption {
                 err = err.mark_corruption();
             }
+            if resource {
+                err = err.mark_resource_exhaustion();
+            }
             err.inner.io_error_kind = Some(kind);
 
             // Provide more context for PermissionDenied
@@ -330,6 +673,26 @@ impl<T> IoResultExt<T> for std::io::Result<T> {
     }
 }
 
+/// Return `true` if the I/O error is caused by the system running out of
+/// memory, disk space or quota, or file descriptors.
+fn is_resource_exhaustion_io_error(err: &io::Error) -> bool {
+    if err.kind() == io::ErrorKind::OutOfMemory {
+        return true;
+    }
+    match err.raw_os_error() {
+        #[cfg(unix)]
+        Some(code) => matches!(
+            code,
+            libc::ENOMEM | libc::ENOSPC | libc::EDQUOT | libc::EMFILE | libc::ENFILE
+        ),
+        // ERROR_TOO_MANY_OPEN_FILES, ERROR_NOT_ENOUGH_MEMORY, ERROR_OUTOFMEMORY,
+        // ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL.
+        #[cfg(windows)]
+        Some(code) => matches!(code, 4 | 8 | 14 | 39 | 112),
+        _ => false,
+    }
+}
+
 pub(crate) trait LazyToString {
     fn to_string_costly(&self) -> String;
 }
@@ -387,6 +750,8 @@ Caused by 2 errors:
 
         // Mark as data corruption.
         e = e.mark_corruption();
+        // Backtrace might be captured depending on the environment.
+        e.inner.backtrace = None;
         assert_eq!(
             format!("{:?}", &e),
             r#"Error Message 1
@@ -422,6 +787,49 @@ Caused by 2 errors:
         );
     }
 
+    #[test]
+    fn test_corruption_constructors() {
+        let path = Path::new("a");
+        let err = Error::corruption(path, "bad checksum");
+        assert!(err.is_corruption());
+        assert!(!err.is_recoverable_corruption());
+
+        let err = Error::corruption_recoverable(path, "bad checksum");
+        assert!(err.is_corruption());
+        assert!(err.is_recoverable_corruption());
+        assert_eq!(err.to_string(), "\"a\": bad checksum");
+
+        assert!(!Error::blank().is_recoverable_corruption());
+        assert!(!Error::path(path, "not corrupted").is_recoverable_corruption());
+    }
+
+    #[test]
+    fn test_inherit_recoverable_corruption() {
+        let recoverable = || Error::corruption_recoverable(Path::new("a"), "x");
+        let unrecoverable = || Error::corruption(Path::new("b"), "y");
+
+        assert!(Error::blank()
+            .source(recoverable())
+            .is_recoverable_corruption());
+        assert!(Error::blank()
+            .source(Error::blank().source(recoverable()))
+            .is_recoverable_corruption());
+
+        // Any non-recoverable corruption makes the error non-recoverable.
+        let err = Error::blank().source(recoverable()).source(unrecoverable());
+        assert!(err.is_corruption());
+        assert!(!err.is_recoverable_corruption());
+        let err = Error::blank().source(unrecoverable()).source(recoverable());
+        assert!(err.is_corruption());
+        assert!(!err.is_recoverable_corruption());
+        let err = unrecoverable().source(recoverable());
+        assert!(!err.is_recoverable_corruption());
+
+        // Non-corruption sources do not affect the flag.
+        let err = recoverable().source(Error::blank());
+        assert!(err.is_recoverable_corruption());
+    }
+
     #[test]
     fn test_io_result_ext() {
         let err = io_result().context(Path::new("a.txt"), "cannot open for reading");
@@ -442,6 +850,338 @@ Caused by 1 errors:
         );
     }
 
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(Error::blank().kind(), ErrorKind::Other);
+        assert_eq!(Error::from("message").kind(), ErrorKind::Other);
+
+        let path = Path::new("a.txt");
+        assert_eq!(
+            Error::corruption(path, "bad checksum").kind(),
+            ErrorKind::Corruption
+        );
+        assert_eq!(Error::path(path, "cannot lock").kind(), ErrorKind::Other);
+        assert_eq!(
+            Error::programming("invalid parameter").kind(),
+            ErrorKind::Programming
+        );
+
+        let wrapped = Error::wrap(Box::new(Error::programming("bad")), "wrapped");
+        assert_eq!(wrapped.kind(), ErrorKind::Programming);
+        let wrapped = Error::wrap(Box::new(Error::corruption(path, "bad")), "wrapped");
+        assert_eq!(wrapped.kind(), ErrorKind::Corruption);
+        let wrapped = Error::wrap(Box::new(io_result().unwrap_err()), "wrapped");
+        assert_eq!(wrapped.kind(), ErrorKind::Io);
+        let wrapped = Error::wrap(Box::new(Error::from("inner")), "wrapped");
+        assert_eq!(wrapped.kind(), ErrorKind::Other);
+
+        let err = io_result().context(path, "cannot open").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        let err = Err::<(), _>(io::Error::from(io::ErrorKind::UnexpectedEof))
+            .context(path, "cannot read")
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Corruption);
+    }
+
+    #[test]
+    fn test_io_error_kind() {
+        let path = Path::new("a.txt");
+        assert_eq!(Error::blank().io_error_kind(), None);
+        assert_eq!(Error::path(path, "cannot lock").io_error_kind(), None);
+
+        let err = Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied))
+            .context(path, "cannot open")
+            .unwrap_err();
+        assert_eq!(err.io_error_kind(), Some(io::ErrorKind::PermissionDenied));
+        assert!(!err.is_corruption());
+
+        // InvalidData and UnexpectedEof are still considered as corruption.
+        let err = Err::<(), _>(io::Error::from(io::ErrorKind::InvalidData))
+            .context(path, "cannot read")
+            .unwrap_err();
+        assert_eq!(err.io_error_kind(), Some(io::ErrorKind::InvalidData));
+        assert!(err.is_corruption());
+
+        // The kind is recovered from nested sources.
+        let nested = Error::blank().message("outer").source(
+            Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+                .context(path, "cannot open")
+                .unwrap_err(),
+        );
+        assert_eq!(nested.io_error_kind(), Some(io::ErrorKind::NotFound));
+        let wrapped = Error::wrap(
+            Box::new(io::Error::from(io::ErrorKind::Interrupted)),
+            "wrapped",
+        );
+        assert_eq!(wrapped.io_error_kind(), Some(io::ErrorKind::Interrupted));
+    }
+
+    #[test]
+    fn test_messages_and_sources() {
+        let e = Error::blank();
+        assert_eq!(e.messages().count(), 0);
+        assert_eq!(e.sources().count(), 0);
+
+        let e = e
+            .message("Message 1")
+            .source(Error::blank().message("Inner 1"))
+            .message("Message 2")
+            .source(io::Error::new(io::ErrorKind::Other, "Inner 2"));
+        assert_eq!(e.messages().collect::<Vec<_>>(), ["Message 1", "Message 2"]);
+        assert_eq!(
+            e.sources().map(|s| s.to_string()).collect::<Vec<_>>(),
+            ["Inner 1", "Inner 2"]
+        );
+        assert_eq!(
+            format!("{}", &e),
+            r#"Message 1
+Message 2
+Caused by 2 errors:
+- Inner 1
+- Inner 2"#
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let e = Error::blank()
+            .message("outer")
+            .source(Error::corruption(Path::new("a"), "bad checksum"));
+        assert_eq!(
+            serde_json::to_string(&e).unwrap(),
+            r#"{"messages":["outer"],"is_corruption":true,"sources":["\"a\": bad checksum"]}"#
+        );
+    }
+
+    #[test]
+    fn test_boxed_error_round_trip() {
+        let path = Path::new("a.txt");
+        let e = Error::blank()
+            .message("outer")
+            .source(Error::corruption(path, "bad checksum"));
+        let boxed: Box<dyn std::error::Error + Send + Sync> = e.into();
+        assert!(boxed.source().is_some());
+
+        let e = boxed.downcast::<Error>().unwrap();
+        assert!(e.is_corruption());
+        assert_eq!(e.corruption_context().unwrap().path(), Some(path));
+
+        let boxed: Box<dyn std::error::Error + Send + Sync> = Error::blank().into();
+        assert!(!boxed.downcast::<Error>().unwrap().is_corruption());
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_into_anyhow() {
+        let e = Error::corruption(Path::new("a.txt"), "bad checksum").into_anyhow();
+        assert_eq!(e.to_string(), r#""a.txt": bad checksum"#);
+        assert!(e.downcast_ref::<Error>().unwrap().is_corruption());
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let mut e = Error::blank().message("outer");
+        e.inner.backtrace = None;
+        assert!(e.backtrace().is_none());
+        assert!(!format!("{:?}", &e).contains("Backtrace:"));
+
+        e.inner.backtrace = Some(Backtrace::force_capture());
+        let mut inner = Error::blank().message("inner");
+        inner.inner.backtrace = Some(Backtrace::force_capture());
+        e = e.source(inner);
+        assert!(e.backtrace().is_some());
+
+        // Backtrace is only included in Debug, once.
+        assert!(!format!("{}", &e).contains("Backtrace:"));
+        assert_eq!(format!("{:?}", &e).matches("Backtrace:").count(), 1);
+        assert!(
+            format!("{:?}", &e).starts_with("outer\nCaused by 1 errors:\n- inner\nBacktrace:\n")
+        );
+    }
+
+    #[test]
+    fn test_corruption_context() {
+        let path = Path::new("a.txt");
+
+        assert_eq!(Error::blank().corruption_context(), None);
+        assert_eq!(Error::path(path, "cannot open").corruption_context(), None);
+
+        let e = Error::corruption(path, "bad checksum");
+        assert_eq!(format!("{}", &e), r#""a.txt": bad checksum"#);
+        let info = e.corruption_context().unwrap();
+        assert_eq!(info.path(), Some(path));
+        assert_eq!(info.messages(), [r#""a.txt": bad checksum"#]);
+
+        // Marked as corruption without a path.
+        let info = Error::blank()
+            .message("bad data")
+            .mark_corruption()
+            .corruption_context()
+            .unwrap();
+        assert_eq!(info.path(), None);
+        assert_eq!(info.messages(), ["bad data"]);
+
+        // The path is found from a corrupted source.
+        let e = Error::blank().message("outer").source(e);
+        let info = e.corruption_context().unwrap();
+        assert_eq!(info.path(), Some(path));
+        assert_eq!(info.messages(), ["outer"]);
+
+        // IoResultExt::context records the path.
+        let info = Err::<(), _>(io::Error::from(io::ErrorKind::InvalidData))
+            .context(Path::new("b"), "cannot read")
+            .unwrap_err()
+            .corruption_context()
+            .unwrap();
+        assert_eq!(info.path(), Some(Path::new("b")));
+    }
+
+    #[test]
+    fn test_std_error_source() {
+        let e = Error::blank().message("outer");
+        assert!(std::error::Error::source(&e).is_none());
+
+        let e = e
+            .source(Error::blank().message("inner 1"))
+            .source(Error::blank().message("inner 2"));
+        let source = std::error::Error::source(&e).unwrap();
+        assert_eq!(source.to_string(), "inner 1");
+        assert!(source.downcast_ref::<Error>().is_some());
+        assert!(std::error::Error::source(source).is_none());
+
+        let e = Error::blank().source(io::Error::from(io::ErrorKind::NotFound));
+        let source = std::error::Error::source(&e).unwrap();
+        assert_eq!(
+            source.downcast_ref::<io::Error>().map(|e| e.kind()),
+            Some(io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let path = Path::new("a.txt");
+        let io_error = |kind: io::ErrorKind| {
+            Err::<(), _>(io::Error::from(kind))
+                .context(path, "cannot lock")
+                .unwrap_err()
+        };
+
+        assert!(io_error(io::ErrorKind::WouldBlock).is_retryable());
+        assert!(io_error(io::ErrorKind::Interrupted).is_retryable());
+        assert!(io_error(io::ErrorKind::TimedOut).is_retryable());
+        assert!(!io_error(io::ErrorKind::PermissionDenied).is_retryable());
+        assert!(!io_error(io::ErrorKind::NotFound).is_retryable());
+        assert!(!Error::blank().is_retryable());
+        assert!(!Error::programming("invalid parameter").is_retryable());
+        assert!(!Error::corruption(path, "bad checksum").is_retryable());
+
+        // Corruption is never retryable, even if it wraps a retryable error.
+        assert!(!io_error(io::ErrorKind::WouldBlock)
+            .mark_corruption()
+            .is_retryable());
+        assert!(!Error::blank()
+            .source(io_error(io::ErrorKind::Interrupted))
+            .mark_corruption()
+            .is_retryable());
+        assert!(Error::blank()
+            .source(io_error(io::ErrorKind::Interrupted))
+            .is_retryable());
+    }
+
+    #[test]
+    fn test_resource_exhaustion() {
+        let path = Path::new("a.txt");
+        let err = Error::resource("too many open files");
+        assert!(err.is_resource_exhaustion());
+        assert!(!err.is_corruption());
+        assert!(err.is_retryable());
+        assert!(!Error::blank().is_resource_exhaustion());
+        assert!(!Error::corruption(path, "bad checksum").is_resource_exhaustion());
+
+        // Marking as corruption does not override resource exhaustion.
+        assert!(
+            !Error::resource("out of memory")
+                .mark_corruption()
+                .is_corruption()
+        );
+        assert!(
+            !Error::resource("out of memory")
+                .mark_recoverable_corruption()
+                .is_corruption()
+        );
+
+        // Resource exhaustion takes precedence over inherited corruption.
+        let err = Error::blank()
+            .source(Error::corruption(path, "bad checksum"))
+            .source(Error::resource("out of memory"));
+        assert!(err.is_resource_exhaustion());
+        assert!(!err.is_corruption());
+        let err = Error::blank()
+            .source(Error::resource("out of memory"))
+            .source(Error::corruption_recoverable(path, "bad checksum"));
+        assert!(err.is_resource_exhaustion());
+        assert!(!err.is_corruption());
+        let err = Error::corruption(path, "bad checksum").source(Error::resource("out of memory"));
+        assert!(!err.is_corruption());
+        assert!(err.is_retryable());
+
+        // Programming errors are not retryable, even if they were caused by
+        // resource exhaustion.
+        let err = Error::programming("invalid parameter").source(Error::resource("out of memory"));
+        assert!(err.is_resource_exhaustion());
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_resource_exhaustion_io_error_mapping() {
+        let path = Path::new("a.txt");
+        let context = |err: io::Error| Err::<(), _>(err).context(path, "cannot mmap").unwrap_err();
+
+        let err = context(io::Error::from(io::ErrorKind::OutOfMemory));
+        assert!(err.is_resource_exhaustion());
+        assert!(!err.is_corruption());
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert!(err.is_retryable());
+
+        #[cfg(unix)]
+        for code in [
+            libc::ENOMEM,
+            libc::ENOSPC,
+            libc::EDQUOT,
+            libc::EMFILE,
+            libc::ENFILE,
+        ] {
+            let err = context(io::Error::from_raw_os_error(code));
+            assert!(err.is_resource_exhaustion(), "{:?}", err);
+            assert!(!err.is_corruption());
+            // Even when the caller marks it as corruption.
+            let result: Result<()> = Err(err);
+            assert!(!result.corruption().unwrap_err().is_corruption());
+
+            // Raw I/O errors attached as sources are mapped too.
+            let err = Error::blank().source(io::Error::from_raw_os_error(code));
+            assert!(err.is_resource_exhaustion());
+        }
+
+        for kind in [
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound,
+            io::ErrorKind::WouldBlock,
+            io::ErrorKind::InvalidData,
+            io::ErrorKind::UnexpectedEof,
+        ] {
+            assert!(!context(io::Error::from(kind)).is_resource_exhaustion());
+        }
+        assert!(context(io::Error::from(io::ErrorKind::InvalidData)).is_corruption());
+        assert!(
+            !io_result()
+                .context(path, "cannot open")
+                .unwrap_err()
+                .is_resource_exhaustion()
+        );
+    }
+
     fn io_result() -> std::io::Result<()> {
         Err(std::io::Error::new(
             std::io::ErrorKind::Other,
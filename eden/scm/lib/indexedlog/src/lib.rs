@@ -29,7 +29,9 @@ mod repair;
 pub mod rotate;
 pub mod utils;
 
+pub use errors::CorruptionInfo;
 pub use errors::Error;
+pub use errors::ErrorKind;
 pub use errors::Result;
 pub use repair::DefaultOpenOptions;
 pub use repair::OpenWithRepair;
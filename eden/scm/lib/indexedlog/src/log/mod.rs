@@ -1278,7 +1278,7 @@ impl Log {
     ) -> crate::Result<LogMetadata> {
         match path.read_meta() {
             Err(err) => {
-                if err.io_error_kind() == io::ErrorKind::NotFound && create {
+                if err.io_error_kind() == Some(io::ErrorKind::NotFound) && create {
                     let dir = path.as_opt_path().unwrap();
                     // Create (and truncate) the primary log and indexes.
                     let primary_path = dir.join(PRIMARY_FILE);
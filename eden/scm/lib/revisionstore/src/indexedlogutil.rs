@@ -87,7 +87,7 @@ impl Store {
             }
             Store::Shared(log) => {
                 if let Err(err) = log.flush() {
-                    if !err.is_corruption() && err.io_error_kind() == ErrorKind::NotFound {
+                    if !err.is_corruption() && err.io_error_kind() == Some(ErrorKind::NotFound) {
                         // File-not-found errors can happen when the hg cache
                         // was blown away during command execution. Ignore the
                         // error since failed cache writes won't cause incorrect